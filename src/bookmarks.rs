@@ -0,0 +1,888 @@
+//! Named directory bookmarks (`bwd save` / `bwd get`).
+//!
+//! Bookmarks live in a JSON file in the platform config directory, keyed by
+//! name: `{"work":{"path":"/home/me/work","tags":["job"],"hits":3}}`.
+//!
+//! Every `bwd get` writes usage statistics back, so writers go through
+//! `BookmarkStore::update`, which holds the same kind of `.lock` file as the
+//! history for the whole read-modify-write and saves atomically.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tinyjson::JsonValue;
+
+use crate::{BwdError, unix_seconds};
+use crate::toml;
+
+/// A single saved directory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bookmark {
+    pub path: PathBuf,
+    pub tags: Vec<String>,
+    /// Unix timestamp (seconds) after which the bookmark is dropped
+    pub expires: Option<u64>,
+    /// How often `bwd get` has resolved this bookmark
+    pub hits: u64,
+    /// Unix timestamp (seconds) of the last `bwd get`
+    pub last_used: Option<u64>,
+}
+
+impl Bookmark {
+    pub fn new(path: PathBuf) -> Self {
+        Bookmark { path, tags: Vec::new(), expires: None, hits: 0, last_used: None }
+    }
+
+    /// Count one use at `now`.
+    pub fn record_use(&mut self, now: SystemTime) {
+        self.hits += 1;
+        self.last_used = Some(unix_seconds(now));
+    }
+
+    /// Hits weighted by how recently the bookmark was last used, see
+    /// `crate::frecency`. Never used means zero.
+    pub fn frecency(&self, now: SystemTime) -> f64 {
+        self.last_used.map_or(0.0, |last_used| crate::frecency(self.hits, last_used, now))
+    }
+
+    /// Expire the bookmark `ttl` after `now`. A `ttl` reaching past what the
+    /// clock can represent is an error rather than an overflow.
+    pub fn with_ttl(mut self, ttl: Duration, now: SystemTime) -> Result<Self, BwdError> {
+        let expires = now
+            .checked_add(ttl)
+            .ok_or_else(|| BwdError::InvalidValue("--ttl".to_string(), format!("{}s", ttl.as_secs())))?;
+        self.expires = Some(unix_seconds(expires));
+        Ok(self)
+    }
+
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires.is_some_and(|expires| expires <= unix_seconds(now))
+    }
+
+    pub fn with_tags(mut self, tags: &[String]) -> Self {
+        for tag in tags {
+            if !self.tags.contains(tag) {
+                self.tags.push(tag.clone());
+            }
+        }
+        self
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    fn to_json(&self) -> JsonValue {
+        let mut map = HashMap::new();
+        map.insert("path".to_string(), JsonValue::String(self.path.to_string_lossy().to_string()));
+        if !self.tags.is_empty() {
+            map.insert("tags".to_string(), tags_json(&self.tags));
+        }
+        if let Some(expires) = self.expires {
+            map.insert("expires".to_string(), JsonValue::Number(expires as f64));
+        }
+        if self.hits > 0 {
+            map.insert("hits".to_string(), JsonValue::Number(self.hits as f64));
+        }
+        if let Some(last_used) = self.last_used {
+            map.insert("last_used".to_string(), JsonValue::Number(last_used as f64));
+        }
+        JsonValue::Object(map)
+    }
+
+    fn from_json(value: &JsonValue) -> Option<Self> {
+        let JsonValue::Object(map) = value else {
+            return None;
+        };
+        let Some(JsonValue::String(path)) = map.get("path") else {
+            return None;
+        };
+        let mut bookmark = Bookmark::new(PathBuf::from(path));
+        if let Some(JsonValue::Array(tags)) = map.get("tags") {
+            for tag in tags {
+                if let JsonValue::String(tag) = tag {
+                    bookmark.tags.push(tag.clone());
+                }
+            }
+        }
+        if let Some(JsonValue::Number(expires)) = map.get("expires") {
+            bookmark.expires = Some(*expires as u64);
+        }
+        if let Some(JsonValue::Number(hits)) = map.get("hits") {
+            bookmark.hits = *hits as u64;
+        }
+        if let Some(JsonValue::Number(last_used)) = map.get("last_used") {
+            bookmark.last_used = Some(*last_used as u64);
+        }
+        Some(bookmark)
+    }
+}
+
+fn tags_json(tags: &[String]) -> JsonValue {
+    JsonValue::Array(tags.iter().cloned().map(JsonValue::String).collect())
+}
+
+/// The on-disk bookmark database. Names are kept sorted for stable output.
+pub struct BookmarkStore {
+    file: PathBuf,
+    entries: BTreeMap<String, Bookmark>,
+}
+
+impl BookmarkStore {
+    /// Load the store from `file`. A missing file is an empty store.
+    /// Expired bookmarks are dropped on load, and so from disk on the next save.
+    pub fn load(file: &Path) -> Result<Self, BwdError> {
+        let entries = if file.exists() {
+            parse_json(&fs::read_to_string(file)?)
+                .map_err(|_| BwdError::Json(format!("{} is not a bookmark file", file.display())))?
+        } else {
+            BTreeMap::new()
+        };
+        let mut store = BookmarkStore { file: file.to_path_buf(), entries };
+        store.prune_expired(SystemTime::now());
+        Ok(store)
+    }
+
+    /// Drop bookmarks whose TTL has run out, returning how many went.
+    pub fn prune_expired(&mut self, now: SystemTime) -> usize {
+        let before = self.entries.len();
+        self.entries.retain(|_, bookmark| !bookmark.is_expired(now));
+        before - self.entries.len()
+    }
+
+    /// Load the store, apply `change` and save it, all under an exclusive
+    /// lock so that concurrent writers cannot lose each other's updates.
+    /// Nothing is saved if `change` fails.
+    pub fn update<T>(file: &Path, change: impl FnOnce(&mut BookmarkStore) -> Result<T, BwdError>) -> Result<T, BwdError> {
+        crate::with_lock(file, || {
+            let mut store = BookmarkStore::load(file)?;
+            let result = change(&mut store)?;
+            store.save()?;
+            Ok(result)
+        })
+    }
+
+    /// Write the store back, creating the config directory if needed.
+    /// Prefer `update` unless no other process can be writing.
+    pub fn save(&self) -> Result<(), BwdError> {
+        crate::write_atomic(&self.file, &self.to_json()?)
+    }
+
+    /// The whole store in its on-disk JSON form, as used by `bwd export`.
+    pub fn to_json(&self) -> Result<String, BwdError> {
+        let map: HashMap<String, JsonValue> =
+            self.entries.iter().map(|(name, bookmark)| (name.clone(), bookmark.to_json())).collect();
+        JsonValue::Object(map).stringify().map_err(|e| BwdError::Json(format!("{:?}", e)))
+    }
+
+    /// The whole store as TOML, one `[name]` table per bookmark.
+    pub fn to_toml(&self) -> String {
+        let mut out = String::new();
+        for (name, bookmark) in &self.entries {
+            if !out.is_empty() {
+                out.push('\n');
+            }
+            out.push_str(&format!("[{}]\n", toml::key(name)));
+            out.push_str(&format!("path = {}\n", toml::quote(&bookmark.path.to_string_lossy())));
+            if !bookmark.tags.is_empty() {
+                out.push_str(&format!("tags = {}\n", toml::string_array(&bookmark.tags)));
+            }
+            if let Some(expires) = bookmark.expires {
+                out.push_str(&format!("expires = {}\n", expires));
+            }
+            if bookmark.hits > 0 {
+                out.push_str(&format!("hits = {}\n", bookmark.hits));
+            }
+            if let Some(last_used) = bookmark.last_used {
+                out.push_str(&format!("last_used = {}\n", last_used));
+            }
+        }
+        out
+    }
+
+    /// Merge `incoming` bookmarks into the store.
+    ///
+    /// A name that already points to the same path is not a conflict. For real
+    /// conflicts, `policy` decides; with `ConflictPolicy::Error` nothing is
+    /// merged at all.
+    pub fn merge(&mut self, incoming: BTreeMap<String, Bookmark>, policy: ConflictPolicy) -> Result<MergeReport, BwdError> {
+        let conflicts: Vec<&String> = incoming
+            .iter()
+            .filter(|(name, bookmark)| self.entries.get(*name).is_some_and(|existing| existing.path != bookmark.path))
+            .map(|(name, _)| name)
+            .collect();
+        if policy == ConflictPolicy::Error && !conflicts.is_empty() {
+            let names: Vec<&str> = conflicts.iter().map(|name| name.as_str()).collect();
+            return Err(BwdError::BookmarkExists(names.join("', '")));
+        }
+
+        let mut report = MergeReport::default();
+        for (name, bookmark) in incoming {
+            match self.entries.get(&name) {
+                None => report.added += 1,
+                Some(existing) if existing.path == bookmark.path => {
+                    // Same place: just pick up any new tags
+                    let tags = bookmark.tags.clone();
+                    let merged = existing.clone().with_tags(&tags);
+                    self.entries.insert(name, merged);
+                    report.unchanged += 1;
+                    continue;
+                }
+                Some(_) if policy == ConflictPolicy::Keep => {
+                    report.skipped += 1;
+                    continue;
+                }
+                Some(_) => report.replaced += 1,
+            }
+            self.entries.insert(name, bookmark);
+        }
+        Ok(report)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Bookmark> {
+        self.entries.get(name)
+    }
+
+    pub fn insert(&mut self, name: &str, bookmark: Bookmark) {
+        self.entries.insert(name.to_string(), bookmark);
+    }
+
+    /// Like `insert`, but refuses to replace an existing bookmark unless `force` is set.
+    pub fn add(&mut self, name: &str, bookmark: Bookmark, force: bool) -> Result<(), BwdError> {
+        if !force && self.get(name).is_some() {
+            return Err(BwdError::BookmarkExists(name.to_string()));
+        }
+        self.insert(name, bookmark);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<Bookmark, BwdError> {
+        self.entries.remove(name).ok_or_else(|| BwdError::BookmarkNotFound(name.to_string()))
+    }
+
+    /// Rename `old` to `new`. An existing `new` is only replaced with `force`.
+    pub fn rename(&mut self, old: &str, new: &str, force: bool) -> Result<(), BwdError> {
+        if !self.entries.contains_key(old) {
+            return Err(BwdError::BookmarkNotFound(old.to_string()));
+        }
+        if old == new {
+            return Ok(());
+        }
+        if !force && self.entries.contains_key(new) {
+            return Err(BwdError::BookmarkExists(new.to_string()));
+        }
+        let bookmark = self.remove(old)?;
+        self.insert(new, bookmark);
+        Ok(())
+    }
+
+    /// Count a use of `name`, as long as it still points at `path`. Returns
+    /// whether anything was recorded.
+    pub fn record_use(&mut self, name: &str, path: &Path, now: SystemTime) -> bool {
+        match self.entries.get_mut(name) {
+            Some(bookmark) if bookmark.path == path => {
+                bookmark.record_use(now);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Layer `entries` over the stored bookmarks, replacing same-named ones.
+    /// Meant for read-only views; saving afterwards would persist them.
+    pub fn overlay(&mut self, entries: BTreeMap<String, Bookmark>) {
+        self.entries.extend(entries);
+    }
+
+    /// All bookmarks, sorted by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Bookmark)> {
+        self.entries.iter()
+    }
+
+    /// Look up a bookmark by name, tolerating typos and abbreviations.
+    ///
+    /// An exact name always wins. Otherwise a unique prefix match is taken,
+    /// then the unique closest name within a small edit distance (one edit per
+    /// three characters, at least one; swapping two neighbours counts as one).
+    /// Several equally good candidates are reported as ambiguous.
+    pub fn resolve_fuzzy(&self, query: &str) -> Result<(&String, &Bookmark), BwdError> {
+        if let Some(entry) = self.entries.get_key_value(query) {
+            return Ok(entry);
+        }
+
+        let prefixed: Vec<&String> = self.entries.keys().filter(|name| name.starts_with(query)).collect();
+        let candidates = if prefixed.is_empty() {
+            let max_distance = (query.chars().count() / 3).max(1);
+            let scored: Vec<(usize, &String)> = self
+                .entries
+                .keys()
+                .map(|name| (edit_distance(query, name), name))
+                .filter(|(distance, _)| *distance <= max_distance)
+                .collect();
+            let best = scored.iter().map(|(distance, _)| *distance).min();
+            scored.into_iter().filter(|(distance, _)| Some(*distance) == best).map(|(_, name)| name).collect()
+        } else {
+            prefixed
+        };
+
+        match candidates.as_slice() {
+            [] => Err(BwdError::BookmarkNotFound(query.to_string())),
+            [name] => Ok(self.entries.get_key_value(name.as_str()).expect("candidate comes from the store")),
+            many => {
+                let names: Vec<&str> = many.iter().map(|name| name.as_str()).collect();
+                Err(BwdError::AmbiguousBookmark(query.to_string(), names.join(", ")))
+            }
+        }
+    }
+}
+
+/// Optimal string alignment distance: insertions, deletions, substitutions
+/// and transpositions of adjacent characters each cost one.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    rows[0] = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut best = (rows[i - 1][j] + 1).min(rows[i][j - 1] + 1).min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                best = best.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = best;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+/// What `merge` does when an imported name already points somewhere else.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConflictPolicy {
+    /// Keep the existing bookmark
+    Keep,
+    /// Replace it with the imported one
+    Overwrite,
+    /// Refuse the whole import
+    Error,
+}
+
+impl ConflictPolicy {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "keep" => Some(ConflictPolicy::Keep),
+            "overwrite" => Some(ConflictPolicy::Overwrite),
+            "error" => Some(ConflictPolicy::Error),
+            _ => None,
+        }
+    }
+}
+
+/// Ordering for `bwd list`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortOrder {
+    /// Alphabetical by name
+    Name,
+    /// Most used first, see `Bookmark::frecency`
+    Frecency,
+}
+
+impl SortOrder {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "name" => Some(SortOrder::Name),
+            "frecency" => Some(SortOrder::Frecency),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct MergeReport {
+    pub added: usize,
+    pub replaced: usize,
+    pub skipped: usize,
+    pub unchanged: usize,
+}
+
+/// Parse bookmarks in the store's JSON form. Malformed entries are skipped
+/// rather than refusing the whole file.
+pub fn parse_json(content: &str) -> Result<BTreeMap<String, Bookmark>, BwdError> {
+    let json: JsonValue = content.parse().map_err(|e| BwdError::Json(format!("{:?}", e)))?;
+    let JsonValue::Object(map) = json else {
+        return Err(BwdError::Json("bookmarks must be a JSON object".to_string()));
+    };
+    Ok(map
+        .iter()
+        .filter_map(|(name, value)| Bookmark::from_json(value).map(|bookmark| (name.clone(), bookmark)))
+        .collect())
+}
+
+/// Parse bookmarks exported as TOML (`[name]` tables with `path` and `tags`).
+pub fn parse_toml(content: &str) -> Result<BTreeMap<String, Bookmark>, BwdError> {
+    let doc = toml::parse(content)?;
+    let mut entries = BTreeMap::new();
+    for (name, table) in doc.tables {
+        let Some(path) = table.get("path").and_then(|value| value.as_str()) else {
+            return Err(BwdError::Toml(format!("bookmark '{}' has no path", name)));
+        };
+        let tags = table.get("tags").and_then(|value| value.as_string_array()).unwrap_or_default();
+        let mut bookmark = Bookmark::new(PathBuf::from(path)).with_tags(&tags);
+        if let Some(toml::TomlValue::Integer(expires)) = table.get("expires") {
+            bookmark.expires = u64::try_from(*expires).ok();
+        }
+        if let Some(toml::TomlValue::Integer(hits)) = table.get("hits") {
+            bookmark.hits = u64::try_from(*hits).unwrap_or(0);
+        }
+        if let Some(toml::TomlValue::Integer(last_used)) = table.get("last_used") {
+            bookmark.last_used = u64::try_from(*last_used).ok();
+        }
+        entries.insert(name, bookmark);
+    }
+    Ok(entries)
+}
+
+/// Name of the per-project bookmark file at the project root.
+pub const PROJECT_FILE: &str = ".bwd";
+
+/// Project-local bookmarks from `<root>/.bwd`, a TOML file like:
+///
+/// ```toml
+/// [bookmarks]
+/// tests = "crates/core/tests"
+/// assets = "web/assets"
+/// ```
+///
+/// Relative paths are taken relative to `root`. A missing file means none.
+pub fn project_bookmarks(root: &Path) -> Result<BTreeMap<String, Bookmark>, BwdError> {
+    let file = root.join(PROJECT_FILE);
+    if !file.is_file() {
+        return Ok(BTreeMap::new());
+    }
+    let doc = toml::parse(&fs::read_to_string(&file)?)?;
+    let mut entries = BTreeMap::new();
+    if let Some(table) = doc.tables.get("bookmarks") {
+        for (name, value) in table {
+            let Some(path) = value.as_str() else {
+                return Err(BwdError::Toml(format!("{}: bookmark '{}' must be a string", file.display(), name)));
+            };
+            entries.insert(name.clone(), Bookmark::new(root.join(path)));
+        }
+    }
+    Ok(entries)
+}
+
+/// Bookmarks carrying every one of `tags`, or all of them when none are given.
+pub fn filter_by_tags<'a>(store: &'a BookmarkStore, tags: &[String]) -> Vec<(&'a String, &'a Bookmark)> {
+    store.iter().filter(|(_, bookmark)| tags.iter().all(|tag| bookmark.has_tag(tag))).collect()
+}
+
+/// Reorder name-sorted `entries`. Equal scores keep their name order.
+pub fn sort_entries(entries: &mut [(&String, &Bookmark)], order: SortOrder, now: SystemTime) {
+    if order == SortOrder::Frecency {
+        entries.sort_by(|(_, a), (_, b)| b.frecency(now).total_cmp(&a.frecency(now)));
+    }
+}
+
+/// One `name  path  [tags]` line per bookmark, with names padded into a
+/// column. Paths are shortened when `home` is given.
+pub fn format_list(entries: &[(&String, &Bookmark)], home: Option<&Path>) -> String {
+    let width = entries.iter().map(|(name, _)| name.chars().count()).max().unwrap_or(0);
+    entries
+        .iter()
+        .map(|(name, bookmark)| {
+            let line = format!("{:<width$}  {}", name, display_path(&bookmark.path, home));
+            if bookmark.tags.is_empty() {
+                line
+            } else {
+                format!("{}  [{}]", line, bookmark.tags.join(", "))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The bookmarks as a JSON array of `{"name", "path", "tags", "expires",
+/// "hits", "last_used"}` objects.
+pub fn list_json(entries: &[(&String, &Bookmark)], home: Option<&Path>) -> JsonValue {
+    let items = entries
+        .iter()
+        .map(|(name, bookmark)| {
+            let mut map = HashMap::new();
+            map.insert("name".to_string(), JsonValue::String((*name).clone()));
+            map.insert("path".to_string(), JsonValue::String(display_path(&bookmark.path, home)));
+            map.insert("tags".to_string(), tags_json(&bookmark.tags));
+            let expires = bookmark.expires.map_or(JsonValue::Null, |e| JsonValue::Number(e as f64));
+            map.insert("expires".to_string(), expires);
+            map.insert("hits".to_string(), JsonValue::Number(bookmark.hits as f64));
+            let last_used = bookmark.last_used.map_or(JsonValue::Null, |t| JsonValue::Number(t as f64));
+            map.insert("last_used".to_string(), last_used);
+            JsonValue::Object(map)
+        })
+        .collect();
+    JsonValue::Array(items)
+}
+
+fn display_path(path: &Path, home: Option<&Path>) -> String {
+    crate::shorten_path(path, home)
+}
+
+/// Location of the bookmark file inside the bwd config directory.
+pub fn default_store_path() -> Option<PathBuf> {
+    crate::config_dir().map(|dir| dir.join("bookmarks.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+    use std::thread;
+
+    #[test]
+    fn test_store_roundtrip() {
+        let temp_dir = std::env::temp_dir();
+        let test_dir = temp_dir.join(format!("bpwd_test_bookmarks_{}", process::id()));
+        let _ = fs::remove_dir_all(&test_dir);
+        // The config directory does not exist yet; saving must create it
+        let file = test_dir.join("bwd").join("bookmarks.json");
+
+        let mut store = BookmarkStore::load(&file).unwrap();
+        assert!(store.get("work").is_none());
+        store.insert("work", Bookmark::new(PathBuf::from("/home/user/work")));
+        store.save().unwrap();
+
+        let reloaded = BookmarkStore::load(&file).unwrap();
+        assert_eq!(reloaded.get("work").unwrap().path, PathBuf::from("/home/user/work"));
+        assert!(matches!(reloaded.resolve_fuzzy("play"), Err(BwdError::BookmarkNotFound(_))));
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_dir);
+    }
+
+    #[test]
+    fn test_list_text_and_json() {
+        let file = std::env::temp_dir().join(format!("bpwd_test_bookmarks_list_{}.json", process::id()));
+        let mut store = BookmarkStore::load(&file).unwrap();
+        store.insert("work", Bookmark::new(PathBuf::from("/home/user/work")));
+        store.insert("api", Bookmark::new(PathBuf::from("/srv/api")));
+
+        let entries = filter_by_tags(&store, &[]);
+        let text = format_list(&entries, None);
+        assert_eq!(text, "api   /srv/api\nwork  /home/user/work");
+
+        let home = PathBuf::from("/home/user");
+        let JsonValue::Array(items) = list_json(&entries, Some(&home)) else {
+            panic!("bookmark list JSON should be an array");
+        };
+        assert_eq!(items.len(), 2);
+        let JsonValue::Object(work) = &items[1] else {
+            panic!("bookmark entries should be objects");
+        };
+        assert_eq!(work.get("name"), Some(&JsonValue::String("work".to_string())));
+        let short = PathBuf::from("$HOME").join("work").to_string_lossy().to_string();
+        assert_eq!(work.get("path"), Some(&JsonValue::String(short)));
+    }
+
+    #[test]
+    fn test_add_overwrite_protection() {
+        let file = std::env::temp_dir().join(format!("bpwd_test_bookmarks_add_{}.json", process::id()));
+        let mut store = BookmarkStore::load(&file).unwrap();
+        store.add("work", Bookmark::new(PathBuf::from("/a")), false).unwrap();
+        assert!(matches!(
+            store.add("work", Bookmark::new(PathBuf::from("/b")), false),
+            Err(BwdError::BookmarkExists(_))
+        ));
+        assert_eq!(store.get("work").unwrap().path, PathBuf::from("/a"));
+
+        store.add("work", Bookmark::new(PathBuf::from("/b")), true).unwrap();
+        assert_eq!(store.get("work").unwrap().path, PathBuf::from("/b"));
+    }
+
+    #[test]
+    fn test_remove_and_rename() {
+        let file = std::env::temp_dir().join(format!("bpwd_test_bookmarks_mv_{}.json", process::id()));
+        let mut store = BookmarkStore::load(&file).unwrap();
+        store.insert("old", Bookmark::new(PathBuf::from("/a")));
+        store.insert("taken", Bookmark::new(PathBuf::from("/b")));
+
+        assert!(matches!(store.rename("old", "taken", false), Err(BwdError::BookmarkExists(_))));
+        store.rename("old", "new", false).unwrap();
+        assert!(store.get("old").is_none());
+        assert_eq!(store.get("new").unwrap().path, PathBuf::from("/a"));
+
+        store.rename("new", "taken", true).unwrap();
+        assert_eq!(store.get("taken").unwrap().path, PathBuf::from("/a"));
+
+        assert_eq!(store.remove("taken").unwrap().path, PathBuf::from("/a"));
+        assert!(matches!(store.remove("taken"), Err(BwdError::BookmarkNotFound(_))));
+        assert!(matches!(store.rename("ghost", "x", false), Err(BwdError::BookmarkNotFound(_))));
+    }
+
+    #[test]
+    fn test_tags_filter_and_roundtrip() {
+        let temp_dir = std::env::temp_dir();
+        let file = temp_dir.join(format!("bpwd_test_bookmarks_tags_{}.json", process::id()));
+        let _ = fs::remove_file(&file);
+        let backend = vec!["backend".to_string(), "backend".to_string()];
+
+        let mut store = BookmarkStore::load(&file).unwrap();
+        store.insert("api", Bookmark::new(PathBuf::from("/srv/api")).with_tags(&backend));
+        store.insert("site", Bookmark::new(PathBuf::from("/srv/site")));
+        store.save().unwrap();
+
+        let store = BookmarkStore::load(&file).unwrap();
+        // Duplicate tags are collapsed
+        assert_eq!(store.get("api").unwrap().tags, vec!["backend".to_string()]);
+
+        let tagged = filter_by_tags(&store, &["backend".to_string()]);
+        assert_eq!(tagged.len(), 1);
+        assert_eq!(tagged[0].0, "api");
+        assert_eq!(format_list(&tagged, None), "api  /srv/api  [backend]");
+        assert_eq!(filter_by_tags(&store, &[]).len(), 2);
+        // Repeated tags all have to match
+        assert!(filter_by_tags(&store, &["backend".to_string(), "prod".to_string()]).is_empty());
+
+        // Cleanup
+        let _ = fs::remove_file(&file);
+    }
+
+    fn sample_store(name: &str) -> BookmarkStore {
+        let file = std::env::temp_dir().join(format!("bpwd_test_bookmarks_{}_{}.json", name, process::id()));
+        let mut store = BookmarkStore::load(&file).unwrap();
+        store.insert("work", Bookmark::new(PathBuf::from("/home/user/work")).with_tags(&["job".to_string()]));
+        store.insert("my \"odd\" name", Bookmark::new(PathBuf::from(r"C:\odd path")));
+        let mut scratch = Bookmark::new(PathBuf::from("/tmp/scratch"));
+        scratch.expires = Some(4_102_444_800);
+        scratch.hits = 7;
+        scratch.last_used = Some(1_700_000_000);
+        store.insert("scratch", scratch);
+        store
+    }
+
+    #[test]
+    fn test_export_import_roundtrip() {
+        let store = sample_store("export");
+        let from_json = parse_json(&store.to_json().unwrap()).unwrap();
+        let from_toml = parse_toml(&store.to_toml()).unwrap();
+        assert_eq!(from_json, store.entries);
+        assert_eq!(from_toml, store.entries);
+    }
+
+    #[test]
+    fn test_merge_conflict_policies() {
+        let incoming = || {
+            let mut entries = BTreeMap::new();
+            entries.insert("work".to_string(), Bookmark::new(PathBuf::from("/elsewhere")));
+            entries.insert("new".to_string(), Bookmark::new(PathBuf::from("/new")));
+            entries
+        };
+
+        let mut store = sample_store("merge_error");
+        assert!(matches!(store.merge(incoming(), ConflictPolicy::Error), Err(BwdError::BookmarkExists(_))));
+        assert!(store.get("new").is_none(), "a refused import must not merge anything");
+
+        let mut store = sample_store("merge_keep");
+        let report = store.merge(incoming(), ConflictPolicy::Keep).unwrap();
+        assert_eq!(report, MergeReport { added: 1, replaced: 0, skipped: 1, unchanged: 0 });
+        assert_eq!(store.get("work").unwrap().path, PathBuf::from("/home/user/work"));
+
+        let mut store = sample_store("merge_overwrite");
+        let report = store.merge(incoming(), ConflictPolicy::Overwrite).unwrap();
+        assert_eq!(report, MergeReport { added: 1, replaced: 1, skipped: 0, unchanged: 0 });
+        assert_eq!(store.get("work").unwrap().path, PathBuf::from("/elsewhere"));
+
+        // Same path is never a conflict, even under the strictest policy
+        let mut store = sample_store("merge_same");
+        let mut same = BTreeMap::new();
+        same.insert("work".to_string(), Bookmark::new(PathBuf::from("/home/user/work")));
+        let report = store.merge(same, ConflictPolicy::Error).unwrap();
+        assert_eq!(report.unchanged, 1);
+        assert_eq!(store.get("work").unwrap().tags, vec!["job".to_string()]);
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("work", "work"), 0);
+        assert_eq!(edit_distance("wokr", "work"), 1);
+        assert_eq!(edit_distance("wrk", "work"), 1);
+        assert_eq!(edit_distance("api", "work"), 4);
+    }
+
+    #[test]
+    fn test_resolve_fuzzy() {
+        let file = std::env::temp_dir().join(format!("bpwd_test_bookmarks_fuzzy_{}.json", process::id()));
+        let mut store = BookmarkStore::load(&file).unwrap();
+        store.insert("work", Bookmark::new(PathBuf::from("/work")));
+        store.insert("world", Bookmark::new(PathBuf::from("/world")));
+        store.insert("api", Bookmark::new(PathBuf::from("/api")));
+        store.insert("apis", Bookmark::new(PathBuf::from("/apis")));
+
+        // Typo with a unique closest match
+        assert_eq!(store.resolve_fuzzy("wokr").unwrap().0, "work");
+        // Unique prefix
+        assert_eq!(store.resolve_fuzzy("worl").unwrap().0, "world");
+        // Exact beats prefix
+        assert_eq!(store.resolve_fuzzy("api").unwrap().0, "api");
+        // Shared prefix is ambiguous and lists the candidates
+        match store.resolve_fuzzy("wor") {
+            Err(BwdError::AmbiguousBookmark(query, candidates)) => {
+                assert_eq!(query, "wor");
+                assert_eq!(candidates, "work, world");
+            }
+            _ => panic!("expected an ambiguous match"),
+        }
+        assert!(matches!(store.resolve_fuzzy("zzzz"), Err(BwdError::BookmarkNotFound(_))));
+    }
+
+    #[test]
+    fn test_project_bookmarks_overlay() {
+        let temp_dir = std::env::temp_dir();
+        let root = temp_dir.join(format!("bpwd_test_project_bookmarks_{}", process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        assert!(project_bookmarks(&root).unwrap().is_empty());
+
+        fs::write(root.join(PROJECT_FILE), "[bookmarks]\ntests = \"crates/core/tests\"\n").unwrap();
+        let local = project_bookmarks(&root).unwrap();
+        assert_eq!(local["tests"].path, root.join("crates/core/tests"));
+
+        // Project bookmarks shadow global ones of the same name
+        let mut store = BookmarkStore::load(&root.join("global.json")).unwrap();
+        store.insert("tests", Bookmark::new(PathBuf::from("/global/tests")));
+        store.insert("work", Bookmark::new(PathBuf::from("/work")));
+        store.overlay(local);
+        assert_eq!(store.resolve_fuzzy("tests").unwrap().1.path, root.join("crates/core/tests"));
+        assert_eq!(store.resolve_fuzzy("work").unwrap().1.path, PathBuf::from("/work"));
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_ttl_expiry() {
+        let temp_dir = std::env::temp_dir();
+        let file = temp_dir.join(format!("bpwd_test_bookmarks_ttl_{}.json", process::id()));
+        let _ = fs::remove_file(&file);
+        let now = SystemTime::now();
+
+        let mut store = BookmarkStore::load(&file).unwrap();
+        store.insert("keep", Bookmark::new(PathBuf::from("/keep")));
+        store.insert("scratch", Bookmark::new(PathBuf::from("/tmp/x")).with_ttl(Duration::from_secs(7200), now).unwrap());
+        assert_eq!(store.prune_expired(now), 0);
+        assert_eq!(store.prune_expired(now + Duration::from_secs(7201)), 1);
+        assert!(store.get("scratch").is_none());
+        assert!(store.get("keep").is_some());
+
+        // Already expired entries disappear when the file is loaded
+        store.insert("old", Bookmark::new(PathBuf::from("/old")).with_ttl(Duration::ZERO, now - Duration::from_secs(5)).unwrap());
+        store.save().unwrap();
+        let reloaded = BookmarkStore::load(&file).unwrap();
+        assert!(reloaded.get("old").is_none());
+        assert!(reloaded.get("keep").is_some());
+
+        // Cleanup
+        let _ = fs::remove_file(&file);
+    }
+
+    #[test]
+    fn test_huge_ttl_is_rejected() {
+        let now = SystemTime::now();
+        // The largest `--ttl` that parses, in seconds
+        let huge = Duration::from_secs(u64::MAX);
+        assert!(matches!(
+            Bookmark::new(PathBuf::from("/tmp/x")).with_ttl(huge, now),
+            Err(BwdError::InvalidValue(flag, _)) if flag == "--ttl"
+        ));
+    }
+
+    #[test]
+    fn test_usage_stats_and_frecency_sort() {
+        let file = std::env::temp_dir().join(format!("bpwd_test_bookmarks_usage_{}.json", process::id()));
+        let mut store = BookmarkStore::load(&file).unwrap();
+        store.insert("api", Bookmark::new(PathBuf::from("/srv/api")));
+        store.insert("old", Bookmark::new(PathBuf::from("/srv/old")));
+        store.insert("work", Bookmark::new(PathBuf::from("/home/user/work")));
+        let now = SystemTime::now();
+        let last_month = now - Duration::from_secs(30 * 86_400);
+
+        // Many hits long ago lose to a few recent ones
+        for _ in 0..5 {
+            assert!(store.record_use("old", Path::new("/srv/old"), last_month));
+        }
+        for _ in 0..2 {
+            assert!(store.record_use("work", Path::new("/home/user/work"), now));
+        }
+        // A name that now points elsewhere (e.g. shadowed by a project file) is not counted
+        assert!(!store.record_use("api", Path::new("/elsewhere"), now));
+        assert_eq!(store.get("work").unwrap().hits, 2);
+
+        let mut entries = filter_by_tags(&store, &[]);
+        sort_entries(&mut entries, SortOrder::Frecency, now);
+        let names: Vec<&str> = entries.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["work", "old", "api"]);
+
+        let JsonValue::Array(items) = list_json(&entries, None) else {
+            panic!("bookmark list JSON should be an array");
+        };
+        let JsonValue::Object(work) = &items[0] else {
+            panic!("bookmark entries should be objects");
+        };
+        assert_eq!(work.get("hits"), Some(&JsonValue::Number(2.0)));
+        assert!(matches!(work.get("last_used"), Some(JsonValue::Number(_))));
+    }
+
+    #[test]
+    fn test_concurrent_updates_are_not_lost() {
+        let temp_dir = std::env::temp_dir();
+        let test_dir = temp_dir.join(format!("bpwd_test_bookmarks_lock_{}", process::id()));
+        let _ = fs::remove_dir_all(&test_dir);
+        let file = test_dir.join("bookmarks.json");
+        BookmarkStore::update(&file, |store| {
+            store.insert("work", Bookmark::new(PathBuf::from("/home/user/work")));
+            Ok(())
+        })
+        .unwrap();
+
+        let writers: Vec<_> = (0..8)
+            .map(|_| {
+                let file = file.clone();
+                thread::spawn(move || {
+                    for _ in 0..10 {
+                        BookmarkStore::update(&file, |store| {
+                            Ok(store.record_use("work", Path::new("/home/user/work"), SystemTime::now()))
+                        })
+                        .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        assert_eq!(BookmarkStore::load(&file).unwrap().get("work").unwrap().hits, 80);
+
+        // A failed change leaves the file alone
+        let removed = BookmarkStore::update(&file, |store| {
+            store.remove("work")?;
+            store.remove("missing")
+        });
+        assert!(matches!(removed, Err(BwdError::BookmarkNotFound(_))));
+        assert!(BookmarkStore::load(&file).unwrap().get("work").is_some());
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_dir);
+    }
+
+    #[test]
+    fn test_load_rejects_non_object() {
+        let temp_dir = std::env::temp_dir();
+        let file = temp_dir.join(format!("bpwd_test_bookmarks_bad_{}.json", process::id()));
+        fs::write(&file, "[1, 2]").unwrap();
+        assert!(matches!(BookmarkStore::load(&file), Err(BwdError::Json(_))));
+        let _ = fs::remove_file(&file);
+    }
+}
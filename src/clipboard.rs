@@ -0,0 +1,361 @@
+//! Where `-c` sends its text.
+//!
+//! The system clipboard goes through `cli_clipboard`, which needs a display
+//! server on the machine bwd runs on. Builds without the `clipboard` feature
+//! (WASI, say) have no system clipboard and fall back to the others. Over
+//! SSH, or in a container without one, the OSC 52 escape sequence asks the
+//! terminal emulator to set its own clipboard instead, wherever that
+//! terminal runs. Inside tmux the sequence is wrapped so tmux passes it
+//! through to the outer terminal.
+//!
+//! A `[clipboard] command` in the settings replaces `cli_clipboard` with an
+//! external program such as `wl-copy` or `clip.exe`.
+//!
+//! The X11 primary selection (middle-click paste) is set through `xclip` or
+//! `xsel`, or `wl-copy --primary` under Wayland.
+//!
+//! `--copy=file` puts the directories themselves on the clipboard rather than
+//! their paths, so pasting into Explorer, Finder or a file manager reaches
+//! the directory: a file drop list (`CF_HDROP`) set by PowerShell on
+//! Windows, a file reference set by AppleScript on macOS, and a
+//! `text/uri-list` elsewhere.
+//!
+//! When the chosen clipboard fails, OSC 52 and then the configured command
+//! are tried before giving up.
+
+use std::env;
+use std::fs::OpenOptions;
+use std::io::{self, IsTerminal, Write};
+use std::process::{self, Stdio};
+
+use crate::BwdError;
+
+/// A trait so tests can swap in a fake.
+pub trait Clipboard {
+    fn set_contents(&mut self, contents: String) -> Result<(), BwdError>;
+}
+
+/// The OS clipboard via `cli_clipboard`.
+pub struct SystemClipboard;
+
+#[cfg(feature = "clipboard")]
+impl Clipboard for SystemClipboard {
+    fn set_contents(&mut self, contents: String) -> Result<(), BwdError> {
+        cli_clipboard::set_contents(contents).map_err(|e| BwdError::Clipboard(e.to_string()))
+    }
+}
+
+#[cfg(not(feature = "clipboard"))]
+impl Clipboard for SystemClipboard {
+    fn set_contents(&mut self, _contents: String) -> Result<(), BwdError> {
+        Err(no_system_clipboard())
+    }
+}
+
+/// The text on the OS clipboard, for `--paste`.
+#[cfg(feature = "clipboard")]
+pub fn get_contents() -> Result<String, BwdError> {
+    cli_clipboard::get_contents().map_err(|e| BwdError::Clipboard(e.to_string()))
+}
+
+#[cfg(not(feature = "clipboard"))]
+pub fn get_contents() -> Result<String, BwdError> {
+    Err(no_system_clipboard())
+}
+
+#[cfg(not(feature = "clipboard"))]
+fn no_system_clipboard() -> BwdError {
+    BwdError::Clipboard("bwd was built without the clipboard feature".to_string())
+}
+
+/// The terminal's clipboard, through an OSC 52 escape sequence.
+pub struct Osc52;
+
+impl Clipboard for Osc52 {
+    fn set_contents(&mut self, contents: String) -> Result<(), BwdError> {
+        let sequence = osc52_sequence(&contents, env::var_os("TMUX").is_some());
+        // Stdout is often captured (`cd "$(bwd -c)"`), so talk to the terminal directly
+        let written = match OpenOptions::new().write(true).open("/dev/tty") {
+            Ok(mut tty) => tty.write_all(sequence.as_bytes()),
+            Err(_) if io::stderr().is_terminal() => io::stderr().write_all(sequence.as_bytes()),
+            Err(_) => return Err(BwdError::Clipboard("no terminal to send OSC 52 to".to_string())),
+        };
+        written.map_err(|e| BwdError::Clipboard(e.to_string()))
+    }
+}
+
+/// An external program reading the text on stdin, such as `wl-copy`,
+/// `xclip -sel clip` or `clip.exe`. The command line is split on whitespace.
+pub struct CommandClipboard<'a>(pub &'a str);
+
+impl Clipboard for CommandClipboard<'_> {
+    fn set_contents(&mut self, contents: String) -> Result<(), BwdError> {
+        let words: Vec<&str> = self.0.split_whitespace().collect();
+        let Some((program, args)) = words.split_first() else {
+            return Err(BwdError::Clipboard("the clipboard command is empty".to_string()));
+        };
+        pipe_to(program, args, &contents)
+    }
+}
+
+/// Run `program` with `contents` on its stdin.
+fn pipe_to(program: &str, args: &[&str], contents: &str) -> Result<(), BwdError> {
+    let error = |e: io::Error| BwdError::Clipboard(format!("{}: {}", program, e));
+    // Stdout is not inherited: `xclip` lingers to serve the selection, and
+    // would keep `$(bwd -c)` waiting for it to close
+    let mut child = process::Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .map_err(error)?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // A program that exits without reading everything is judged by its
+        // exit status, not by the broken pipe
+        match stdin.write_all(contents.as_bytes()) {
+            Err(e) if e.kind() != io::ErrorKind::BrokenPipe => return Err(error(e)),
+            _ => {}
+        }
+    }
+    let status = child.wait().map_err(error)?;
+    if !status.success() {
+        return Err(BwdError::Clipboard(format!("{} exited with {}", program, status)));
+    }
+    Ok(())
+}
+
+/// The paths on `contents`' lines as file objects, for pasting into a file
+/// manager. Finder takes a single file reference, so macOS gets the first.
+fn copy_file_references(contents: &str) -> Result<(), BwdError> {
+    let paths: Vec<&str> = contents.lines().filter(|line| !line.is_empty()).collect();
+    if cfg!(windows) {
+        // `$input` enumerates the lines piped to PowerShell
+        pipe_to("powershell", &["-NoProfile", "-NonInteractive", "-Command", "Set-Clipboard -LiteralPath @($input)"], contents)
+    } else if cfg!(target_os = "macos") {
+        let path = paths.first().copied().unwrap_or_default();
+        let script = ["-e", "on run argv", "-e", "set the clipboard to (POSIX file (item 1 of argv))", "-e", "end run", path];
+        pipe_to("osascript", &script, "")
+    } else {
+        let uris: Vec<String> = paths.iter().map(|path| format!("{}\r\n", crate::file_uri(path, false))).collect();
+        let uris = uris.concat();
+        if env::var_os("WAYLAND_DISPLAY").is_some() {
+            pipe_to("wl-copy", &["--type", "text/uri-list"], &uris)
+        } else {
+            pipe_to("xclip", &["-selection", "clipboard", "-t", "text/uri-list"], &uris)
+        }
+    }
+}
+
+/// Programs setting the primary selection, in the order tried.
+fn primary_commands() -> Vec<&'static str> {
+    let mut commands = Vec::new();
+    if env::var_os("WAYLAND_DISPLAY").is_some() {
+        commands.push("wl-copy --primary");
+    }
+    commands.extend(["xclip -selection primary", "xsel --primary --input"]);
+    commands
+}
+
+/// Which clipboard `-c` uses.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Backend {
+    /// OSC 52 in an SSH session, otherwise the system clipboard
+    Auto,
+    System,
+    Osc52,
+    /// tmux's paste buffer, for `prefix ]`
+    Tmux,
+    /// The X11 primary selection, pasted with the middle button
+    Primary,
+    /// The directories as file objects rather than text
+    File,
+    /// The configured `[clipboard] command`
+    Command(String),
+}
+
+impl Backend {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "auto" => Some(Backend::Auto),
+            "system" => Some(Backend::System),
+            "osc52" => Some(Backend::Osc52),
+            "tmux" => Some(Backend::Tmux),
+            "primary" => Some(Backend::Primary),
+            "file" => Some(Backend::File),
+            _ => None,
+        }
+    }
+
+    /// `Auto` made concrete.
+    fn resolve(self) -> Self {
+        match self {
+            // Over SSH the system clipboard, if it works at all, is the remote machine's
+            Backend::Auto if is_remote() => Backend::Osc52,
+            Backend::Auto => Backend::System,
+            backend => backend,
+        }
+    }
+
+    fn label(&self) -> &str {
+        match self {
+            Backend::Auto => "auto",
+            Backend::System => "system",
+            Backend::Osc52 => "osc52",
+            Backend::Tmux => "tmux",
+            Backend::Primary => "primary",
+            Backend::File => "file",
+            Backend::Command(command) => command,
+        }
+    }
+}
+
+impl Clipboard for Backend {
+    fn set_contents(&mut self, contents: String) -> Result<(), BwdError> {
+        match self.clone().resolve() {
+            Backend::Osc52 => Osc52.set_contents(contents),
+            // `-` reads the buffer from stdin
+            Backend::Tmux => CommandClipboard("tmux load-buffer -").set_contents(contents),
+            Backend::Primary => {
+                let mut result = Ok(());
+                for command in primary_commands() {
+                    result = CommandClipboard(command).set_contents(contents.clone());
+                    if result.is_ok() {
+                        break;
+                    }
+                }
+                result
+            }
+            Backend::File => copy_file_references(&contents),
+            Backend::Command(command) => CommandClipboard(&command).set_contents(contents),
+            _ => SystemClipboard.set_contents(contents),
+        }
+    }
+}
+
+/// Clipboards tried in order until one takes the text.
+#[derive(Debug, PartialEq)]
+pub struct Fallback(pub Vec<Backend>);
+
+impl Fallback {
+    /// `primary`, then OSC 52, then the configured `command`, each tried once.
+    pub fn new(primary: Backend, command: Option<String>) -> Self {
+        let mut chain: Vec<Backend> = Vec::new();
+        for backend in [Some(primary.resolve()), Some(Backend::Osc52), command.map(Backend::Command)].into_iter().flatten() {
+            if !chain.contains(&backend) {
+                chain.push(backend);
+            }
+        }
+        Fallback(chain)
+    }
+}
+
+impl Clipboard for Fallback {
+    /// The error, if every clipboard failed, lists why each one did.
+    fn set_contents(&mut self, contents: String) -> Result<(), BwdError> {
+        let mut failures = Vec::new();
+        for backend in &mut self.0 {
+            match backend.set_contents(contents.clone()) {
+                Ok(()) => return Ok(()),
+                Err(BwdError::Clipboard(message)) => failures.push(format!("{}: {}", backend.label(), message)),
+                Err(err) => failures.push(format!("{}: {}", backend.label(), err)),
+            }
+        }
+        Err(BwdError::Clipboard(failures.join("; ")))
+    }
+}
+
+fn is_remote() -> bool {
+    env::var_os("SSH_TTY").is_some() || env::var_os("SSH_CONNECTION").is_some()
+}
+
+/// The OSC 52 sequence setting the clipboard to `text`, wrapped in tmux's
+/// DCS passthrough when `tmux` is set.
+pub fn osc52_sequence(text: &str, tmux: bool) -> String {
+    let osc = format!("\x1b]52;c;{}\x07", base64(text.as_bytes()));
+    if tmux {
+        // Escapes inside the passthrough are doubled
+        format!("\x1bPtmux;{}\x1b\\", osc.replace('\x1b', "\x1b\x1b"))
+    } else {
+        osc
+    }
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64("/home/é".as_bytes()), "L2hvbWUvw6k=");
+    }
+
+    #[test]
+    fn test_osc52_sequence() {
+        assert_eq!(osc52_sequence("/tmp", false), "\x1b]52;c;L3RtcA==\x07");
+        assert_eq!(osc52_sequence("/tmp", true), "\x1bPtmux;\x1b\x1b]52;c;L3RtcA==\x07\x1b\\");
+        assert_eq!(Backend::from_name("osc52"), Some(Backend::Osc52));
+        assert_eq!(Backend::from_name("tmux"), Some(Backend::Tmux));
+        assert_eq!(Backend::from_name("primary"), Some(Backend::Primary));
+        assert_eq!(Backend::from_name("file"), Some(Backend::File));
+        assert_eq!(Backend::from_name("x11"), None);
+    }
+
+    #[test]
+    fn test_fallback_chain() {
+        let chain = Fallback::new(Backend::System, Some("wl-copy".to_string()));
+        assert_eq!(chain.0, vec![Backend::System, Backend::Osc52, Backend::Command("wl-copy".to_string())]);
+        // Each clipboard is tried once
+        let chain = Fallback::new(Backend::Command("wl-copy".to_string()), Some("wl-copy".to_string()));
+        assert_eq!(chain.0, vec![Backend::Command("wl-copy".to_string()), Backend::Osc52]);
+        assert_eq!(Fallback::new(Backend::Osc52, None).0, vec![Backend::Osc52]);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_fallback_reports_every_failure() {
+        let mut chain = Fallback(vec![Backend::Command("false".to_string()), Backend::Command("true".to_string())]);
+        assert!(chain.set_contents("/srv".to_string()).is_ok());
+        let mut chain = Fallback(vec![Backend::Command("false".to_string()), Backend::Command("bpwd-no-such-program".to_string())]);
+        let Err(BwdError::Clipboard(message)) = chain.set_contents("/srv".to_string()) else {
+            panic!("expected a clipboard error");
+        };
+        assert!(message.starts_with("false: false exited with"));
+        assert!(message.contains("; bpwd-no-such-program: "));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_command_clipboard() {
+        let temp_dir = std::env::temp_dir();
+        let file = temp_dir.join(format!("bpwd_test_clipboard_command_{}", std::process::id()));
+        let command = format!("tee {}", file.to_string_lossy());
+        CommandClipboard(&command).set_contents("/srv/app".to_string()).unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "/srv/app");
+        assert!(CommandClipboard("false").set_contents(String::new()).is_err());
+        assert!(CommandClipboard("bpwd-no-such-program").set_contents(String::new()).is_err());
+        assert!(CommandClipboard("  ").set_contents(String::new()).is_err());
+
+        // Cleanup
+        let _ = std::fs::remove_file(&file);
+    }
+}
@@ -0,0 +1,148 @@
+//! ANSI colors for human output, themed from `config.toml`:
+//!
+//! ```toml
+//! [color]
+//! mode = "auto"       # or "always", "never" (the default)
+//! home = "2"          # SGR codes: dim
+//! root = "1;34"       # bold blue
+//! basename = "1"      # bold
+//! ```
+
+use std::env;
+use std::path::{MAIN_SEPARATOR, Path};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorMode {
+    /// Color when stdout is a terminal and `NO_COLOR` is not set
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "auto" => Some(ColorMode::Auto),
+            "always" => Some(ColorMode::Always),
+            "never" => Some(ColorMode::Never),
+            _ => None,
+        }
+    }
+
+    pub fn enabled(self, is_terminal: bool) -> bool {
+        match self {
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+            ColorMode::Auto => is_terminal && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()),
+        }
+    }
+}
+
+/// SGR parameters for each part of a path.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    /// The home prefix (`$HOME`, `~` or the directory itself)
+    pub home: String,
+    /// The project root's component, and the project name in `bwd prompt`
+    pub root: String,
+    /// The last component
+    pub basename: String,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme { home: "2".to_string(), root: "1;34".to_string(), basename: "1".to_string() }
+    }
+}
+
+/// Wrap `text` in the SGR sequence `code` and a reset.
+pub fn paint(code: &str, text: &str) -> String {
+    paint_with(code, text, str::to_string)
+}
+
+/// [`paint`], with both escape sequences passed through `wrap`, as a
+/// prompt needs them marked for its shell.
+pub fn paint_with(code: &str, text: &str, wrap: impl Fn(&str) -> String) -> String {
+    format!("{}{}{}", wrap(&format!("\x1b[{}m", code)), text, wrap("\x1b[0m"))
+}
+
+/// Color a printed path: dim the home prefix, highlight the project root's
+/// component and color the basename. `home_prefixes` are the spellings of
+/// home that may start `text` and `roots` those of the root directory; the
+/// first match wins. Anything not recognized is left plain.
+pub fn colorize_path(text: &str, home_prefixes: &[String], roots: &[String], theme: &Theme) -> String {
+    let is_boundary = |end: usize| end == text.len() || text[end..].starts_with(['/', '\\', MAIN_SEPARATOR]);
+    let matching_prefix =
+        |prefixes: &[String]| prefixes.iter().find(|p| !p.is_empty() && text.starts_with(p.as_str()) && is_boundary(p.len())).map(String::len);
+
+    let home_end = matching_prefix(home_prefixes).unwrap_or(0);
+    let root_range = matching_prefix(roots).map(|end| {
+        let start = text[..end].trim_end_matches(['/', '\\']).rfind(['/', '\\']).map_or(0, |i| i + 1);
+        (start.max(home_end), end)
+    });
+    let base_start = text.trim_end_matches(['/', '\\']).rfind(['/', '\\']).map_or(0, |i| i + 1);
+
+    let mut spans: Vec<(usize, usize, &str)> = Vec::new();
+    if home_end > 0 {
+        spans.push((0, home_end, &theme.home));
+    }
+    if let Some((start, end)) = root_range.filter(|(start, end)| start < end) {
+        spans.push((start, end, &theme.root));
+    }
+    let last_end = spans.last().map_or(0, |span| span.1);
+    if base_start >= last_end && base_start < text.len() {
+        spans.push((base_start, text.len(), &theme.basename));
+    }
+
+    let mut out = String::with_capacity(text.len() + spans.len() * 12);
+    let mut at = 0;
+    for (start, end, code) in spans {
+        out.push_str(&text[at..start]);
+        out.push_str(&paint(code, &text[start..end]));
+        at = end;
+    }
+    out.push_str(&text[at..]);
+    out
+}
+
+/// The ways `dir` may appear at the start of printed output: as is, and
+/// with the home directory spelled `$HOME` or `~`.
+pub fn spellings(dir: &Path, home: Option<&Path>) -> Vec<String> {
+    let mut spellings = vec![dir.to_string_lossy().to_string()];
+    if let Some(below) = home.and_then(|home| dir.strip_prefix(home).ok()) {
+        for token in ["$HOME", "~"] {
+            let spelled = if below.as_os_str().is_empty() { Path::new(token).to_path_buf() } else { Path::new(token).join(below) };
+            spellings.push(spelled.to_string_lossy().to_string());
+        }
+    }
+    spellings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_colorize_path() {
+        let theme = Theme::default();
+        let home = Path::new("/home/me");
+        let root = Path::new("/home/me/code/bpwd");
+        let colorize = |text: &str| colorize_path(text, &spellings(home, Some(home)), &spellings(root, Some(home)), &theme);
+
+        assert_eq!(
+            colorize("$HOME/code/bpwd/src"),
+            format!("{}/code/{}/{}", paint("2", "$HOME"), paint("1;34", "bpwd"), paint("1", "src"))
+        );
+        // At the root itself the root color wins over the basename's
+        assert_eq!(colorize("/home/me/code/bpwd"), format!("{}/code/{}", paint("2", "/home/me"), paint("1;34", "bpwd")));
+        // A relative path only has a basename
+        assert_eq!(colorize("src/api"), format!("src/{}", paint("1", "api")));
+        // `/home/meow` is not under `/home/me`
+        assert_eq!(colorize("/home/meow"), format!("/home/{}", paint("1", "meow")));
+
+        assert!(ColorMode::Always.enabled(false));
+        assert!(!ColorMode::Never.enabled(true));
+        assert!(!ColorMode::Auto.enabled(false));
+        assert_eq!(ColorMode::from_name("sometimes"), None);
+    }
+}
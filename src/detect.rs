@@ -0,0 +1,153 @@
+//! Pluggable project root detection for [`Resolver`](crate::Resolver).
+//!
+//! A [`RootDetector`] names the root for a path, or passes. Detectors
+//! registered with [`Resolver::detector`](crate::Resolver::detector) are
+//! asked in order before the resolver's [`RootSearch`], so a build system
+//! bwd knows nothing about can claim its workspaces:
+//!
+//! ```
+//! use bpwd::Resolver;
+//! use bpwd::detect::{EnvDetector, MarkerDetector};
+//!
+//! let resolver = Resolver::new()
+//!     .detector(EnvDetector::new("BUILD_WORKSPACE_DIRECTORY"))
+//!     .detector(MarkerDetector::new(["MODULE.bazel", "WORKSPACE", ".buckconfig"]));
+//! println!("{:?}", resolver.resolve(".")?.root);
+//! # Ok::<(), bpwd::BwdError>(())
+//! ```
+//!
+//! Any `Fn(&Path) -> Option<PathBuf>` is a detector too.
+
+use std::env;
+use std::path::{Path, PathBuf};
+
+use crate::{ROOT_MARKERS, RootSearch, env_git_repo, has_marker, pinned_root_from};
+
+/// Something that can tell where the project enclosing a path starts.
+pub trait RootDetector: Send + Sync {
+    /// The root for `path`, which is absolute, or `None` to leave the
+    /// question to the next detector.
+    fn detect(&self, path: &Path) -> Option<PathBuf>;
+}
+
+impl<F> RootDetector for F
+where
+    F: Fn(&Path) -> Option<PathBuf> + Send + Sync,
+{
+    fn detect(&self, path: &Path) -> Option<PathBuf> {
+        self(path)
+    }
+}
+
+impl RootDetector for RootSearch {
+    fn detect(&self, path: &Path) -> Option<PathBuf> {
+        self.find(path)
+    }
+}
+
+/// The nearest ancestor holding any of the markers, each a file or
+/// directory name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarkerDetector {
+    markers: Vec<String>,
+}
+
+impl MarkerDetector {
+    pub fn new<I, S>(markers: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        MarkerDetector { markers: markers.into_iter().map(Into::into).collect() }
+    }
+}
+
+impl RootDetector for MarkerDetector {
+    fn detect(&self, path: &Path) -> Option<PathBuf> {
+        path.ancestors()
+            .find(|dir| self.markers.iter().any(|marker| dir.join(marker).exists()))
+            .map(Path::to_path_buf)
+    }
+}
+
+/// The nearest version control checkout: git (including linked worktrees
+/// and submodules), Mercurial, Subversion and Jujutsu. Like the root
+/// search, it takes the work tree `GIT_DIR` and `GIT_WORK_TREE` name as a
+/// git checkout.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct VcsDetector;
+
+impl RootDetector for VcsDetector {
+    fn detect(&self, path: &Path) -> Option<PathBuf> {
+        let git_work_tree = env_git_repo().map(|repo| repo.work_tree);
+        path.ancestors()
+            .find(|dir| vcs_markers().any(|marker| has_marker(dir, marker, git_work_tree.as_deref())))
+            .map(Path::to_path_buf)
+    }
+}
+
+/// The built-in root markers that belong to a version control system, that
+/// is all but `.bwd-root`.
+fn vcs_markers() -> impl Iterator<Item = &'static str> {
+    ROOT_MARKERS.iter().copied().filter(|marker| *marker != ".bwd-root")
+}
+
+/// The directory named by an environment variable, for paths inside it,
+/// like bwd's own `BWD_ROOT` and read the same way: a relative value is
+/// taken from the current directory, and symlinks are resolved. An unset or
+/// empty variable passes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnvDetector {
+    var: String,
+}
+
+impl EnvDetector {
+    pub fn new(var: impl Into<String>) -> Self {
+        EnvDetector { var: var.into() }
+    }
+}
+
+impl RootDetector for EnvDetector {
+    fn detect(&self, path: &Path) -> Option<PathBuf> {
+        let root = pinned_root_from(env::var_os(&self.var), &env::current_dir().ok()?)?;
+        path.starts_with(&root).then_some(root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Resolver;
+    use std::fs;
+
+    #[test]
+    fn test_detectors() {
+        let temp_dir = std::env::temp_dir();
+        let test_root = temp_dir.join(format!("bpwd_test_detect_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&test_root);
+        let package = test_root.join("third_party").join("lib");
+        fs::create_dir_all(test_root.join(".git")).unwrap();
+        fs::create_dir_all(package.join("src")).unwrap();
+        fs::write(test_root.join("MODULE.bazel"), "").unwrap();
+        fs::write(package.join("BUILD"), "").unwrap();
+        let src = package.join("src");
+
+        assert_eq!(VcsDetector.detect(&src), Some(test_root.clone()));
+        assert_eq!(MarkerDetector::new(["BUILD", "MODULE.bazel"]).detect(&src), Some(package.clone()));
+        assert_eq!(MarkerDetector::new(["pom.xml"]).detect(&src), None);
+        assert_eq!(EnvDetector::new("BPWD_TEST_DETECT_UNSET").detect(&src), None);
+
+        // Registered detectors go first, in order; the root search is the fallback
+        let resolver = Resolver::new().detector(|_: &Path| None).detector(MarkerDetector::new(["BUILD"]));
+        assert_eq!(resolver.resolve(&src).unwrap().root, Some(fs::canonicalize(&package).unwrap()));
+        let resolver = Resolver::new().detector(MarkerDetector::new(["pom.xml"]));
+        assert_eq!(resolver.resolve(&src).unwrap().root, Some(fs::canonicalize(&test_root).unwrap()));
+
+        // `.bwd-root` is a root marker, but not version control
+        fs::write(package.join(".bwd-root"), "").unwrap();
+        assert_eq!(VcsDetector.detect(&src), Some(test_root.clone()));
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_root);
+    }
+}
@@ -0,0 +1,145 @@
+//! A C interface for plugin hosts that load bwd as a shared library
+//! (`libbpwd.so`, `libbpwd.dylib`, `bpwd.dll`) instead of spawning it, such
+//! as Neovim through LuaJIT's FFI:
+//!
+//! ```c
+//! char *bwd_resolve(const char *path);
+//! void bwd_free(char *json);
+//! ```
+//!
+//! Every string returned by `bwd_resolve` is owned by the library and must
+//! be handed back to `bwd_free`.
+
+use std::collections::HashMap;
+use std::ffi::{CStr, CString, c_char};
+use std::ptr;
+
+use tinyjson::JsonValue;
+
+use crate::project::ProjectMeta;
+use crate::{BwdError, PathInfo, Resolver, schema};
+
+/// Resolve `path` against the host's working directory and return the
+/// result as a version 1 `-j` record (see [`schema`]) with `path`, `short`,
+/// `home_relative`, `root` (relative to the project root), `root_path` and
+/// `project`. The root is found by [`Resolver::from_env`]: like `bwd -j`
+/// with `BWD_ROOT`, `GIT_DIR`, `GIT_WORK_TREE` and the `$HOME` and mount
+/// point boundaries, but with the built-in markers, as `config.toml` is not
+/// read. A failure is reported as `{"error": "<kind>", "message": "..."}`,
+/// using the same kinds as `--error-json`. Returns null only when `path` is
+/// null.
+///
+/// # Safety
+///
+/// `path` must be null or point to a NUL-terminated string that stays valid
+/// for the duration of the call.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bwd_resolve(path: *const c_char) -> *mut c_char {
+    if path.is_null() {
+        return ptr::null_mut();
+    }
+    // SAFETY: the caller guarantees a valid NUL-terminated string
+    let path = unsafe { CStr::from_ptr(path) };
+    let record = match path.to_str() {
+        Ok(path) => Resolver::from_env().resolve(path).map(|info| record(&info)),
+        Err(_) => Err(BwdError::InvalidPath(path.to_string_lossy().to_string())),
+    };
+    let json = match record {
+        Ok(record) => record,
+        Err(err) => error_record(&err),
+    };
+    // Serialized JSON escapes control characters, so it holds no NUL byte
+    CString::new(json.stringify().unwrap_or_default()).map_or(ptr::null_mut(), CString::into_raw)
+}
+
+/// Free a string returned by [`bwd_resolve`]. Null is ignored.
+///
+/// # Safety
+///
+/// `json` must be null or a pointer returned by `bwd_resolve` that has not
+/// been freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bwd_free(json: *mut c_char) {
+    if !json.is_null() {
+        // SAFETY: the pointer came from CString::into_raw in bwd_resolve
+        drop(unsafe { CString::from_raw(json) });
+    }
+}
+
+fn optional(value: Option<String>) -> JsonValue {
+    value.map_or(JsonValue::Null, JsonValue::String)
+}
+
+fn record(info: &PathInfo) -> JsonValue {
+    let mut map = HashMap::new();
+    map.insert("path".to_string(), JsonValue::String(info.path.to_string_lossy().to_string()));
+    map.insert("short".to_string(), JsonValue::String(info.short()));
+    map.insert("home_relative".to_string(), optional(info.home_relative()));
+    map.insert("root".to_string(), optional(info.relative_to_root()));
+    map.insert(
+        "root_path".to_string(),
+        optional(info.root.as_ref().map(|root| root.to_string_lossy().to_string())),
+    );
+    // A malformed `.bwd-root` is still a marker, named after its directory
+    // as `bwd -j` names it
+    let project = info.project_name().unwrap_or_else(|_| {
+        info.root.as_deref().and_then(|root| ProjectMeta::default().display_name(root))
+    });
+    map.insert("project".to_string(), optional(project));
+    schema::v1(map)
+}
+
+fn error_record(err: &BwdError) -> JsonValue {
+    let mut map = HashMap::new();
+    map.insert("error".to_string(), JsonValue::String(err.kind().to_string()));
+    map.insert("message".to_string(), JsonValue::String(err.to_string()));
+    JsonValue::Object(map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn resolve(path: &str) -> JsonValue {
+        let path = CString::new(path).unwrap();
+        unsafe {
+            let json = bwd_resolve(path.as_ptr());
+            let value = CStr::from_ptr(json).to_str().unwrap().parse().unwrap();
+            bwd_free(json);
+            value
+        }
+    }
+
+    #[test]
+    fn test_bwd_resolve() {
+        let temp_dir = std::env::temp_dir();
+        let test_root = temp_dir.join(format!("bpwd_test_ffi_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&test_root);
+        fs::create_dir_all(test_root.join(".git")).unwrap();
+        fs::create_dir_all(test_root.join("src")).unwrap();
+
+        let JsonValue::Object(map) = resolve(&test_root.join("src").to_string_lossy()) else {
+            panic!("expected an object");
+        };
+        assert_eq!(map["root"], JsonValue::String("src".to_string()));
+        assert_eq!(map["project"], JsonValue::String(format!("bpwd_test_ffi_{}", std::process::id())));
+        assert_eq!(map["schema_version"], JsonValue::Number(schema::VERSION as f64));
+        // A malformed `.bwd-root` is still a marker, named after its directory
+        fs::write(test_root.join(".bwd-root"), "this is not toml\n").unwrap();
+        let JsonValue::Object(map) = resolve(&test_root.join("src").to_string_lossy()) else {
+            panic!("expected an object");
+        };
+        assert_eq!(map["project"], JsonValue::String(format!("bpwd_test_ffi_{}", std::process::id())));
+
+        let JsonValue::Object(map) = resolve(&test_root.join("missing").to_string_lossy()) else {
+            panic!("expected an object");
+        };
+        assert_eq!(map["error"], JsonValue::String("InvalidPath".to_string()));
+
+        assert!(unsafe { bwd_resolve(ptr::null()) }.is_null());
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_root);
+    }
+}
@@ -0,0 +1,577 @@
+//! Just enough of git's repository layout to find things without running git.
+//!
+//! A work tree's `.git` is usually the git directory itself. Linked worktrees
+//! (`git worktree add`) and submodules instead have a `.git` *file* holding
+//! `gitdir: <path>`. For a worktree that directory also has a `commondir`
+//! file leading back to the main repository's `.git`; a submodule's lives
+//! under its superproject's `.git/modules/`.
+//!
+//! `HEAD` in the git directory is either `ref: refs/heads/<branch>` or, when
+//! detached, a bare commit id. Remotes are `[remote "<name>"]` sections of
+//! the shared `config`.
+//!
+//! The `index` lists every tracked path with the size and modification time
+//! it had when last staged, followed by extensions; the `TREE` one caches
+//! the tree ids git would commit, and is invalidated along any path that
+//! `git add` or `git rm` changes.
+//!
+//! A repository can also be given explicitly, the way `GIT_DIR` and
+//! `GIT_WORK_TREE` name one (a bare dotfiles repository checked out to `~`,
+//! say) whose work tree has no `.git` at all. Nothing here reads the
+//! environment; that is up to the caller.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// How a work tree is attached to its repository.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Layout {
+    /// `.git` is a directory
+    Main,
+    /// A linked worktree of another repository
+    Worktree,
+    /// A submodule inside a superproject
+    Submodule,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GitRepo {
+    /// The directory holding `.git`
+    pub work_tree: PathBuf,
+    /// Where this work tree's HEAD and index live
+    pub git_dir: PathBuf,
+    /// Where shared state (config, refs, objects) lives; differs from
+    /// `git_dir` only for linked worktrees
+    pub common_dir: PathBuf,
+    pub layout: Layout,
+}
+
+impl GitRepo {
+    /// Read the repository whose work tree is `work_tree`, i.e. which has a
+    /// `.git` entry directly inside it.
+    pub fn open(work_tree: &Path) -> Option<GitRepo> {
+        let dot_git = work_tree.join(".git");
+        if dot_git.is_dir() {
+            return Some(GitRepo {
+                work_tree: work_tree.to_path_buf(),
+                git_dir: dot_git.clone(),
+                common_dir: dot_git,
+                layout: Layout::Main,
+            });
+        }
+        let git_dir = read_gitdir_file(&dot_git)?;
+        let (common_dir, layout) = match fs::read_to_string(git_dir.join("commondir")) {
+            Ok(common) => (lexical_join(&git_dir, common.trim()), Layout::Worktree),
+            Err(_) => (git_dir.clone(), Layout::Submodule),
+        };
+        Some(GitRepo { work_tree: work_tree.to_path_buf(), git_dir, common_dir, layout })
+    }
+
+    /// The nearest enclosing work tree of `path`.
+    pub fn discover(path: &Path) -> Option<GitRepo> {
+        GitRepo::discover_with(path, None)
+    }
+
+    /// The nearest enclosing work tree of `path`, counting `explicit` (a
+    /// repository given by `GIT_DIR`, say) as one too.
+    pub fn discover_with(path: &Path, explicit: Option<&GitRepo>) -> Option<GitRepo> {
+        let is_explicit = |dir: &Path| explicit.is_some_and(|repo| repo.work_tree == dir);
+        let dir = path.ancestors().find(|dir| is_explicit(dir) || dir.join(".git").exists())?;
+        match explicit {
+            Some(repo) if is_explicit(dir) => Some(repo.clone()),
+            _ => GitRepo::open(dir),
+        }
+    }
+
+    /// The repository in `git_dir` checked out in `work_tree`, as given by
+    /// `GIT_DIR` and `GIT_WORK_TREE`.
+    pub fn with_git_dir(git_dir: PathBuf, work_tree: PathBuf) -> GitRepo {
+        let (common_dir, layout) = match fs::read_to_string(git_dir.join("commondir")) {
+            Ok(common) => (lexical_join(&git_dir, common.trim()), Layout::Worktree),
+            Err(_) => (git_dir.clone(), Layout::Main),
+        };
+        GitRepo { work_tree, git_dir, common_dir, layout }
+    }
+
+    /// The checked-out branch, `None` when HEAD is detached or unreadable.
+    pub fn branch(&self) -> Option<String> {
+        let head = fs::read_to_string(self.git_dir.join("HEAD")).ok()?;
+        let reference = head.trim().strip_prefix("ref:")?.trim();
+        Some(reference.strip_prefix("refs/heads/").unwrap_or(reference).to_string())
+    }
+
+    /// The commit HEAD points at, following a branch through its loose ref
+    /// or `packed-refs`. `None` on a branch without commits.
+    pub fn head_commit(&self) -> Option<String> {
+        let head = fs::read_to_string(self.git_dir.join("HEAD")).ok()?;
+        let Some(reference) = head.trim().strip_prefix("ref:").map(str::trim) else {
+            return Some(head.trim().to_string());
+        };
+        if let Ok(commit) = fs::read_to_string(self.common_dir.join(reference)) {
+            return Some(commit.trim().to_string());
+        }
+        let packed = fs::read_to_string(self.common_dir.join("packed-refs")).ok()?;
+        packed
+            .lines()
+            .filter_map(|line| line.split_once(' '))
+            .find(|(_, name)| *name == reference)
+            .map(|(commit, _)| commit.to_string())
+    }
+
+    /// The URL of the `origin` remote, or of the first remote when there is
+    /// no `origin`.
+    pub fn remote_url(&self) -> Option<String> {
+        let config = fs::read_to_string(self.common_dir.join("config")).ok()?;
+        let mut remote: Option<String> = None;
+        let mut urls: Vec<(String, String)> = Vec::new();
+        for line in config.lines().map(str::trim) {
+            if let Some(header) = line.strip_prefix('[') {
+                remote = header
+                    .strip_prefix("remote")
+                    .and_then(|rest| rest.trim().strip_prefix('"'))
+                    .and_then(|rest| rest.split_once('"'))
+                    .map(|(name, _)| name.to_string());
+            } else if let (Some(name), Some((key, value))) = (&remote, line.split_once('='))
+                && key.trim().eq_ignore_ascii_case("url")
+            {
+                urls.push((name.clone(), value.trim().trim_matches('"').to_string()));
+            }
+        }
+        let origin = urls.iter().position(|(name, _)| name == "origin").unwrap_or(0);
+        (!urls.is_empty()).then(|| urls.swap_remove(origin).1)
+    }
+
+    /// The main repository's work tree: the repository itself, the main
+    /// worktree of a linked worktree, or the top-level superproject of a
+    /// submodule. `None` for a bare main repository.
+    pub fn main_work_tree(&self) -> Option<PathBuf> {
+        match self.layout {
+            Layout::Main => Some(self.work_tree.clone()),
+            Layout::Worktree => dot_git_parent(&self.common_dir),
+            // The outermost `.git` above `<super>/.git/modules/<name>`
+            Layout::Submodule => self
+                .git_dir
+                .ancestors()
+                .filter(|dir| dir.file_name().is_some_and(|name| name == ".git"))
+                .last()
+                .and_then(|dot_git| dot_git.parent())
+                .map(Path::to_path_buf),
+        }
+    }
+
+    /// Whether the work tree has changes to tracked files, judged from the
+    /// index like `git diff HEAD --quiet` without reading any object:
+    ///
+    /// - a tracked file that is missing, or whose size or modification time
+    ///   differs from its index entry, is an unstaged change;
+    /// - a conflicted or intent-to-add entry, an index whose cached root tree
+    ///   is invalidated, or entries on a branch without commits, are staged
+    ///   changes.
+    ///
+    /// This is a heuristic: touching a file without changing it reads as
+    /// dirty, and so does an index `git reset` left without a cached tree
+    /// until the next commit; a staged change that leaves the cached tree
+    /// alone (such as after `git reset --soft`) reads as clean, and
+    /// untracked files are not looked for. It costs one `stat` per tracked
+    /// file, with no directory walk, and stops at the first change. `None`
+    /// when there is no readable index.
+    pub fn is_dirty(&self) -> Option<bool> {
+        let index = read_index(&self.git_dir.join("index"), self.object_id_len())?;
+        if index.root_tree_valid == Some(false) || (!index.entries.is_empty() && self.head_commit().is_none()) {
+            return Some(true);
+        }
+        Some(index.entries.iter().any(|entry| entry.is_modified(&self.work_tree)))
+    }
+
+    /// The length of object ids in the index: 32 bytes for a SHA-256
+    /// repository, else 20.
+    fn object_id_len(&self) -> usize {
+        let config = fs::read_to_string(self.common_dir.join("config")).unwrap_or_default();
+        let sha256 = config.lines().filter_map(|line| line.split_once('=')).any(|(key, value)| {
+            key.trim().eq_ignore_ascii_case("objectformat") && value.trim().eq_ignore_ascii_case("sha256")
+        });
+        if sha256 { 32 } else { 20 }
+    }
+}
+
+/// The parts of `.git/index` [`GitRepo::is_dirty`] looks at.
+struct Index {
+    entries: Vec<IndexEntry>,
+    /// Whether the `TREE` extension's root is valid; `None` without one
+    root_tree_valid: Option<bool>,
+}
+
+struct IndexEntry {
+    path: String,
+    mtime: (u32, u32),
+    size: u32,
+    mode: u32,
+    /// The merge stage; anything but 0 is a conflict
+    stage: u16,
+    skip_worktree: bool,
+    intent_to_add: bool,
+}
+
+impl IndexEntry {
+    /// Whether the file in `work_tree` no longer matches the entry.
+    fn is_modified(&self, work_tree: &Path) -> bool {
+        if self.stage != 0 || self.intent_to_add {
+            return true;
+        }
+        // Sparse checkouts leave these out; submodules have their own index
+        if self.skip_worktree || self.mode == GITLINK_MODE {
+            return false;
+        }
+        let Ok(meta) = fs::symlink_metadata(work_tree.join(&self.path)) else {
+            return true;
+        };
+        let Some(mtime) = meta.modified().ok().and_then(|time| time.duration_since(UNIX_EPOCH).ok()) else {
+            return true;
+        };
+        // The index truncates sizes and times to 32 bits; a git built
+        // without nanosecond support writes 0 nanoseconds
+        let (seconds, nanos) = self.mtime;
+        meta.is_dir()
+            || meta.len() as u32 != self.size
+            || mtime.as_secs() as u32 != seconds
+            || (nanos != 0 && mtime.subsec_nanos() != nanos)
+    }
+}
+
+const GITLINK_MODE: u32 = 0o160000;
+
+/// Parse an index of version 2, 3 or 4 whose object ids are `id_len` bytes.
+fn read_index(file: &Path, id_len: usize) -> Option<Index> {
+    let data = fs::read(file).ok()?;
+    let u16_at = |at: usize| Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?));
+    let u32_at = |at: usize| Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?));
+    if data.get(..4)? != b"DIRC" {
+        return None;
+    }
+    let version = u32_at(4)?;
+    if !(2..=4).contains(&version) {
+        return None;
+    }
+    let count = u32_at(8)?;
+
+    let mut entries = Vec::new();
+    let mut at = 12;
+    let mut previous: Vec<u8> = Vec::new();
+    for _ in 0..count {
+        let start = at;
+        let flags = u16_at(start + 40 + id_len)?;
+        at = start + 42 + id_len;
+        let extended = if version >= 3 && flags & 0x4000 != 0 {
+            at += 2;
+            u16_at(at - 2)?
+        } else {
+            0
+        };
+        let path = if version == 4 {
+            // The path is the previous one minus a varint-counted suffix,
+            // plus a NUL-terminated tail, with no padding
+            let (strip, len) = read_varint(data.get(at..)?)?;
+            at += len;
+            let tail_len = data.get(at..)?.iter().position(|&byte| byte == 0)?;
+            let mut path = previous.get(..previous.len().checked_sub(strip)?)?.to_vec();
+            path.extend_from_slice(&data[at..at + tail_len]);
+            at += tail_len + 1;
+            path
+        } else {
+            let len = data.get(at..)?.iter().position(|&byte| byte == 0)?;
+            let path = data[at..at + len].to_vec();
+            // NUL padded to a multiple of 8 bytes, at least one NUL
+            at = start + ((at - start + len + 8) & !7);
+            path
+        };
+        entries.push(IndexEntry {
+            path: String::from_utf8_lossy(&path).to_string(),
+            mtime: (u32_at(start + 8)?, u32_at(start + 12)?),
+            size: u32_at(start + 36)?,
+            mode: u32_at(start + 24)?,
+            stage: (flags >> 12) & 0x3,
+            skip_worktree: extended & 0x4000 != 0,
+            intent_to_add: extended & 0x2000 != 0,
+        });
+        previous = path;
+    }
+
+    // Extensions up to the trailing checksum
+    let mut root_tree_valid = None;
+    while at + 8 + id_len <= data.len() {
+        let size = u32_at(at + 4)? as usize;
+        let body = data.get(at + 8..at + 8 + size)?;
+        if &data[at..at + 4] == b"TREE" && !body.is_empty() {
+            // `<path>\0<entry count> <subtrees>\n`, the root first with an
+            // empty path and a count of -1 when invalidated
+            let header = body.get(1..)?.split(|&byte| byte == b' ').next()?;
+            root_tree_valid = Some(body[0] == 0 && header != b"-1");
+        }
+        at += 8 + size;
+    }
+    Some(Index { entries, root_tree_valid })
+}
+
+/// Git's offset encoding: 7 bits per byte, big-endian, each continuation
+/// adding one. Returns the value and the bytes it took.
+fn read_varint(bytes: &[u8]) -> Option<(usize, usize)> {
+    let mut value = 0usize;
+    for (i, &byte) in bytes.iter().enumerate() {
+        if i > 0 {
+            value = value.checked_add(1)?.checked_shl(7)?;
+        }
+        value |= usize::from(byte & 0x7f);
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+/// The repository name in a remote URL: `bpwd` for
+/// `git@github.com:CodeTease/bpwd.git`, `https://github.com/CodeTease/bpwd`
+/// or `/srv/git/bpwd.git`.
+pub fn repo_name(url: &str) -> Option<String> {
+    let last = url.trim_end_matches(['/', '\\']).rsplit(['/', '\\', ':']).next()?;
+    let name = last.strip_suffix(".git").unwrap_or(last);
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+fn dot_git_parent(dir: &Path) -> Option<PathBuf> {
+    if dir.file_name().is_some_and(|name| name == ".git") {
+        dir.parent().map(Path::to_path_buf)
+    } else {
+        None
+    }
+}
+
+/// The target of a `gitdir: <path>` file, relative paths taken from the
+/// file's directory.
+fn read_gitdir_file(file: &Path) -> Option<PathBuf> {
+    let content = fs::read_to_string(file).ok()?;
+    let target = content.lines().next()?.strip_prefix("gitdir:")?.trim();
+    Some(lexical_join(file.parent()?, target))
+}
+
+fn lexical_join(base: &Path, relative: &str) -> PathBuf {
+    crate::lexical_normalize(&base.join(relative))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+
+    #[test]
+    fn test_worktree_and_submodule_layouts() {
+        let temp_dir = std::env::temp_dir();
+        let test_root = temp_dir.join(format!("bpwd_test_git_layout_{}", process::id()));
+        let _ = fs::remove_dir_all(&test_root);
+
+        // Main repository with a linked worktree and a submodule
+        let main = test_root.join("main");
+        let worktree_git = main.join(".git").join("worktrees").join("feature");
+        let module_git = main.join(".git").join("modules").join("libs").join("dep");
+        fs::create_dir_all(&worktree_git).unwrap();
+        fs::create_dir_all(&module_git).unwrap();
+        fs::write(worktree_git.join("commondir"), "../..\n").unwrap();
+
+        let feature = test_root.join("feature");
+        fs::create_dir_all(feature.join("src")).unwrap();
+        fs::write(feature.join(".git"), format!("gitdir: {}\n", worktree_git.display())).unwrap();
+
+        let dep = main.join("libs").join("dep");
+        fs::create_dir_all(&dep).unwrap();
+        fs::write(dep.join(".git"), "gitdir: ../../.git/modules/libs/dep\n").unwrap();
+
+        let repo = GitRepo::discover(&main.join("libs")).unwrap();
+        assert_eq!(repo.layout, Layout::Main);
+        assert_eq!(repo.main_work_tree(), Some(main.clone()));
+
+        let repo = GitRepo::discover(&feature.join("src")).unwrap();
+        assert_eq!(repo.layout, Layout::Worktree);
+        assert_eq!(repo.work_tree, feature);
+        assert_eq!(repo.git_dir, worktree_git);
+        assert_eq!(repo.common_dir, main.join(".git"));
+        assert_eq!(repo.main_work_tree(), Some(main.clone()));
+
+        let repo = GitRepo::discover(&dep).unwrap();
+        assert_eq!(repo.layout, Layout::Submodule);
+        assert_eq!(repo.git_dir, module_git);
+        assert_eq!(repo.main_work_tree(), Some(main.clone()));
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_root);
+    }
+
+    #[test]
+    fn test_remote_url_and_repo_name() {
+        let temp_dir = std::env::temp_dir();
+        let test_root = temp_dir.join(format!("bpwd_test_git_remote_{}", process::id()));
+        let _ = fs::remove_dir_all(&test_root);
+        fs::create_dir_all(test_root.join(".git")).unwrap();
+        let repo = GitRepo::open(&test_root).unwrap();
+        assert_eq!(repo.remote_url(), None);
+
+        let config = "[core]\n\tbare = false\n[remote \"fork\"]\n\turl = https://github.com/me/bpwd-fork\n[remote \"origin\"]\n\turl = git@github.com:CodeTease/bpwd.git\n";
+        fs::write(test_root.join(".git").join("config"), config).unwrap();
+        assert_eq!(repo.remote_url(), Some("git@github.com:CodeTease/bpwd.git".to_string()));
+        fs::write(test_root.join(".git").join("config"), "[remote \"fork\"]\n\turl = https://github.com/me/bpwd-fork\n").unwrap();
+        assert_eq!(repo.remote_url(), Some("https://github.com/me/bpwd-fork".to_string()));
+
+        assert_eq!(repo_name("git@github.com:CodeTease/bpwd.git"), Some("bpwd".to_string()));
+        assert_eq!(repo_name("https://gitlab.com/group/sub/tool/"), Some("tool".to_string()));
+        assert_eq!(repo_name("/srv/git/bpwd.git"), Some("bpwd".to_string()));
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_root);
+    }
+
+    #[test]
+    fn test_discover_with_explicit_repo() {
+        let temp_dir = std::env::temp_dir();
+        let test_root = temp_dir.join(format!("bpwd_test_git_explicit_{}", process::id()));
+        let _ = fs::remove_dir_all(&test_root);
+        let home = test_root.join("home");
+        let project = home.join("code").join("app");
+        fs::create_dir_all(test_root.join("dotfiles.git")).unwrap();
+        fs::create_dir_all(home.join(".config")).unwrap();
+        fs::create_dir_all(project.join(".git")).unwrap();
+
+        // A bare dotfiles repository checked out to a home directory
+        let dotfiles = GitRepo::with_git_dir(test_root.join("dotfiles.git"), home.clone());
+        assert_eq!(dotfiles.layout, Layout::Main);
+        assert_eq!(GitRepo::discover(&home.join(".config")), None);
+        assert_eq!(GitRepo::discover_with(&home.join(".config"), Some(&dotfiles)), Some(dotfiles.clone()));
+        // A nearer `.git` still wins
+        assert_eq!(GitRepo::discover_with(&project, Some(&dotfiles)).unwrap().work_tree, project);
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_root);
+    }
+
+    #[test]
+    fn test_head_commit() {
+        let temp_dir = std::env::temp_dir();
+        let test_root = temp_dir.join(format!("bpwd_test_git_head_{}", process::id()));
+        let _ = fs::remove_dir_all(&test_root);
+        let dot_git = test_root.join(".git");
+        fs::create_dir_all(dot_git.join("refs").join("heads")).unwrap();
+        fs::write(dot_git.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        let repo = GitRepo::open(&test_root).unwrap();
+
+        // Unborn branch
+        assert_eq!(repo.head_commit(), None);
+        fs::write(dot_git.join("packed-refs"), "# pack-refs with: peeled fully-peeled sorted\nc0ffee refs/heads/main\n").unwrap();
+        assert_eq!(repo.head_commit(), Some("c0ffee".to_string()));
+        // A loose ref is newer than the packed one
+        fs::write(dot_git.join("refs").join("heads").join("main"), "decade\n").unwrap();
+        assert_eq!(repo.head_commit(), Some("decade".to_string()));
+        fs::write(dot_git.join("HEAD"), "facade\n").unwrap();
+        assert_eq!(repo.head_commit(), Some("facade".to_string()));
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_root);
+    }
+
+    #[test]
+    fn test_branch() {
+        let temp_dir = std::env::temp_dir();
+        let test_root = temp_dir.join(format!("bpwd_test_git_branch_{}", process::id()));
+        let _ = fs::remove_dir_all(&test_root);
+        fs::create_dir_all(test_root.join(".git")).unwrap();
+        let repo = GitRepo::open(&test_root).unwrap();
+
+        assert_eq!(repo.branch(), None);
+        fs::write(test_root.join(".git").join("HEAD"), "ref: refs/heads/feature/login\n").unwrap();
+        assert_eq!(repo.branch(), Some("feature/login".to_string()));
+        fs::write(test_root.join(".git").join("HEAD"), "3f786850e387550fdab836ed7e6dc881de23001b\n").unwrap();
+        assert_eq!(repo.branch(), None);
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_root);
+    }
+
+    /// A version 2 index of `(path, size, mtime)` entries and, if given, a
+    /// `TREE` extension whose root has `tree_count` entries.
+    fn index_bytes(entries: &[(&str, u32, (u32, u32))], tree_count: Option<&str>) -> Vec<u8> {
+        let mut data = b"DIRC".to_vec();
+        data.extend(2u32.to_be_bytes());
+        data.extend((entries.len() as u32).to_be_bytes());
+        for (path, size, (seconds, nanos)) in entries {
+            let start = data.len();
+            data.extend([0; 8]);
+            data.extend(seconds.to_be_bytes());
+            data.extend(nanos.to_be_bytes());
+            data.extend([0; 8]);
+            data.extend(0o100644u32.to_be_bytes());
+            data.extend([0; 8]);
+            data.extend(size.to_be_bytes());
+            data.extend([0; 20]);
+            data.extend((path.len() as u16).to_be_bytes());
+            data.extend(path.as_bytes());
+            data.resize(start + ((62 + path.len() + 8) & !7), 0);
+        }
+        if let Some(count) = tree_count {
+            let body = format!("\0{count} 0\n");
+            data.extend(b"TREE");
+            data.extend((body.len() as u32 + 20).to_be_bytes());
+            data.extend(body.as_bytes());
+            data.extend([0; 20]);
+        }
+        data.extend([0; 20]);
+        data
+    }
+
+    #[test]
+    fn test_is_dirty() {
+        let temp_dir = std::env::temp_dir();
+        let test_root = temp_dir.join(format!("bpwd_test_git_dirty_{}", process::id()));
+        let _ = fs::remove_dir_all(&test_root);
+        let dot_git = test_root.join(".git");
+        fs::create_dir_all(&dot_git).unwrap();
+        fs::create_dir_all(test_root.join("src")).unwrap();
+        fs::write(dot_git.join("HEAD"), "c0ffee\n").unwrap();
+        fs::write(test_root.join("src").join("main.rs"), "fn main() {}").unwrap();
+        let repo = GitRepo::open(&test_root).unwrap();
+
+        // No index yet: nothing to compare against
+        assert_eq!(repo.is_dirty(), None);
+
+        let mtime = fs::metadata(test_root.join("src").join("main.rs")).unwrap().modified().unwrap();
+        let mtime = mtime.duration_since(UNIX_EPOCH).unwrap();
+        let stat = (mtime.as_secs() as u32, mtime.subsec_nanos());
+        let write_index = |entries: &[(&str, u32, (u32, u32))], tree_count| {
+            fs::write(dot_git.join("index"), index_bytes(entries, tree_count)).unwrap();
+        };
+
+        write_index(&[("src/main.rs", 12, stat)], Some("1"));
+        assert_eq!(repo.is_dirty(), Some(false));
+        // Untracked and ignored files are not looked for
+        fs::write(test_root.join("notes.txt"), "").unwrap();
+        assert_eq!(repo.is_dirty(), Some(false));
+        // Unstaged modification
+        write_index(&[("src/main.rs", 11, stat)], Some("1"));
+        assert_eq!(repo.is_dirty(), Some(true));
+        // Unstaged deletion
+        write_index(&[("src/main.rs", 12, stat), ("src/lib.rs", 0, stat)], Some("2"));
+        assert_eq!(repo.is_dirty(), Some(true));
+        // Staged change: `git add` invalidated the cached tree
+        write_index(&[("src/main.rs", 12, stat)], Some("-1"));
+        assert_eq!(repo.is_dirty(), Some(true));
+        // Staged on a branch without commits
+        fs::write(dot_git.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        write_index(&[("src/main.rs", 12, stat)], None);
+        assert_eq!(repo.is_dirty(), Some(true));
+        write_index(&[], None);
+        assert_eq!(repo.is_dirty(), Some(false));
+
+        assert_eq!(read_varint(&[0x05]), Some((5, 1)));
+        assert_eq!(read_varint(&[0x80, 0x00]), Some((128, 2)));
+        assert_eq!(read_varint(&[0x80]), None);
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_root);
+    }
+}
@@ -0,0 +1,136 @@
+//! Shell-style glob matching on paths, used for history exclusions and
+//! for expanding glob targets.
+//!
+//! `*` matches within one path component, `?` matches a single character and
+//! a `**` component matches any number of components, including none, so
+//! `/tmp/**` covers `/tmp` itself as well as everything below it. Both `/`
+//! and `\` separate components.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Whether `s` contains a wildcard.
+pub fn is_pattern(s: &str) -> bool {
+    s.contains(['*', '?'])
+}
+
+/// The existing paths matching `pattern`, a relative one taken from `base`,
+/// sorted. The walk starts at the pattern's literal leading directories and
+/// does not follow symlinked directories, so `**` cannot loop.
+pub fn expand(pattern: &str, base: &Path) -> Vec<PathBuf> {
+    let pattern = pattern.trim_end_matches(['/', '\\']);
+    let components: Vec<&str> = pattern.split(['/', '\\']).collect();
+    let literal_len = components.iter().take_while(|c| !is_pattern(c)).count();
+    let start = base.join(components[..literal_len].join("/"));
+    let rest = &components[literal_len..];
+    let mut found = Vec::new();
+    if start.is_dir() {
+        walk(&start, &mut Vec::new(), rest, &mut found);
+    }
+    found.sort();
+    found
+}
+
+fn walk(dir: &Path, relative: &mut Vec<String>, pattern: &[&str], found: &mut Vec<PathBuf>) {
+    let relative_strs: Vec<&str> = relative.iter().map(String::as_str).collect();
+    if match_components(pattern, &relative_strs) {
+        found.push(dir.to_path_buf());
+    }
+    // Without `**` nothing deeper than the pattern can match
+    if !pattern.contains(&"**") && relative.len() >= pattern.len() {
+        return;
+    }
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let is_dir = entry.file_type().is_ok_and(|file_type| file_type.is_dir());
+        relative.push(name);
+        if is_dir {
+            walk(&entry.path(), relative, pattern, found);
+        } else {
+            let relative_strs: Vec<&str> = relative.iter().map(String::as_str).collect();
+            if match_components(pattern, &relative_strs) {
+                found.push(entry.path());
+            }
+        }
+        relative.pop();
+    }
+}
+
+/// Whether `path` matches `pattern` as a whole.
+pub fn matches(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<&str> = pattern.split(['/', '\\']).collect();
+    let path: Vec<&str> = path.split(['/', '\\']).collect();
+    match_components(&pattern, &path)
+}
+
+fn match_components(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| match_components(rest, &path[skip..])),
+        Some((segment, rest)) => match path.split_first() {
+            Some((component, path_rest)) => {
+                match_component(&segment.chars().collect::<Vec<_>>(), &component.chars().collect::<Vec<_>>())
+                    && match_components(rest, path_rest)
+            }
+            None => false,
+        },
+    }
+}
+
+fn match_component(pattern: &[char], text: &[char]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some(('*', rest)) => (0..=text.len()).any(|skip| match_component(rest, &text[skip..])),
+        Some(('?', rest)) => !text.is_empty() && match_component(rest, &text[1..]),
+        Some((c, rest)) => text.first() == Some(c) && match_component(rest, &text[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(matches("**/node_modules/**", "/home/me/app/node_modules"));
+        assert!(matches("**/node_modules/**", "/home/me/app/node_modules/left-pad/lib"));
+        assert!(!matches("**/node_modules/**", "/home/me/app/node_modules_old"));
+        assert!(matches("/tmp/**", "/tmp"));
+        assert!(matches("/tmp/**", "/tmp/a/b"));
+        assert!(!matches("/tmp/**", "/var/tmp"));
+        assert!(matches("/home/*/.cache", "/home/me/.cache"));
+        assert!(!matches("/home/*/.cache", "/home/me/x/.cache"));
+        assert!(matches("/srv/build-??", "/srv/build-42"));
+        assert!(matches(r"C:\Temp\**", r"C:\Temp\x"));
+    }
+
+    #[test]
+    fn test_expand() {
+        let temp_dir = std::env::temp_dir();
+        let test_root = temp_dir.join(format!("bpwd_test_glob_expand_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&test_root);
+        fs::create_dir_all(test_root.join("src").join("a").join("fixtures")).unwrap();
+        fs::create_dir_all(test_root.join("src").join("b").join("c").join("fixtures")).unwrap();
+        fs::write(test_root.join("src").join("main.rs"), "").unwrap();
+
+        assert_eq!(
+            expand("src/**/fixtures", &test_root),
+            vec![
+                test_root.join("src").join("a").join("fixtures"),
+                test_root.join("src").join("b").join("c").join("fixtures"),
+            ]
+        );
+        assert_eq!(expand("src/*.rs", &test_root), vec![test_root.join("src").join("main.rs")]);
+        assert_eq!(expand("src/?", &test_root), vec![test_root.join("src").join("a"), test_root.join("src").join("b")]);
+        assert!(expand("missing/*", &test_root).is_empty());
+        // Absolute patterns ignore the base
+        let absolute = format!("{}/src/*/fixtures", test_root.to_string_lossy());
+        assert_eq!(expand(&absolute, Path::new("/elsewhere")), vec![test_root.join("src").join("a").join("fixtures")]);
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_root);
+    }
+}
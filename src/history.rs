@@ -0,0 +1,535 @@
+//! Directory visit history (`bwd visit` / `bwd recent` / `bwd jump`).
+//!
+//! History is opt-in: directories are only recorded by `bwd visit`, usually
+//! from a shell hook, or on every resolve when `BWD_HISTORY=1` is set. The
+//! file holds one directory per line as `last_visit<TAB>visits<TAB>path`,
+//! with the path last so that it may itself contain tabs.
+//!
+//! Several shells may record at once, so writers go through `History::update`,
+//! which holds an advisory lock on a `.lock` file next to the history for the
+//! whole read-modify-write, and saves by renaming a finished temporary file
+//! over the old one so that readers never see a half-written history.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tinyjson::JsonValue;
+
+use crate::{BwdError, unix_seconds};
+
+/// Directories kept before the least recently visited ones are pruned.
+pub const MAX_ENTRIES: usize = 1000;
+
+/// One remembered directory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Visit {
+    pub path: PathBuf,
+    pub visits: u64,
+    /// Unix timestamp (seconds) of the latest visit
+    pub last_visit: u64,
+}
+
+impl Visit {
+    /// Visits weighted by recency, see `crate::frecency`.
+    pub fn score(&self, now: SystemTime) -> f64 {
+        crate::frecency(self.visits, self.last_visit, now)
+    }
+
+    /// Whether the path matches all `keywords`, zoxide style: they must
+    /// appear in order, ignoring case, and the last one must be found in the
+    /// final path component.
+    pub fn matches(&self, keywords: &[String]) -> bool {
+        let path = self.path.to_string_lossy().to_lowercase();
+        let mut rest = path.as_str();
+        for keyword in keywords {
+            let keyword = keyword.to_lowercase();
+            match rest.find(&keyword) {
+                Some(index) => rest = &rest[index + keyword.len()..],
+                None => return false,
+            }
+        }
+        let Some(last) = keywords.last() else {
+            return true;
+        };
+        let name = self.path.file_name().map(|name| name.to_string_lossy().to_lowercase());
+        name.is_some_and(|name| name.contains(&last.to_lowercase()))
+    }
+}
+
+/// The on-disk history, one entry per directory.
+pub struct History {
+    file: PathBuf,
+    entries: BTreeMap<PathBuf, Visit>,
+}
+
+impl History {
+    /// Load the history from `file`. A missing file is an empty history.
+    pub fn load(file: &Path) -> Result<Self, BwdError> {
+        let entries = if file.exists() {
+            parse(&fs::read_to_string(file)?)
+        } else {
+            BTreeMap::new()
+        };
+        Ok(History { file: file.to_path_buf(), entries })
+    }
+
+    /// Load the history, apply `change` and save it, all under an exclusive
+    /// lock so that concurrent writers cannot lose each other's updates.
+    pub fn update(file: &Path, change: impl FnOnce(&mut History)) -> Result<(), BwdError> {
+        crate::with_lock(file, || {
+            let mut history = History::load(file)?;
+            change(&mut history);
+            history.save()
+        })
+    }
+
+    /// Write the history back, creating the config directory if needed.
+    /// Prefer `update` unless no other process can be writing.
+    pub fn save(&self) -> Result<(), BwdError> {
+        crate::write_atomic(&self.file, &self.to_lines())
+    }
+
+    fn to_lines(&self) -> String {
+        self.entries
+            .values()
+            .map(|visit| format!("{}\t{}\t{}\n", visit.last_visit, visit.visits, visit.path.to_string_lossy()))
+            .collect()
+    }
+
+    /// Count a visit to `path` at `now`. Revisits update the existing entry
+    /// instead of adding a duplicate. Paths with a newline cannot be stored
+    /// in the line format and are ignored.
+    pub fn record(&mut self, path: &Path, now: SystemTime) {
+        if path.to_string_lossy().contains('\n') {
+            return;
+        }
+        let visit = self.entries.entry(path.to_path_buf()).or_insert_with(|| Visit {
+            path: path.to_path_buf(),
+            visits: 0,
+            last_visit: 0,
+        });
+        visit.visits += 1;
+        visit.last_visit = visit.last_visit.max(unix_seconds(now));
+    }
+
+    /// Keep only the `max` most recently visited directories, returning how
+    /// many were dropped.
+    pub fn prune(&mut self, max: usize) -> usize {
+        if self.entries.len() <= max {
+            return 0;
+        }
+        let keep: HashSet<PathBuf> = self.recent().into_iter().take(max).map(|visit| visit.path.clone()).collect();
+        let before = self.entries.len();
+        self.entries.retain(|path, _| keep.contains(path));
+        before - self.entries.len()
+    }
+
+    /// Fold an imported entry in: visit counts add up and the later visit
+    /// time wins.
+    pub fn merge_visit(&mut self, imported: Visit) {
+        if imported.path.to_string_lossy().contains('\n') {
+            return;
+        }
+        match self.entries.get_mut(&imported.path) {
+            Some(visit) => {
+                visit.visits += imported.visits;
+                visit.last_visit = visit.last_visit.max(imported.last_visit);
+            }
+            None => {
+                self.entries.insert(imported.path.clone(), imported);
+            }
+        }
+    }
+
+    /// Entries matching `keywords`, best first: highest score, then most
+    /// recent visit, then the shorter path, then path order.
+    pub fn ranked(&self, keywords: &[String], now: SystemTime) -> Vec<&Visit> {
+        let mut matches: Vec<&Visit> = self.entries.values().filter(|visit| visit.matches(keywords)).collect();
+        matches.sort_by(|a, b| {
+            b.score(now)
+                .total_cmp(&a.score(now))
+                .then(b.last_visit.cmp(&a.last_visit))
+                .then(a.path.as_os_str().len().cmp(&b.path.as_os_str().len()))
+        });
+        matches
+    }
+
+    /// All entries, most recently visited first. Ties keep path order.
+    pub fn recent(&self) -> Vec<&Visit> {
+        let mut visits: Vec<&Visit> = self.entries.values().collect();
+        visits.sort_by_key(|visit| Reverse(visit.last_visit));
+        visits
+    }
+}
+
+/// Parse the line format, skipping lines that do not fit it.
+fn parse(content: &str) -> BTreeMap<PathBuf, Visit> {
+    let mut entries = BTreeMap::new();
+    for line in content.lines() {
+        let mut fields = line.splitn(3, '\t');
+        let (Some(last_visit), Some(visits), Some(path)) = (fields.next(), fields.next(), fields.next()) else {
+            continue;
+        };
+        let (Ok(last_visit), Ok(visits)) = (last_visit.parse(), visits.parse()) else {
+            continue;
+        };
+        if path.is_empty() {
+            continue;
+        }
+        let path = PathBuf::from(path);
+        entries.insert(path.clone(), Visit { path, visits, last_visit });
+    }
+    entries
+}
+
+/// Visits as a JSON array of `{"path", "visits", "last_visit"}` objects.
+/// Paths are shortened when `home` is given.
+pub fn recent_json(visits: &[&Visit], home: Option<&Path>) -> JsonValue {
+    let items = visits
+        .iter()
+        .map(|visit| {
+            let mut map = HashMap::new();
+            map.insert("path".to_string(), JsonValue::String(crate::shorten_path(&visit.path, home)));
+            map.insert("visits".to_string(), JsonValue::Number(visit.visits as f64));
+            map.insert("last_visit".to_string(), JsonValue::Number(visit.last_visit as f64));
+            JsonValue::Object(map)
+        })
+        .collect();
+    JsonValue::Array(items)
+}
+
+/// Another jump tool whose database `bwd import-history` understands.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HistorySource {
+    Zoxide,
+    Autojump,
+    Z,
+}
+
+impl HistorySource {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "zoxide" => Some(HistorySource::Zoxide),
+            "autojump" => Some(HistorySource::Autojump),
+            "z" => Some(HistorySource::Z),
+            _ => None,
+        }
+    }
+
+    /// Where the tool keeps its database by default, honoring the same
+    /// environment variables as the tool itself.
+    pub fn default_path(&self) -> Option<PathBuf> {
+        let home = crate::get_home_dir();
+        match self {
+            HistorySource::Zoxide => {
+                if let Some(dir) = env::var_os("_ZO_DATA_DIR") {
+                    return Some(PathBuf::from(dir).join("db.zo"));
+                }
+                data_dir().map(|dir| dir.join("zoxide").join("db.zo"))
+            }
+            HistorySource::Autojump => {
+                if cfg!(target_os = "macos") {
+                    home.map(|home| home.join("Library").join("autojump").join("autojump.txt"))
+                } else {
+                    data_dir().map(|dir| dir.join("autojump").join("autojump.txt"))
+                }
+            }
+            HistorySource::Z => env::var_os("_Z_DATA").map(PathBuf::from).or_else(|| home.map(|home| home.join(".z"))),
+        }
+    }
+
+    /// Parse the tool's database. Its rank becomes the visit count, rounded
+    /// and at least one.
+    pub fn parse(&self, data: &[u8], now: SystemTime) -> Result<Vec<Visit>, BwdError> {
+        match self {
+            HistorySource::Zoxide => parse_zoxide(data),
+            HistorySource::Autojump => Ok(parse_autojump(&String::from_utf8_lossy(data), unix_seconds(now))),
+            HistorySource::Z => Ok(parse_z(&String::from_utf8_lossy(data))),
+        }
+    }
+}
+
+/// The per-user data directory zoxide and autojump use.
+fn data_dir() -> Option<PathBuf> {
+    if cfg!(windows) {
+        env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        crate::get_home_dir().map(|home| home.join("Library").join("Application Support"))
+    } else {
+        env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| crate::get_home_dir().map(|home| home.join(".local").join("share")))
+    }
+}
+
+fn rank_to_visits(rank: f64) -> u64 {
+    if rank.is_finite() { rank.round().max(1.0) as u64 } else { 1 }
+}
+
+/// zoxide's `db.zo`: a little-endian `u32` format version (3), then a
+/// bincode list of `{path: String, rank: f64, last_accessed: u64}`.
+fn parse_zoxide(data: &[u8]) -> Result<Vec<Visit>, BwdError> {
+    let mut reader = ByteReader { data };
+    let version = reader.take(4).map(|b| u32::from_le_bytes(b.try_into().expect("four bytes")));
+    match version {
+        Some(3) => {}
+        Some(other) => return Err(BwdError::HistoryImport(format!("unsupported zoxide database version {}", other))),
+        None => return Err(BwdError::HistoryImport("zoxide database is empty".to_string())),
+    }
+    let corrupt = || BwdError::HistoryImport("zoxide database is truncated or corrupt".to_string());
+    let count = reader.u64().ok_or_else(corrupt)?;
+    let mut visits = Vec::new();
+    for _ in 0..count {
+        let len = reader.u64().ok_or_else(corrupt)?;
+        let path = reader.take(usize::try_from(len).map_err(|_| corrupt())?).ok_or_else(corrupt)?;
+        let rank = f64::from_bits(reader.u64().ok_or_else(corrupt)?);
+        let last_visit = reader.u64().ok_or_else(corrupt)?;
+        visits.push(Visit {
+            path: PathBuf::from(String::from_utf8_lossy(path).into_owned()),
+            visits: rank_to_visits(rank),
+            last_visit,
+        });
+    }
+    Ok(visits)
+}
+
+struct ByteReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.data.len() < len {
+            return None;
+        }
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Some(head)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.take(8).map(|b| u64::from_le_bytes(b.try_into().expect("eight bytes")))
+    }
+}
+
+/// autojump's `autojump.txt`: `weight<TAB>path` lines. It keeps no visit
+/// times, so everything counts as visited at `now`.
+fn parse_autojump(content: &str, now: u64) -> Vec<Visit> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let (weight, path) = line.split_once('\t')?;
+            let weight: f64 = weight.trim().parse().ok()?;
+            (!path.is_empty()).then(|| Visit { path: PathBuf::from(path), visits: rank_to_visits(weight), last_visit: now })
+        })
+        .collect()
+}
+
+/// z's `~/.z`: `path|rank|time` lines. The path may itself contain `|`.
+fn parse_z(content: &str) -> Vec<Visit> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.rsplitn(3, '|');
+            let last_visit = fields.next()?.trim().parse().ok()?;
+            let rank: f64 = fields.next()?.trim().parse().ok()?;
+            let path = fields.next().filter(|path| !path.is_empty())?;
+            Some(Visit { path: PathBuf::from(path), visits: rank_to_visits(rank), last_visit })
+        })
+        .collect()
+}
+
+/// Whether every resolve should be recorded, i.e. `BWD_HISTORY` is set to
+/// something other than empty or `0`.
+pub fn enabled_by_env() -> bool {
+    env::var("BWD_HISTORY").is_ok_and(|value| !value.is_empty() && value != "0")
+}
+
+/// Location of the history file inside the bwd config directory.
+pub fn default_history_path() -> Option<PathBuf> {
+    crate::config_dir().map(|dir| dir.join("history"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_record_dedup_and_roundtrip() {
+        let temp_dir = std::env::temp_dir();
+        let test_dir = temp_dir.join(format!("bpwd_test_history_{}", process::id()));
+        let _ = fs::remove_dir_all(&test_dir);
+        let file = test_dir.join("bwd").join("history");
+        let now = SystemTime::now();
+
+        let mut history = History::load(&file).unwrap();
+        history.record(Path::new("/srv/api"), now);
+        history.record(Path::new("/srv/with\ttab"), now + Duration::from_secs(1));
+        history.record(Path::new("/srv/api"), now + Duration::from_secs(2));
+        history.record(Path::new("/srv/bad\nname"), now);
+        history.save().unwrap();
+
+        let reloaded = History::load(&file).unwrap();
+        let recent = reloaded.recent();
+        assert_eq!(recent.len(), 2);
+        assert_eq!(recent[0].path, PathBuf::from("/srv/api"));
+        assert_eq!(recent[0].visits, 2);
+        assert_eq!(recent[1].path, PathBuf::from("/srv/with\ttab"));
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_dir);
+    }
+
+    #[test]
+    fn test_prune_keeps_most_recent() {
+        let file = std::env::temp_dir().join(format!("bpwd_test_history_prune_{}", process::id()));
+        let mut history = History::load(&file).unwrap();
+        let start = SystemTime::now();
+        for i in 0..5u64 {
+            history.record(&PathBuf::from(format!("/d{}", i)), start + Duration::from_secs(i));
+        }
+        assert_eq!(history.prune(10), 0);
+        assert_eq!(history.prune(3), 2);
+        let paths: Vec<&Path> = history.recent().iter().map(|visit| visit.path.as_path()).collect();
+        assert_eq!(paths, [Path::new("/d4"), Path::new("/d3"), Path::new("/d2")]);
+    }
+
+    #[test]
+    fn test_keyword_matching() {
+        let visit = Visit { path: PathBuf::from("/home/me/src/API-server"), visits: 1, last_visit: 0 };
+        let keywords = |words: &[&str]| words.iter().map(|word| word.to_string()).collect::<Vec<_>>();
+        assert!(visit.matches(&keywords(&["api"])));
+        assert!(visit.matches(&keywords(&["src", "server"])));
+        // Out of order, or the last keyword not in the final component
+        assert!(!visit.matches(&keywords(&["server", "src"])));
+        assert!(!visit.matches(&keywords(&["src"])));
+    }
+
+    #[test]
+    fn test_ranked_frecency_and_ties() {
+        let file = std::env::temp_dir().join(format!("bpwd_test_history_rank_{}", process::id()));
+        let mut history = History::load(&file).unwrap();
+        let now = SystemTime::now();
+        let last_month = now - Duration::from_secs(30 * 86_400);
+        for _ in 0..6 {
+            history.record(Path::new("/old/api"), last_month);
+        }
+        history.record(Path::new("/new/api"), now);
+        history.record(Path::new("/new/api"), now);
+        // Same score and time: the shorter path wins, then path order
+        history.record(Path::new("/x/api-long"), now);
+        history.record(Path::new("/b/api"), now);
+        history.record(Path::new("/a/api"), now);
+
+        let keywords = vec!["api".to_string()];
+        let ranked: Vec<&Path> = history.ranked(&keywords, now).iter().map(|visit| visit.path.as_path()).collect();
+        assert_eq!(
+            ranked,
+            [Path::new("/new/api"), Path::new("/a/api"), Path::new("/b/api"), Path::new("/x/api-long"), Path::new("/old/api")]
+        );
+    }
+
+    #[test]
+    fn test_parse_z_and_autojump() {
+        let z = parse_z("/home/me/a|b|12.6|1700000000\n/home/me/src|1|1690000000\nbroken line\n");
+        assert_eq!(z.len(), 2);
+        assert_eq!(z[0], Visit { path: PathBuf::from("/home/me/a|b"), visits: 13, last_visit: 1_700_000_000 });
+
+        let autojump = parse_autojump("22.4\t/home/me/work\n0.2\t/tmp\nnonsense\n", 42);
+        assert_eq!(autojump.len(), 2);
+        assert_eq!(autojump[0], Visit { path: PathBuf::from("/home/me/work"), visits: 22, last_visit: 42 });
+        // Tiny weights still count as one visit
+        assert_eq!(autojump[1].visits, 1);
+    }
+
+    #[test]
+    fn test_parse_zoxide_db() {
+        let mut db = 3u32.to_le_bytes().to_vec();
+        db.extend(2u64.to_le_bytes());
+        for (path, rank, time) in [("/home/me/api", 7.5f64, 1_700_000_000u64), ("/srv", 1.0, 5)] {
+            db.extend((path.len() as u64).to_le_bytes());
+            db.extend(path.as_bytes());
+            db.extend(rank.to_bits().to_le_bytes());
+            db.extend(time.to_le_bytes());
+        }
+        let visits = parse_zoxide(&db).unwrap();
+        assert_eq!(visits[0], Visit { path: PathBuf::from("/home/me/api"), visits: 8, last_visit: 1_700_000_000 });
+        assert_eq!(visits[1].path, PathBuf::from("/srv"));
+
+        assert!(matches!(parse_zoxide(&db[..db.len() - 3]), Err(BwdError::HistoryImport(_))));
+        assert!(matches!(parse_zoxide(&1u32.to_le_bytes()), Err(BwdError::HistoryImport(_))));
+    }
+
+    #[test]
+    fn test_merge_visit_adds_up() {
+        let file = std::env::temp_dir().join(format!("bpwd_test_history_merge_{}", process::id()));
+        let mut history = History::load(&file).unwrap();
+        history.record(Path::new("/srv/api"), SystemTime::UNIX_EPOCH + Duration::from_secs(100));
+        history.merge_visit(Visit { path: PathBuf::from("/srv/api"), visits: 4, last_visit: 50 });
+        history.merge_visit(Visit { path: PathBuf::from("/srv/new"), visits: 2, last_visit: 70 });
+        let recent = history.recent();
+        assert_eq!(recent[0], &Visit { path: PathBuf::from("/srv/api"), visits: 5, last_visit: 100 });
+        assert_eq!(recent[1].visits, 2);
+    }
+
+    #[test]
+    fn test_recent_json() {
+        let visit = Visit { path: PathBuf::from("/home/me/src"), visits: 3, last_visit: 1_700_000_000 };
+        let home = PathBuf::from("/home/me");
+        let JsonValue::Array(items) = recent_json(&[&visit], Some(&home)) else {
+            panic!("recent JSON should be an array");
+        };
+        let JsonValue::Object(item) = &items[0] else {
+            panic!("recent entries should be objects");
+        };
+        let short = PathBuf::from("$HOME").join("src").to_string_lossy().to_string();
+        assert_eq!(item.get("path"), Some(&JsonValue::String(short)));
+        assert_eq!(item.get("visits"), Some(&JsonValue::Number(3.0)));
+    }
+
+    #[test]
+    fn test_concurrent_updates_are_not_lost() {
+        let temp_dir = std::env::temp_dir();
+        let test_dir = temp_dir.join(format!("bpwd_test_history_lock_{}", process::id()));
+        let _ = fs::remove_dir_all(&test_dir);
+        let file = test_dir.join("history");
+
+        let writers: Vec<_> = (0..8)
+            .map(|shell| {
+                let file = file.clone();
+                thread::spawn(move || {
+                    for _ in 0..10 {
+                        History::update(&file, |history| {
+                            history.record(Path::new("/shared"), SystemTime::now());
+                            history.record(&PathBuf::from(format!("/shell{}", shell)), SystemTime::now());
+                        })
+                        .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let history = History::load(&file).unwrap();
+        assert_eq!(history.recent().len(), 9);
+        assert_eq!(history.entries[&PathBuf::from("/shared")].visits, 80);
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_dir);
+    }
+
+    #[test]
+    fn test_parse_skips_malformed_lines() {
+        let entries = parse("100\t2\t/ok\nnot a line\nx\t1\t/bad\n5\t1\t\n");
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[&PathBuf::from("/ok")].visits, 2);
+    }
+}
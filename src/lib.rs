@@ -0,0 +1,912 @@
+//! The path logic behind `bwd`, a better `pwd`, for embedding in other
+//! tools such as prompts:
+//!
+//! ```
+//! use bpwd::Resolver;
+//!
+//! let info = Resolver::new().resolve(".")?;
+//! println!("{}", info.short()); // $HOME/src/bpwd
+//! if let Some(relative) = info.relative_to_root() {
+//!     println!("{}", relative); // `.` at the project root itself
+//! }
+//! # Ok::<(), bpwd::BwdError>(())
+//! ```
+//!
+//! [`Resolver`] resolves a target the way plain `bwd` does and returns a
+//! [`PathInfo`]; [`RootSearch`] tunes how the project root is found and
+//! [`detect`] lets other tools teach it about their own workspaces. The
+//! pieces are also available on their own: [`shorten_path`], [`find_root`]
+//! and [`clean_windows_path`].
+//!
+//! With the `serde` feature, [`PathInfo`] implements `Serialize` and
+//! `Deserialize`.
+//!
+//! The crate also builds as a C shared library; see [`ffi`].
+
+pub mod detect;
+pub mod ffi;
+pub mod git;
+pub mod project;
+pub mod schema;
+// Shared with the `bwd` binary, not part of the library's API
+#[doc(hidden)]
+pub mod toml;
+
+use std::env;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use thiserror::Error;
+
+use crate::detect::RootDetector;
+use crate::git::GitRepo;
+
+/// Everything that can go wrong in bwd, from a missing path to a broken
+/// bookmark file.
+#[derive(Error, Debug)]
+pub enum BwdError {
+    #[error("IO Error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Clipboard Error: {0}")]
+    Clipboard(String),
+    #[error("Invalid path: '{0}'")]
+    InvalidPath(String),
+    #[error("Root not found")]
+    RootNotFound,
+    #[error("JSON Error: {0}")]
+    Json(String),
+    #[error("Missing value for '{0}'")]
+    MissingValue(String),
+    #[error("Root mismatch: expected '{0}', found '{1}'")]
+    RootMismatch(String, String),
+    #[error("Invalid value '{1}' for '{0}'")]
+    InvalidValue(String, String),
+    #[error("Home directory not found (neither HOME nor USERPROFILE is set)")]
+    HomeNotFound,
+    #[error("Bookmark not found: '{0}'")]
+    BookmarkNotFound(String),
+    #[error("Config directory not found")]
+    ConfigDirNotFound,
+    #[error("Bookmark already exists: '{0}' (use --force to overwrite)")]
+    BookmarkExists(String),
+    #[error("TOML Error: {0}")]
+    Toml(String),
+    #[error("Ambiguous bookmark '{0}', candidates: {1}")]
+    AmbiguousBookmark(String, String),
+    #[error("No visited directory matches '{0}'")]
+    NoHistoryMatch(String),
+    #[error("History import error: {0}")]
+    HistoryImport(String),
+    #[error("Directory stack is empty")]
+    StackEmpty,
+    #[error("No previous directory (OLDPWD is not set and the history has no other entry)")]
+    NoPreviousDirectory,
+    #[error("{0} of {1} targets failed")]
+    BatchFailed(usize, usize),
+    #[error("No web URL: {0}")]
+    WebUrl(String),
+}
+
+impl BwdError {
+    /// Stable identifier for the error variant, used by `--error-json`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            BwdError::Io(_) => "Io",
+            BwdError::Clipboard(_) => "Clipboard",
+            BwdError::InvalidPath(_) => "InvalidPath",
+            BwdError::RootNotFound => "RootNotFound",
+            BwdError::Json(_) => "Json",
+            BwdError::MissingValue(_) => "MissingValue",
+            BwdError::RootMismatch(_, _) => "RootMismatch",
+            BwdError::InvalidValue(_, _) => "InvalidValue",
+            BwdError::HomeNotFound => "HomeNotFound",
+            BwdError::BookmarkNotFound(_) => "BookmarkNotFound",
+            BwdError::ConfigDirNotFound => "ConfigDirNotFound",
+            BwdError::BookmarkExists(_) => "BookmarkExists",
+            BwdError::Toml(_) => "Toml",
+            BwdError::AmbiguousBookmark(_, _) => "AmbiguousBookmark",
+            BwdError::NoHistoryMatch(_) => "NoHistoryMatch",
+            BwdError::HistoryImport(_) => "HistoryImport",
+            BwdError::StackEmpty => "StackEmpty",
+            BwdError::NoPreviousDirectory => "NoPreviousDirectory",
+            BwdError::BatchFailed(_, _) => "BatchFailed",
+            BwdError::WebUrl(_) => "WebUrl",
+        }
+    }
+}
+
+/// Resolves targets the way plain `bwd` does: relative to the working
+/// directory, with symlinks resolved, and with the project root found by a
+/// [`RootSearch`].
+///
+/// Everything the CLI takes from the environment can be set instead, so the
+/// answer depends on nothing global:
+///
+/// ```
+/// use bpwd::Resolver;
+///
+/// let info = Resolver::new()
+///     .cwd("/srv/app")
+///     .home("/srv")
+///     .root_markers(["package.json", ".git"])
+///     .logical(true)
+///     .resolve("src/../lib")?;
+/// println!("{}", info.short()); // $HOME/app/lib
+/// # Ok::<(), bpwd::BwdError>(())
+/// ```
+#[derive(Clone)]
+pub struct Resolver {
+    cwd: Option<PathBuf>,
+    home: Option<PathBuf>,
+    logical: bool,
+    detectors: Vec<Arc<dyn RootDetector>>,
+    root_search: RootSearch,
+}
+
+impl fmt::Debug for Resolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Resolver")
+            .field("cwd", &self.cwd)
+            .field("home", &self.home)
+            .field("logical", &self.logical)
+            .field("detectors", &self.detectors.len())
+            .field("root_search", &self.root_search)
+            .finish()
+    }
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Resolver {
+    /// A resolver working from the current directory, shortening against
+    /// the home directory from the environment and using the built-in root
+    /// markers.
+    pub fn new() -> Self {
+        Resolver { cwd: None, home: get_home_dir(), logical: false, detectors: Vec::new(), root_search: RootSearch::default() }
+    }
+
+    /// A resolver searching for the root like `bwd` does without a
+    /// `config.toml`; see [`RootSearch::from_env`].
+    pub fn from_env() -> Self {
+        Resolver { root_search: RootSearch::from_env(), ..Resolver::new() }
+    }
+
+    /// Resolve relative targets against `cwd` rather than the process's
+    /// current directory.
+    pub fn cwd(mut self, cwd: impl Into<PathBuf>) -> Self {
+        self.cwd = Some(cwd.into());
+        self
+    }
+
+    /// Shorten against `home` rather than `HOME`.
+    pub fn home(mut self, home: impl Into<PathBuf>) -> Self {
+        self.home = Some(home.into());
+        self
+    }
+
+    /// Collapse `.` and `..` lexically, like `bwd --dots-only`: symlinks are
+    /// kept and the target does not need to exist.
+    pub fn logical(mut self, logical: bool) -> Self {
+        self.logical = logical;
+        self
+    }
+
+    /// Look for these root markers, in priority order, instead of the
+    /// built-in ones.
+    pub fn root_markers<I, S>(mut self, markers: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.root_search.markers = markers.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Count `work_tree` as a git checkout although it has no `.git`, as
+    /// `GIT_WORK_TREE` does for the CLI.
+    pub fn git_work_tree(mut self, work_tree: impl Into<PathBuf>) -> Self {
+        self.root_search.git_work_tree = Some(work_tree.into());
+        self
+    }
+
+    /// Search for the project root with `root_search`, replacing any
+    /// markers set so far.
+    pub fn root_search(mut self, root_search: RootSearch) -> Self {
+        self.root_search = root_search;
+        self
+    }
+
+    /// Ask `detector` for the project root before the root search. Detectors
+    /// are asked in the order they were added and the first answer wins; see
+    /// [`detect`] for the built-in ones.
+    pub fn detector(mut self, detector: impl RootDetector + 'static) -> Self {
+        self.detectors.push(Arc::new(detector));
+        self
+    }
+
+    /// The root search this resolver falls back on after its detectors.
+    pub fn search(&self) -> &RootSearch {
+        &self.root_search
+    }
+
+    /// Resolve `target`, relative to the working directory unless absolute.
+    /// Unless the resolver is logical, it has to exist.
+    pub fn resolve(&self, target: impl AsRef<Path>) -> Result<PathInfo, BwdError> {
+        let path = self.locate(target)?;
+        let root = self.find_root(&path);
+        Ok(PathInfo { path, home: self.home.clone(), root })
+    }
+
+    /// The absolute path `target` names: canonical, or for a logical
+    /// resolver normalized lexically.
+    pub fn locate(&self, target: impl AsRef<Path>) -> Result<PathBuf, BwdError> {
+        let target = target.as_ref();
+        let cwd = match &self.cwd {
+            Some(cwd) => cwd.clone(),
+            None => env::current_dir()?,
+        };
+        let requested = cwd.join(target);
+        if self.logical {
+            Ok(lexical_normalize(&requested))
+        } else if requested.exists() {
+            Ok(clean_windows_path(fs::canonicalize(&requested)?))
+        } else {
+            Err(BwdError::InvalidPath(target.to_string_lossy().to_string()))
+        }
+    }
+
+    /// The project root of `path`: the first detector's answer, else the
+    /// root search's.
+    pub fn find_root(&self, path: &Path) -> Option<PathBuf> {
+        self.detectors.iter().find_map(|detector| detector.detect(path)).or_else(|| self.root_search.find(path))
+    }
+}
+
+/// A resolved directory and what bwd knows about it.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PathInfo {
+    /// The absolute path, symlinks resolved
+    pub path: PathBuf,
+    /// The home directory it is shortened against
+    pub home: Option<PathBuf>,
+    /// The enclosing project root, if any
+    pub root: Option<PathBuf>,
+}
+
+impl PathInfo {
+    /// The path with `$HOME` for the home directory, as `bwd -s`.
+    pub fn short(&self) -> String {
+        shorten_path(&self.path, self.home.as_deref())
+    }
+
+    /// The path below the home directory, as the `home_relative` field of `bwd -j`.
+    pub fn home_relative(&self) -> Option<String> {
+        home_relative(&self.path, self.home.as_deref())
+    }
+
+    /// The path below the project root, as `bwd -r`.
+    pub fn relative_to_root(&self) -> Option<String> {
+        self.root.as_deref().map(|root| relative_to_root(&self.path, root))
+    }
+
+    /// The project's display name: `name` from its `.bwd-root`, else the
+    /// root's directory name. `None` outside a project, an error if the
+    /// root's `.bwd-root` is malformed.
+    pub fn project_name(&self) -> Result<Option<String>, BwdError> {
+        match &self.root {
+            Some(root) => project::project_name(root),
+            None => Ok(None),
+        }
+    }
+}
+
+/// The home directory: `HOME`, or `USERPROFILE` on Windows.
+pub fn get_home_dir() -> Option<PathBuf> {
+    env::var("HOME").ok().map(PathBuf::from)
+        .or_else(|| env::var("USERPROFILE").ok().map(PathBuf::from))
+}
+
+/// `path` with the home directory replaced by `$HOME`, as `bwd -s` prints it.
+pub fn shorten_path(path: &Path, home: Option<&Path>) -> String {
+    if let Some(h) = home
+        && let Ok(stripped) = path.strip_prefix(h)
+    {
+        let replacement = if stripped.as_os_str().is_empty() {
+            PathBuf::from("$HOME")
+        } else {
+            PathBuf::from("$HOME").join(stripped)
+        };
+        return replacement.to_string_lossy().to_string();
+    }
+    path.to_string_lossy().to_string()
+}
+
+/// `path` relative to `home` without any `$HOME` token (`.` at home itself).
+pub fn home_relative(path: &Path, home: Option<&Path>) -> Option<String> {
+    let stripped = path.strip_prefix(home?).ok()?;
+    Some(dot_if_empty(stripped))
+}
+
+/// `path` relative to the project `root` (`.` at the root itself).
+pub fn relative_to_root(path: &Path, root: &Path) -> String {
+    dot_if_empty(path.strip_prefix(root).unwrap_or(Path::new("")))
+}
+
+fn dot_if_empty(relative: &Path) -> String {
+    if relative.as_os_str().is_empty() {
+        ".".to_string()
+    } else {
+        relative.to_string_lossy().to_string()
+    }
+}
+
+/// Entries whose presence marks a directory as a project root: the
+/// checkouts of git, Mercurial, Subversion (1.7+, which keeps a single
+/// `.svn` at the top) and Jujutsu, plus bwd's own marker.
+pub const ROOT_MARKERS: &[&str] = &[".git", ".hg", ".svn", ".jj", ".bwd-root"];
+
+fn has_root_marker(dir: &Path, git_work_tree: Option<&Path>) -> bool {
+    ROOT_MARKERS.iter().any(|marker| has_marker(dir, marker, git_work_tree))
+}
+
+/// Whether `dir` holds `marker`. `git_work_tree`, a git work tree without a
+/// `.git` of its own (see [`RootSearch::git_work_tree`]), counts as holding
+/// `.git`.
+pub(crate) fn has_marker(dir: &Path, marker: &str, git_work_tree: Option<&Path>) -> bool {
+    dir.join(marker).exists() || (marker == ".git" && git_work_tree == Some(dir))
+}
+
+/// The nearest ancestor of `path` (or `path` itself) holding one of the
+/// built-in root markers.
+pub fn find_root(path: &Path) -> Option<PathBuf> {
+    RootSearch::default().find(path)
+}
+
+/// Collapse `.` and `..` components without touching the filesystem.
+///
+/// Symlinks are not resolved, so `a/link/..` becomes `a` even if `link` points
+/// elsewhere. Leading `..` components of a relative path are kept as-is.
+pub fn lexical_normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                // `..` at the filesystem root stays at the root
+                Some(Component::RootDir) | Some(Component::Prefix(_)) => {}
+                _ => normalized.push(".."),
+            },
+            other => normalized.push(other.as_os_str()),
+        }
+    }
+    if normalized.as_os_str().is_empty() {
+        normalized.push(".");
+    }
+    normalized
+}
+
+/// Canonicalize `path` when it exists, otherwise fall back to `lexical_normalize`.
+pub fn resolve_existing_or_lexical(path: &Path) -> PathBuf {
+    match fs::canonicalize(path) {
+        Ok(resolved) => clean_windows_path(resolved),
+        Err(_) => lexical_normalize(path),
+    }
+}
+
+/// Strip the UNC prefix (\\?\$ which is common on Windows when using canonicalize()
+pub fn clean_windows_path(path: PathBuf) -> PathBuf {
+    let path_str = path.to_string_lossy();
+    if let Some(stripped) = path_str.strip_prefix(r"\\?\") {
+        PathBuf::from(stripped)
+    } else {
+        path
+    }
+}
+
+/// How the project root is looked for. The default finds the nearest
+/// ancestor holding a VCS directory or `.bwd-root`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RootSearch {
+    /// Marker that wins regardless of depth (`--prefer-marker`)
+    pub prefer: Option<String>,
+    /// Markers in priority order (`[root] markers` or `--root-marker`);
+    /// empty means the built-in `ROOT_MARKERS`
+    pub markers: Vec<String>,
+    /// Take the outermost marked ancestor instead of the nearest (`--root=outer`)
+    pub outer: bool,
+    /// Directories the search checks but never climbs above (`[root] stop_at`)
+    pub stop_at: Vec<PathBuf>,
+    /// Also stop at the top of the path's filesystem (`[root] stop_at_mounts`)
+    pub stop_at_mounts: bool,
+    /// Let `markers` in an enclosing `.bwd-root` file replace `markers`
+    pub project_markers: bool,
+    /// The root for every path inside it, no searching (`BWD_ROOT`)
+    pub pinned: Option<PathBuf>,
+    /// A git work tree that counts as holding `.git` although it has none,
+    /// such as the one `GIT_DIR` and `GIT_WORK_TREE` name
+    pub git_work_tree: Option<PathBuf>,
+}
+
+impl RootSearch {
+    /// The search `bwd` makes before `config.toml` and flags have their say:
+    /// bounded by the home directory and mount points, with markers from
+    /// `.bwd-root` honored, the root pinned by `BWD_ROOT` and the work tree
+    /// named by `GIT_DIR` and `GIT_WORK_TREE` counting as a checkout.
+    pub fn from_env() -> Self {
+        let cwd = env::current_dir().ok();
+        RootSearch {
+            stop_at: get_home_dir().iter().map(|home| resolve_existing_or_lexical(home)).collect(),
+            stop_at_mounts: true,
+            project_markers: true,
+            pinned: cwd.as_deref().and_then(|cwd| pinned_root_from(env::var_os("BWD_ROOT"), cwd)),
+            git_work_tree: env_git_repo().map(|repo| repo.work_tree),
+            ..RootSearch::default()
+        }
+    }
+
+    /// Find the project root.
+    ///
+    /// A preferred marker is looked for first, so it wins even when a `.git`
+    /// or `.bwd-root` sits closer. Configured markers are then tried in order:
+    /// later markers only count when no ancestor has an earlier one. The
+    /// built-in markers count equally, so the nearest of them wins.
+    pub fn find(&self, path: &Path) -> Option<PathBuf> {
+        if let Some(root) = self.pinned_root(path) {
+            return Some(root);
+        }
+        let builtin: Vec<String> = ROOT_MARKERS.iter().map(|marker| marker.to_string()).collect();
+        let markers = self.markers_for(path);
+        let mut groups: Vec<&[String]> = Vec::new();
+        if let Some(prefer) = &self.prefer {
+            groups.push(std::slice::from_ref(prefer));
+        }
+        if markers.is_empty() {
+            groups.push(&builtin);
+        } else {
+            groups.extend(markers.iter().map(std::slice::from_ref));
+        }
+        groups.into_iter().find_map(|group| {
+            let mut marked = self
+                .ancestors(path)
+                .filter(|dir| group.iter().any(|marker| has_marker(dir, marker, self.git_work_tree.as_deref())));
+            let root = if self.outer { marked.last() } else { marked.next() };
+            root.map(Path::to_path_buf)
+        })
+    }
+
+    /// The pinned root, when `path` is inside it.
+    pub fn pinned_root(&self, path: &Path) -> Option<PathBuf> {
+        self.pinned.as_ref().filter(|root| path.starts_with(root)).cloned()
+    }
+
+    /// The markers that apply below `path`: those of the nearest `.bwd-root`
+    /// declaring any, when allowed, else the configured ones.
+    fn markers_for(&self, path: &Path) -> Vec<String> {
+        let declared = if self.project_markers { project::marker_override(self.ancestors(path)) } else { None };
+        declared.unwrap_or_else(|| self.markers.clone())
+    }
+
+    /// `path` and its ancestors up to and including the first boundary. A
+    /// boundary may itself be a root, e.g. dotfiles kept in git at `~`.
+    pub fn ancestors<'a>(&self, path: &'a Path) -> impl Iterator<Item = &'a Path> {
+        let mut done = false;
+        path.ancestors().take_while(move |dir| {
+            if done {
+                return false;
+            }
+            done = self.stop_at.iter().any(|stop| stop == dir) || (self.stop_at_mounts && is_mount_point(dir));
+            true
+        })
+    }
+
+    /// Whether `dir` holds any marker this search would accept.
+    pub fn is_root(&self, dir: &Path) -> bool {
+        let git_work_tree = self.git_work_tree.as_deref();
+        let marked = |marker: &String| has_marker(dir, marker, git_work_tree);
+        let markers = self.markers_for(dir);
+        self.prefer.iter().any(marked)
+            || if markers.is_empty() { has_root_marker(dir, git_work_tree) } else { markers.iter().any(marked) }
+    }
+
+    /// Everything that changes the answer, for the `--cache` key.
+    pub fn cache_key(&self) -> String {
+        let mode = if self.outer { "outer" } else { "inner" };
+        let stop_at: Vec<String> = self.stop_at.iter().map(|dir| dir.to_string_lossy().to_string()).collect();
+        let mounts = if self.stop_at_mounts { "mounts" } else { "" };
+        let project = if self.project_markers { "project" } else { "" };
+        let git_work_tree = self.git_work_tree.as_ref().map(|dir| dir.to_string_lossy().to_string());
+        format!(
+            "{}|{}|{}|{}|{}|{}|{}",
+            self.prefer.as_deref().unwrap_or(""),
+            self.markers.join(","),
+            mode,
+            stop_at.join(","),
+            mounts,
+            project,
+            git_work_tree.unwrap_or_default()
+        )
+    }
+}
+
+/// The project root given by `BWD_ROOT`, relative to `cwd` if need be.
+/// Build systems and containers that already know it spare us the search.
+/// It is resolved like the paths it is compared with, so a symlinked root
+/// still contains them.
+pub(crate) fn pinned_root_from(root: Option<OsString>, cwd: &Path) -> Option<PathBuf> {
+    let root = root.filter(|root| !root.is_empty())?;
+    Some(resolve_existing_or_lexical(&cwd.join(root)))
+}
+
+/// The repository named by `GIT_DIR`, checked out in `GIT_WORK_TREE` or, as
+/// git assumes without it, the current directory. `None` unless `GIT_DIR` is
+/// set.
+pub fn env_git_repo() -> Option<GitRepo> {
+    let cwd = env::current_dir().ok()?;
+    git_repo_from_vars(env::var_os("GIT_DIR"), env::var_os("GIT_WORK_TREE"), &cwd)
+}
+
+/// [`env_git_repo`] on the given values, relative ones taken from `cwd`.
+fn git_repo_from_vars(git_dir: Option<OsString>, work_tree: Option<OsString>, cwd: &Path) -> Option<GitRepo> {
+    let git_dir = resolve_existing_or_lexical(&cwd.join(git_dir.filter(|dir| !dir.is_empty())?));
+    let work_tree = match work_tree.filter(|dir| !dir.is_empty()) {
+        Some(dir) => resolve_existing_or_lexical(&cwd.join(dir)),
+        None => resolve_existing_or_lexical(cwd),
+    };
+    Some(GitRepo::with_git_dir(git_dir, work_tree))
+}
+
+/// Whether `dir` is on a different filesystem than its parent.
+#[cfg(unix)]
+fn is_mount_point(dir: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    let Some(parent) = dir.parent() else {
+        return false;
+    };
+    match (fs::metadata(dir), fs::metadata(parent)) {
+        (Ok(dir), Ok(parent)) => dir.dev() != parent.dev(),
+        _ => false,
+    }
+}
+
+/// Mount points are not detected outside Unix; drive roots end the walk anyway.
+#[cfg(not(unix))]
+fn is_mount_point(_dir: &Path) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+
+    #[test]
+    fn test_shorten_path_match() {
+        // Construct paths in a platform-agnostic way for testing logic
+        let home = PathBuf::from("/home/user");
+        let path = home.join("docs/project");
+        let short = shorten_path(&path, Some(&home));
+        
+        // Expected: $HOME/docs/project
+        // Note: join uses OS separator. On unix it's /, on windows \
+        // The shorten_path implementation uses PathBuf::from("$HOME").join(...)
+        // So it should match OS separator.
+        let expected = PathBuf::from("$HOME").join("docs/project").to_string_lossy().to_string();
+        assert_eq!(short, expected);
+    }
+
+    #[test]
+    fn test_shorten_path_exact_match() {
+        let home = PathBuf::from("/home/user");
+        let short = shorten_path(&home, Some(&home));
+        assert_eq!(short, "$HOME");
+    }
+
+    #[test]
+    fn test_shorten_path_no_match() {
+        let home = PathBuf::from("/home/user");
+        let path = PathBuf::from("/var/log");
+        let short = shorten_path(&path, Some(&home));
+        assert_eq!(short, path.to_string_lossy().to_string());
+    }
+
+    #[test]
+    fn test_shorten_path_no_home() {
+        let path = PathBuf::from("/home/user/docs");
+        let short = shorten_path(&path, None);
+        assert_eq!(short, path.to_string_lossy().to_string());
+    }
+
+    #[test]
+    fn test_clean_windows_path_no_prefix() {
+        let p = PathBuf::from("/usr/bin");
+        assert_eq!(clean_windows_path(p.clone()), p);
+    }
+
+    #[test]
+    fn test_clean_windows_path_with_prefix() {
+        let p = PathBuf::from(r"\\?\C:\Windows");
+        let expected = PathBuf::from(r"C:\Windows");
+        assert_eq!(clean_windows_path(p), expected);
+    }
+
+    #[test]
+    fn test_find_root_git() {
+        let temp_dir = std::env::temp_dir();
+        let test_root = temp_dir.join(format!("bpwd_test_git_{}", process::id()));
+        if test_root.exists() {
+            let _ = fs::remove_dir_all(&test_root);
+        }
+        fs::create_dir_all(&test_root).unwrap();
+        fs::create_dir(test_root.join(".git")).unwrap();
+        
+        let child = test_root.join("subdir");
+        fs::create_dir(&child).unwrap();
+
+        assert_eq!(RootSearch::default().find(&child), Some(test_root.clone()));
+        assert_eq!(RootSearch::default().find(&test_root), Some(test_root.clone()));
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_root);
+    }
+
+    #[test]
+    fn test_find_root_bwd() {
+        let temp_dir = std::env::temp_dir();
+        let test_root = temp_dir.join(format!("bpwd_test_bwd_{}", process::id()));
+        if test_root.exists() {
+            let _ = fs::remove_dir_all(&test_root);
+        }
+        fs::create_dir_all(&test_root).unwrap();
+        fs::create_dir(test_root.join(".bwd-root")).unwrap();
+        
+        let child = test_root.join("subdir/deep");
+        fs::create_dir_all(&child).unwrap();
+
+        assert_eq!(RootSearch::default().find(&child), Some(test_root.clone()));
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_root);
+    }
+
+    #[test]
+    fn test_find_root_preferring_marker() {
+        let temp_dir = std::env::temp_dir();
+        let test_root = temp_dir.join(format!("bpwd_test_prefer_{}", process::id()));
+        if test_root.exists() {
+            let _ = fs::remove_dir_all(&test_root);
+        }
+        let inner = test_root.join("inner");
+        let child = inner.join("src");
+        fs::create_dir_all(&child).unwrap();
+        fs::create_dir(test_root.join(".git")).unwrap();
+        fs::create_dir(inner.join(".bwd-root")).unwrap();
+
+        let preferring = |marker: Option<&str>| RootSearch { prefer: marker.map(String::from), ..RootSearch::default() };
+        // Nearest marker wins by default
+        assert_eq!(preferring(None).find(&child), Some(inner.clone()));
+        // A preferred marker further up wins over the nearer one
+        assert_eq!(preferring(Some(".git")).find(&child), Some(test_root.clone()));
+        // A preferred marker that is nowhere to be found falls back to the default search
+        assert_eq!(preferring(Some("bpwd-missing-marker")).find(&child), Some(inner.clone()));
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_root);
+    }
+
+    #[test]
+    fn test_find_root_other_vcs() {
+        let temp_dir = std::env::temp_dir();
+        let test_root = temp_dir.join(format!("bpwd_test_other_vcs_{}", process::id()));
+        let _ = fs::remove_dir_all(&test_root);
+
+        for marker in [".hg", ".svn", ".jj"] {
+            let checkout = test_root.join(marker.trim_start_matches('.'));
+            let child = checkout.join("src");
+            fs::create_dir_all(&child).unwrap();
+            fs::create_dir(checkout.join(marker)).unwrap();
+            assert_eq!(RootSearch::default().find(&child), Some(checkout.clone()), "{}", marker);
+        }
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_root);
+    }
+
+    #[test]
+    fn test_lexical_normalize() {
+        assert_eq!(lexical_normalize(Path::new("a/../b")), PathBuf::from("b"));
+        assert_eq!(lexical_normalize(Path::new("./x")), PathBuf::from("x"));
+        assert_eq!(lexical_normalize(Path::new("../y/./z/..")), PathBuf::from("../y"));
+        assert_eq!(lexical_normalize(Path::new("a/..")), PathBuf::from("."));
+        assert_eq!(lexical_normalize(Path::new("/a/../../b")), PathBuf::from("/b"));
+    }
+
+    #[test]
+    fn test_find_root_with_configured_markers() {
+        let temp_dir = std::env::temp_dir();
+        let test_root = temp_dir.join(format!("bpwd_test_markers_{}", process::id()));
+        let _ = fs::remove_dir_all(&test_root);
+        let package = test_root.join("packages").join("web");
+        let child = package.join("src");
+        fs::create_dir_all(&child).unwrap();
+        fs::create_dir(test_root.join(".git")).unwrap();
+        fs::write(package.join("package.json"), "{}").unwrap();
+
+        let with_markers = |names: &[&str]| RootSearch {
+            markers: names.iter().map(|name| name.to_string()).collect(),
+            ..RootSearch::default()
+        };
+        // The first configured marker found anywhere above wins, however far up
+        assert_eq!(with_markers(&[".git", "package.json"]).find(&child), Some(test_root.clone()));
+        assert_eq!(with_markers(&["package.json", ".git"]).find(&child), Some(package.clone()));
+        assert_eq!(with_markers(&["go.mod", "package.json"]).find(&child), Some(package.clone()));
+        // No configured markers means the built-in ones
+        assert_eq!(with_markers(&[]).find(&child), Some(test_root.clone()));
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_root);
+    }
+
+    #[test]
+    fn test_root_search_stops_at_boundary() {
+        let temp_dir = std::env::temp_dir();
+        let test_root = temp_dir.join(format!("bpwd_test_stop_at_{}", process::id()));
+        let _ = fs::remove_dir_all(&test_root);
+        let home = test_root.join("home");
+        let child = home.join("notes").join("drafts");
+        fs::create_dir_all(&child).unwrap();
+        fs::create_dir(test_root.join(".git")).unwrap();
+
+        let bounded = RootSearch { stop_at: vec![home.clone()], ..RootSearch::default() };
+        assert_eq!(RootSearch::default().find(&child), Some(test_root.clone()));
+        assert_eq!(bounded.find(&child), None);
+        // The boundary itself is still checked
+        fs::create_dir(home.join(".bwd-root")).unwrap();
+        assert_eq!(bounded.find(&child), Some(home.clone()));
+        // Paths outside the boundary are unaffected
+        assert_eq!(bounded.find(&test_root), Some(test_root.clone()));
+        assert_ne!(bounded.cache_key(), RootSearch::default().cache_key());
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_root);
+    }
+
+    #[test]
+    fn test_resolver_path_info() {
+        let temp_dir = std::env::temp_dir();
+        let test_root = temp_dir.join(format!("bpwd_test_resolver_{}", process::id()));
+        let _ = fs::remove_dir_all(&test_root);
+        fs::create_dir_all(test_root.join(".bwd-root")).unwrap();
+        fs::create_dir_all(test_root.join("src").join("api")).unwrap();
+
+        let info = Resolver::new().resolve(test_root.join("src").join("api")).unwrap();
+        let root = clean_windows_path(fs::canonicalize(&test_root).unwrap());
+        assert_eq!(info.path, root.join("src").join("api"));
+        assert_eq!(info.root.as_deref(), Some(root.as_path()));
+        assert_eq!(info.relative_to_root(), Some(Path::new("src").join("api").to_string_lossy().to_string()));
+        let at_home = PathInfo { home: Some(root.clone()), ..info.clone() };
+        assert_eq!(at_home.short(), PathBuf::from("$HOME").join("src").join("api").to_string_lossy());
+        assert!(matches!(Resolver::new().resolve(test_root.join("missing")), Err(BwdError::InvalidPath(_))));
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_root);
+    }
+
+    #[test]
+    fn test_resolver_builder() {
+        let temp_dir = std::env::temp_dir();
+        let test_root = temp_dir.join(format!("bpwd_test_resolver_builder_{}", process::id()));
+        let _ = fs::remove_dir_all(&test_root);
+        fs::create_dir_all(test_root.join("app").join("src")).unwrap();
+        fs::write(test_root.join("app").join("package.json"), "{}").unwrap();
+
+        let resolver = Resolver::new().cwd(test_root.join("app")).home(&test_root).root_markers(["package.json"]);
+        let info = resolver.clone().logical(true).resolve("src/../src").unwrap();
+        assert_eq!(info.path, test_root.join("app").join("src"));
+        assert_eq!(info.root, Some(test_root.join("app")));
+        assert_eq!(info.short(), PathBuf::from("$HOME").join("app").join("src").to_string_lossy());
+        // Logical targets need not exist; physical ones must
+        assert!(resolver.clone().logical(true).resolve("missing").is_ok());
+        assert!(matches!(resolver.resolve("missing"), Err(BwdError::InvalidPath(_))));
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_root);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_path_info_serde() {
+        let info = PathInfo {
+            path: PathBuf::from("/home/me/src/app"),
+            home: Some(PathBuf::from("/home/me")),
+            root: None,
+        };
+        let json = serde_json::to_string(&info).unwrap();
+        assert_eq!(json, r#"{"path":"/home/me/src/app","home":"/home/me","root":null}"#);
+        assert_eq!(serde_json::from_str::<PathInfo>(&json).unwrap(), info);
+    }
+
+    #[test]
+    fn test_git_work_tree_counts_as_git() {
+        let temp_dir = std::env::temp_dir();
+        let test_root = temp_dir.join(format!("bpwd_test_git_work_tree_{}", process::id()));
+        let _ = fs::remove_dir_all(&test_root);
+        fs::create_dir_all(&test_root).unwrap();
+
+        // A GIT_WORK_TREE has no .git of its own
+        assert!(!has_marker(&test_root, ".git", None));
+        assert!(has_marker(&test_root, ".git", Some(&test_root)));
+        assert!(!has_marker(&test_root, ".hg", Some(&test_root)));
+        assert!(has_root_marker(&test_root, Some(&test_root)));
+        assert!(!has_root_marker(&test_root, Some(&temp_dir)));
+
+        // Only when given explicitly; nothing is read from the environment
+        let child = test_root.join("child");
+        fs::create_dir_all(&child).unwrap();
+        assert_eq!(RootSearch::default().find(&child), None);
+        let search = RootSearch { git_work_tree: Some(test_root.clone()), ..RootSearch::default() };
+        assert_eq!(search.find(&child), Some(test_root.clone()));
+        assert_ne!(search.cache_key(), RootSearch::default().cache_key());
+        let resolver = Resolver::new().logical(true).git_work_tree(&test_root);
+        assert_eq!(resolver.resolve(&child).unwrap().root, Some(test_root.clone()));
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_root);
+    }
+
+    #[test]
+    fn test_git_repo_from_vars() {
+        let temp_dir = std::env::temp_dir();
+        let test_root = temp_dir.join(format!("bpwd_test_git_env_{}", process::id()));
+        let _ = fs::remove_dir_all(&test_root);
+        fs::create_dir_all(test_root.join("dotfiles.git")).unwrap();
+        fs::create_dir_all(test_root.join("home").join(".config")).unwrap();
+        let test_root = fs::canonicalize(&test_root).unwrap();
+
+        // A bare dotfiles repository checked out to a home directory
+        let repo = git_repo_from_vars(Some("dotfiles.git".into()), Some(test_root.join("home").into()), &test_root).unwrap();
+        assert_eq!(repo.git_dir, test_root.join("dotfiles.git"));
+        assert_eq!(repo.work_tree, test_root.join("home"));
+        // Without GIT_WORK_TREE the current directory is the work tree
+        let cwd = test_root.join("home").join(".config");
+        let repo = git_repo_from_vars(Some(test_root.join("dotfiles.git").into()), None, &cwd).unwrap();
+        assert_eq!(repo.work_tree, cwd);
+        assert_eq!(git_repo_from_vars(None, Some("home".into()), &test_root), None);
+        assert_eq!(git_repo_from_vars(Some("".into()), None, &test_root), None);
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_root);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_pinned_root_is_canonical() {
+        let temp_dir = std::env::temp_dir();
+        let test_root = temp_dir.join(format!("bpwd_test_pinned_link_{}", process::id()));
+        let _ = fs::remove_dir_all(&test_root);
+        fs::create_dir_all(test_root.join("real").join("src")).unwrap();
+        std::os::unix::fs::symlink(test_root.join("real"), test_root.join("link")).unwrap();
+        let real = fs::canonicalize(test_root.join("real")).unwrap();
+
+        let pinned = pinned_root_from(Some("link".into()), &test_root);
+        assert_eq!(pinned, Some(real.clone()));
+        let search = RootSearch { pinned, ..RootSearch::default() };
+        assert_eq!(search.find(&real.join("src")), Some(real.clone()));
+        // A root that does not exist is still taken, normalized lexically
+        assert_eq!(pinned_root_from(Some("gone/../later".into()), &test_root), Some(test_root.join("later")));
+        assert_eq!(pinned_root_from(Some("".into()), &test_root), None);
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_root);
+    }
+}
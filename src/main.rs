@@ -1,411 +1,3603 @@
+mod bookmarks;
+mod clipboard;
+mod color;
+mod glob;
+mod history;
+mod settings;
+mod shell;
+mod stack;
+mod wsl;
+
+use std::cell::Cell;
 use std::env;
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::{Component, Path, PathBuf};
 use std::process;
-use std::collections::HashMap;
-use thiserror::Error;
+use std::sync::Once;
+use std::thread;
+use std::time::{Duration, SystemTime};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use tinyjson::JsonValue;
 
-/// Custom error types for bpwd
-#[derive(Error, Debug)]
-enum BwdError {
-    #[error("IO Error: {0}")]
-    Io(#[from] std::io::Error),
-    #[error("Clipboard Error: {0}")]
-    Clipboard(String),
-    #[error("Invalid path: '{0}'")]
-    InvalidPath(String),
-    #[error("Root not found")]
-    RootNotFound,
-    #[error("JSON Error: {0}")]
-    Json(String),
-}
+use bpwd::{
+    BwdError, Resolver, RootSearch, clean_windows_path, env_git_repo, get_home_dir, git, home_relative,
+    lexical_normalize, project, relative_to_root, resolve_existing_or_lexical, schema, shorten_path, toml,
+};
+
+use bookmarks::{Bookmark, BookmarkStore, ConflictPolicy, SortOrder};
+use clipboard::Clipboard;
+use color::{ColorMode, Theme};
+use git::GitRepo;
+use history::{History, HistorySource};
+use project::ProjectMeta;
+use settings::Settings;
+use shell::{Dialect, Shell};
+
+/// Subcommands, recognized as the first positional argument.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Command {
+    Save,
+    Get,
+    List,
+    Remove,
+    Rename,
+    Export,
+    Import,
+    Visit,
+    Recent,
+    Jump,
+    ImportHistory,
+    Init,
+    Completions,
+    Complete,
+    Push,
+    Pop,
+    Stack,
+    Root,
+    Prompt,
+}
+
+impl Command {
+    /// The user-facing subcommands, as offered by shell completion.
+    const ALL: [Command; 18] = [
+        Command::Save,
+        Command::Get,
+        Command::List,
+        Command::Remove,
+        Command::Rename,
+        Command::Export,
+        Command::Import,
+        Command::Visit,
+        Command::Recent,
+        Command::Jump,
+        Command::ImportHistory,
+        Command::Init,
+        Command::Completions,
+        Command::Push,
+        Command::Pop,
+        Command::Stack,
+        Command::Root,
+        Command::Prompt,
+    ];
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "save" => Some(Command::Save),
+            "get" => Some(Command::Get),
+            "list" => Some(Command::List),
+            "rm" => Some(Command::Remove),
+            "mv" => Some(Command::Rename),
+            "export" => Some(Command::Export),
+            "import" => Some(Command::Import),
+            "visit" => Some(Command::Visit),
+            "recent" => Some(Command::Recent),
+            "jump" => Some(Command::Jump),
+            "import-history" => Some(Command::ImportHistory),
+            "init" => Some(Command::Init),
+            "completions" => Some(Command::Completions),
+            "__complete" => Some(Command::Complete),
+            "push" => Some(Command::Push),
+            "pop" => Some(Command::Pop),
+            "stack" => Some(Command::Stack),
+            "root" => Some(Command::Root),
+            "prompt" => Some(Command::Prompt),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Command::Save => "save",
+            Command::Get => "get",
+            Command::List => "list",
+            Command::Remove => "rm",
+            Command::Rename => "mv",
+            Command::Export => "export",
+            Command::Import => "import",
+            Command::Visit => "visit",
+            Command::Recent => "recent",
+            Command::Jump => "jump",
+            Command::ImportHistory => "import-history",
+            Command::Init => "init",
+            Command::Completions => "completions",
+            Command::Complete => "__complete",
+            Command::Push => "push",
+            Command::Pop => "pop",
+            Command::Stack => "stack",
+            Command::Root => "root",
+            Command::Prompt => "prompt",
+        }
+    }
+}
+
+#[derive(Default)]
+struct Config {
+    target: Option<String>,
+    command: Option<Command>,
+    operands: Vec<String>,
+    copy: bool,
+    /// The clipboard `--copy=NAME` picked
+    copy_backend: Option<clipboard::Backend>,
+    /// Template for the copied text, independent of what is printed
+    copy_format: Option<String>,
+    short: bool,
+    json: bool,
+    root: bool,
+    assert_root: Option<String>,
+    home_relative_only: bool,
+    prefer_marker: Option<String>,
+    type_suffix: bool,
+    home_depth: bool,
+    dots_only: bool,
+    cache: bool,
+    common_with: Option<String>,
+    rel_threshold: Option<usize>,
+    from_root: Option<String>,
+    sanitize: bool,
+    git: bool,
+    safe: bool,
+    git_root: bool,
+    bytes: bool,
+    relative_to: Vec<String>,
+    require_home: bool,
+    no_resolve: bool,
+    print_sep: bool,
+    pretty_prompt: bool,
+    last: Option<usize>,
+    json_root_abs: bool,
+    copy_stdin: bool,
+    force: bool,
+    tags: Vec<String>,
+    on_conflict: Option<String>,
+    ttl: Option<Duration>,
+    sort: Option<String>,
+    from: Option<String>,
+    count: Option<usize>,
+    escape: Option<Dialect>,
+    as_cd: bool,
+    /// The target is `-`, the previous directory
+    previous: bool,
+    nu: bool,
+    root_markers: Vec<String>,
+    root_outer: bool,
+    format: Option<String>,
+    /// Terminate entries with NUL instead of a newline (`-0`)
+    null: bool,
+    json_pretty: bool,
+    yaml: bool,
+    uri: bool,
+    /// `--web`: the path's page on the repository's forge
+    web: bool,
+    /// `--permalink`: that page at the current commit rather than the branch
+    permalink: bool,
+    git_pathspec: bool,
+    /// Separator forced by `--unix` or `--windows`
+    separator: Option<char>,
+    wsl: bool,
+    win: bool,
+    /// Where `--msys` or `--cygwin` mount drives
+    drive_root: Option<&'static str>,
+    split: bool,
+    /// Separator for `--breadcrumb`
+    breadcrumb: Option<String>,
+    /// The shell whose prompt `bwd prompt` is embedded in (`--shell`)
+    prompt_shell: Option<Shell>,
+    /// `--color[=auto|always|never]`; bare `--color` means always
+    color: Option<ColorMode>,
+    /// Trailing components `--abbrev` leaves whole
+    abbrev: Option<usize>,
+    max_width: Option<usize>,
+    /// Targets after the first, each with whether it is `-`
+    more_targets: Vec<(String, bool)>,
+    /// Read more targets from stdin
+    stdin: bool,
+    /// Keep only the first match of a glob target
+    first: bool,
+    /// Report failing targets and carry on with the rest
+    keep_going: bool,
+    /// Resolve the path on the clipboard
+    paste: bool,
+    /// Drop batch entries resolving to a path already printed
+    unique: bool,
+    error_json: bool,
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Err(err) = run(&args) {
+        // Checked on the raw args so that even parse errors honor --error-json.
+        if flag_args(&args).iter().any(|arg| arg == "--error-json") {
+            eprintln!("{}", error_json(&err));
+        } else {
+            eprintln!("[bwd error] {}", err);
+        }
+        process::exit(1);
+    }
+}
+
+/// The arguments before the `--` separator, i.e. the ones that may be flags.
+fn flag_args(args: &[String]) -> &[String] {
+    let flags_end = args.iter().position(|arg| arg == "--").unwrap_or(args.len());
+    &args[..flags_end]
+}
+
+/// Render a JSON value in NUON, Nushell's object notation, with record
+/// keys sorted so the output is stable.
+fn to_nuon(value: &JsonValue) -> String {
+    match value {
+        JsonValue::Null => "null".to_string(),
+        JsonValue::Boolean(b) => b.to_string(),
+        JsonValue::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => format!("{}", *n as i64),
+        JsonValue::Number(n) => n.to_string(),
+        JsonValue::String(s) => nuon_string(s),
+        JsonValue::Array(items) => {
+            let items: Vec<String> = items.iter().map(to_nuon).collect();
+            format!("[{}]", items.join(", "))
+        }
+        JsonValue::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            let fields: Vec<String> = keys
+                .into_iter()
+                .map(|key| {
+                    let bare = !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+                    let key_str = if bare { key.clone() } else { nuon_string(key) };
+                    format!("{}: {}", key_str, to_nuon(&map[key]))
+                })
+                .collect();
+            format!("{{{}}}", fields.join(", "))
+        }
+    }
+}
+
+fn nuon_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Render a JSON value as a block-style YAML document, keys sorted like
+/// `to_nuon`. Strings are always double-quoted (JSON escapes are valid
+/// YAML), so values such as `no`, `1.0` or `~` keep their type.
+fn to_yaml(value: &JsonValue) -> String {
+    let mut out = String::new();
+    write_yaml(value, 0, &mut out);
+    out
+}
+
+fn write_yaml(value: &JsonValue, indent: usize, out: &mut String) {
+    let pad = "  ".repeat(indent);
+    match value {
+        JsonValue::Object(map) if !map.is_empty() => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            for key in keys {
+                let bare = !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+                let key_str = if bare { key.clone() } else { yaml_scalar(&JsonValue::String(key.clone())) };
+                if is_yaml_block(&map[key]) {
+                    out.push_str(&format!("{}{}:\n", pad, key_str));
+                    write_yaml(&map[key], indent + 1, out);
+                } else {
+                    out.push_str(&format!("{}{}: {}\n", pad, key_str, yaml_scalar(&map[key])));
+                }
+            }
+        }
+        JsonValue::Array(items) if !items.is_empty() => {
+            for item in items {
+                if is_yaml_block(item) {
+                    out.push_str(&format!("{}-\n", pad));
+                    write_yaml(item, indent + 1, out);
+                } else {
+                    out.push_str(&format!("{}- {}\n", pad, yaml_scalar(item)));
+                }
+            }
+        }
+        scalar => out.push_str(&format!("{}{}\n", pad, yaml_scalar(scalar))),
+    }
+}
+
+/// Non-empty records and lists, which YAML writes as indented blocks.
+fn is_yaml_block(value: &JsonValue) -> bool {
+    match value {
+        JsonValue::Object(map) => !map.is_empty(),
+        JsonValue::Array(items) => !items.is_empty(),
+        _ => false,
+    }
+}
+
+fn yaml_scalar(value: &JsonValue) -> String {
+    match value {
+        JsonValue::Object(_) => "{}".to_string(),
+        JsonValue::Array(_) => "[]".to_string(),
+        // JSON's own spelling of strings, booleans and null is YAML too
+        JsonValue::String(_) | JsonValue::Boolean(_) | JsonValue::Null => value.stringify().unwrap_or_default(),
+        JsonValue::Number(_) => to_nuon(value),
+    }
+}
+
+/// Render an error as `{"error":"<kind>","message":"<display>"}`.
+fn error_json(err: &BwdError) -> String {
+    JsonValue::Object(error_record(err))
+        .stringify()
+        .unwrap_or_else(|_| format!("{{\"error\":\"{}\"}}", err.kind()))
+}
+
+fn error_record(err: &BwdError) -> HashMap<String, JsonValue> {
+    let mut map = HashMap::new();
+    map.insert("error".to_string(), JsonValue::String(err.kind().to_string()));
+    map.insert("message".to_string(), JsonValue::String(err.to_string()));
+    map
+}
+
+fn run(args: &[String]) -> Result<(), BwdError> {
+    // Check for help/version flags, but respect the -- separator.
+    let flags_slice = flag_args(args);
+
+    if flags_slice.iter().any(|arg| arg == "-h" || arg == "--help") {
+        print_help();
+        return Ok(());
+    }
+
+    if flags_slice.iter().any(|arg| arg == "-v" || arg == "--version") {
+        println!("bwd v{}", env!("CARGO_PKG_VERSION"));
+        return Ok(());
+    }
+
+    let config = parse_config(args)?;
+
+    if config.print_sep {
+        println!("{}", path_separator());
+        return Ok(());
+    }
+
+    // Plain pbcopy-style mode, independent of path resolution
+    if config.copy_stdin {
+        let text = copy_stdin(io::stdin().lock(), &mut clipboard_backend(&config))?;
+        print!("{}", text);
+        io::stdout().flush()?;
+        return Ok(());
+    }
+
+    if config.command == Some(Command::Init) {
+        let name = command_operand(&config, 0)?;
+        let shell = Shell::from_name(name).ok_or_else(|| BwdError::InvalidValue("init".to_string(), name.to_string()))?;
+        print!("{}", shell.init_script());
+        return Ok(());
+    }
+
+    if config.command == Some(Command::Completions) {
+        let name = command_operand(&config, 0)?;
+        let shell =
+            Shell::from_name(name).ok_or_else(|| BwdError::InvalidValue("completions".to_string(), name.to_string()))?;
+        print!("{}", shell.completion_script());
+        return Ok(());
+    }
+
+    // Hidden entry point for the completion scripts: one candidate per line.
+    if config.command == Some(Command::Complete) {
+        match command_operand(&config, 0)? {
+            "commands" => {
+                for command in Command::ALL {
+                    println!("{}", command.name());
+                }
+            }
+            "flags" => {
+                for flag in FLAGS {
+                    println!("{}", flag);
+                }
+            }
+            "bookmarks" => {
+                // Completion must never print an error into the user's prompt
+                let store = bookmark_store_path().and_then(|path| BookmarkStore::load(&path));
+                if let Ok(mut store) = store {
+                    if let Ok(cwd) = env::current_dir()
+                        && let Some(root) = settings_root_search().find(&cwd)
+                    {
+                        store.overlay(project_bookmarks(&root));
+                    }
+                    for (name, _) in store.iter() {
+                        println!("{}", name);
+                    }
+                }
+            }
+            other => return Err(BwdError::InvalidValue("__complete".to_string(), other.to_string())),
+        }
+        return Ok(());
+    }
+
+    if config.command == Some(Command::List) {
+        let store = BookmarkStore::load(&bookmark_store_path()?)?;
+        let home_dir = if config.short { get_home_dir() } else { None };
+        let mut entries = bookmarks::filter_by_tags(&store, &config.tags);
+        if let Some(name) = &config.sort {
+            let order = SortOrder::from_name(name)
+                .ok_or_else(|| BwdError::InvalidValue("--sort".to_string(), name.clone()))?;
+            bookmarks::sort_entries(&mut entries, order, SystemTime::now());
+        }
+        if config.json {
+            let json = bookmarks::list_json(&entries, home_dir.as_deref());
+            println!("{}", json_text(&json, config.json_pretty)?);
+        } else if !entries.is_empty() {
+            println!("{}", bookmarks::format_list(&entries, home_dir.as_deref()));
+        }
+        return Ok(());
+    }
+
+    if matches!(config.command, Some(Command::Remove | Command::Rename)) {
+        BookmarkStore::update(&bookmark_store_path()?, |store| {
+            if config.command == Some(Command::Remove) {
+                store.remove(command_operand(&config, 0)?)?;
+            } else {
+                let old = command_operand(&config, 0)?;
+                let new = command_operand(&config, 1)?;
+                store.rename(old, new, config.force)?;
+            }
+            Ok(())
+        })?;
+        return Ok(());
+    }
+
+    if config.command == Some(Command::Export) {
+        let store = BookmarkStore::load(&bookmark_store_path()?)?;
+        match config.operands.first().map(String::as_str) {
+            None | Some("json") => println!("{}", store.to_json()?),
+            Some("toml") => print!("{}", store.to_toml()),
+            Some(other) => return Err(BwdError::InvalidValue("export".to_string(), other.to_string())),
+        }
+        return Ok(());
+    }
+
+    if config.command == Some(Command::Import) {
+        let source = command_operand(&config, 0)?;
+        let content = if source == "-" {
+            let mut content = String::new();
+            io::stdin().read_to_string(&mut content)?;
+            content
+        } else {
+            fs::read_to_string(source)?
+        };
+        // Without an explicit format, a .toml extension means TOML and anything else JSON
+        let format = match config.operands.get(1) {
+            Some(format) => format.as_str(),
+            None if source.ends_with(".toml") => "toml",
+            None => "json",
+        };
+        let incoming = match format {
+            "json" => bookmarks::parse_json(&content)?,
+            "toml" => bookmarks::parse_toml(&content)?,
+            other => return Err(BwdError::InvalidValue("import".to_string(), other.to_string())),
+        };
+        let policy = match (&config.on_conflict, config.force) {
+            (Some(name), _) => ConflictPolicy::from_name(name)
+                .ok_or_else(|| BwdError::InvalidValue("--on-conflict".to_string(), name.clone()))?,
+            (None, true) => ConflictPolicy::Overwrite,
+            (None, false) => ConflictPolicy::Error,
+        };
+
+        let report = BookmarkStore::update(&bookmark_store_path()?, |store| store.merge(incoming, policy))?;
+        eprintln!(
+            "Imported bookmarks: {} added, {} replaced, {} kept, {} unchanged",
+            report.added, report.replaced, report.skipped, report.unchanged
+        );
+        return Ok(());
+    }
+
+    if config.command == Some(Command::Recent) {
+        let history = History::load(&history_path()?)?;
+        let home_dir = if config.short { get_home_dir() } else { None };
+        let recent: Vec<_> = history.recent().into_iter().take(config.count.unwrap_or(10)).collect();
+        if config.json {
+            let json = history::recent_json(&recent, home_dir.as_deref());
+            println!("{}", json_text(&json, config.json_pretty)?);
+        } else {
+            for visit in recent {
+                print_entry(&shorten_path(&visit.path, home_dir.as_deref()), config.null);
+            }
+        }
+        return Ok(());
+    }
+
+    if config.command == Some(Command::ImportHistory) {
+        let name = config.from.as_deref().ok_or_else(|| BwdError::MissingValue("--from".to_string()))?;
+        let source =
+            HistorySource::from_name(name).ok_or_else(|| BwdError::InvalidValue("--from".to_string(), name.to_string()))?;
+        let database = match config.operands.first() {
+            Some(file) => PathBuf::from(file),
+            None => source.default_path().ok_or(BwdError::HomeNotFound)?,
+        };
+        if !database.is_file() {
+            return Err(BwdError::InvalidPath(database.to_string_lossy().to_string()));
+        }
+        let imported = source.parse(&fs::read(&database)?, SystemTime::now())?;
+        let count = imported.len();
+
+        let settings = load_settings();
+        let home = get_home_dir();
+        History::update(&history_path()?, |history| {
+            for visit in imported {
+                if !settings.history_excluded(&visit.path, home.as_deref()) {
+                    history.merge_visit(visit);
+                }
+            }
+            history.prune(history::MAX_ENTRIES);
+        })?;
+        eprintln!("Imported {} directories from {}", count, name);
+        return Ok(());
+    }
+
+    if config.command == Some(Command::Stack) {
+        let home_dir = if config.short { get_home_dir() } else { None };
+        for path in stack::load(&stack_path()?)?.iter().rev() {
+            print_entry(&shorten_path(path, home_dir.as_deref()), config.null);
+        }
+        return Ok(());
+    }
+
+    let cwd = env::current_dir().map_err(BwdError::Io)?;
+    let settings = load_settings();
+    let root_search = RootSearch {
+        prefer: config.prefer_marker.clone(),
+        // Markers given on the command line replace the configured ones
+        markers: if config.root_markers.is_empty() { settings.root_markers.clone() } else { config.root_markers.clone() },
+        outer: config.root_outer,
+        stop_at: stop_at_dirs(&settings),
+        stop_at_mounts: settings.stop_at_mounts.unwrap_or(true),
+        // ... and so do the ones a project declares in its `.bwd-root`
+        project_markers: config.root_markers.is_empty(),
+        ..RootSearch::from_env()
+    };
+    let resolver = Resolver::new().cwd(&cwd).logical(config.dots_only).root_search(root_search);
+
+    // `bwd save NAME [PATH]`, `bwd visit [PATH]`, `bwd push [PATH]`,
+    // `bwd root [PATH]` and `bwd prompt [PATH]` take their path as an
+    // operand rather than a target.
+    let target = match config.command {
+        Some(Command::Save) => config.operands.get(1),
+        Some(Command::Visit | Command::Push | Command::Root | Command::Prompt) => config.operands.first(),
+        _ => config.target.as_ref(),
+    };
+    let mut targets = vec![(target.cloned(), config.previous)];
+    targets.extend(config.more_targets.iter().map(|(target, previous)| (Some(target.clone()), *previous)));
+    if config.stdin {
+        // Piped paths replace the current directory as the default target
+        targets.retain(|(target, _)| target.is_some());
+        let mut input = Vec::new();
+        io::stdin().read_to_end(&mut input)?;
+        let stdin = stdin_targets(&input, config.null)?;
+        targets.extend(stdin.into_iter().map(|target| (Some(target), false)));
+    }
+    if config.paste {
+        // So does the path on the clipboard
+        targets.retain(|(target, _)| target.is_some());
+        let pasted = pasted_target(&clipboard::get_contents()?, get_home_dir().as_deref(), |name| env::var(name).ok())?;
+        targets.push((Some(pasted), false));
+    }
+    // Expand glob targets ourselves, for shells that don't (a path that
+    // exists as written is taken literally)
+    let mut globbed = false;
+    let mut expanded = Vec::new();
+    for (target, previous) in targets {
+        match target {
+            Some(pattern) if config.command.is_none() && glob::is_pattern(&pattern) && !cwd.join(&pattern).exists() => {
+                let mut matches = glob::expand(&pattern, &cwd);
+                if matches.is_empty() {
+                    return Err(BwdError::InvalidPath(pattern));
+                }
+                if config.first {
+                    matches.truncate(1);
+                } else {
+                    globbed = true;
+                }
+                expanded.extend(matches.into_iter().map(|path| (Some(path.to_string_lossy().to_string()), false)));
+            }
+            target => expanded.push((target, previous)),
+        }
+    }
+    let targets = expanded;
+
+    // `bwd list` has its own keys; a batch can only be ordered by path
+    let sort_paths = match config.sort.as_deref() {
+        None => false,
+        Some("path") => true,
+        Some(other) => return Err(BwdError::InvalidValue("--sort".to_string(), other.to_string())),
+    };
+
+    let context = Context { cwd, settings, resolver };
+    let records = config.json || config.nu || config.yaml;
+    let mut entries = Vec::new();
+    let mut failed = 0;
+    for ((target, _), result) in targets.iter().zip(resolve_all(&config, &context, &targets)) {
+        match result {
+            Ok(entry) => entries.extend(entry.map(|(path, entry)| (Some(path), entry))),
+            // With --keep-going a failure is reported in place of its entry
+            Err(err) if config.keep_going => {
+                failed += 1;
+                if records {
+                    let mut record = error_record(&err);
+                    let target = target.clone().map_or(JsonValue::Null, JsonValue::String);
+                    record.insert("target".to_string(), target);
+                    entries.push((None, Entry::Record(JsonValue::Object(record))));
+                } else if config.error_json {
+                    eprintln!("{}", error_json(&err));
+                } else {
+                    eprintln!("[bwd error] {}", err);
+                }
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    // Both work on the resolved paths, so `a/../b` and `b` are the same entry
+    if config.unique {
+        let mut seen = HashSet::new();
+        entries.retain(|(path, _)| path.as_ref().is_none_or(|path| seen.insert(path.clone())));
+    }
+    if sort_paths {
+        // Failed targets have no path and go last, in input order
+        entries.sort_by(|(a, _), (b, _)| a.is_none().cmp(&b.is_none()).then_with(|| a.cmp(b)));
+    }
+    let entries = entries.into_iter().map(|(_, entry)| entry).collect();
+    emit_entries(&config, entries, config.stdin || globbed || targets.len() > 1)?;
+    if failed > 0 {
+        return Err(BwdError::BatchFailed(failed, targets.len()));
+    }
+    Ok(())
+}
+
+/// Paths piped to `--stdin`: NUL-separated with `-0` (as from
+/// `find -print0`), otherwise one per line. Empty entries are skipped.
+/// Targets are strings like the arguments, so a path that is not UTF-8 is
+/// rejected rather than mangled into one that can never resolve.
+fn stdin_targets(input: &[u8], null: bool) -> Result<Vec<String>, BwdError> {
+    let separator = if null { b'\0' } else { b'\n' };
+    input
+        .split(|&byte| byte == separator)
+        .map(|entry| if null { entry } else { entry.strip_suffix(b"\r").unwrap_or(entry) })
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            String::from_utf8(entry.to_vec()).map_err(|_| {
+                BwdError::InvalidPath(format!("{} (not valid UTF-8)", String::from_utf8_lossy(entry).escape_debug()))
+            })
+        })
+        .collect()
+}
+
+/// The path in pasted `text`: its first non-blank line without surrounding
+/// quotes, with `~`, `$VAR`, `${VAR}` and `%VAR%` expanded through `var`.
+fn pasted_target(text: &str, home: Option<&Path>, var: impl Fn(&str) -> Option<String>) -> Result<String, BwdError> {
+    let line = text.lines().map(str::trim).find(|line| !line.is_empty()).unwrap_or_default();
+    let line = ['"', '\'', '`']
+        .iter()
+        .find_map(|&quote| line.strip_prefix(quote).and_then(|rest| rest.strip_suffix(quote)))
+        .unwrap_or(line);
+    if line.is_empty() {
+        return Err(BwdError::Clipboard("the clipboard holds no path".to_string()));
+    }
+    Ok(expand_vars(line, home, var))
+}
+
+/// Expand a leading `~` to `home` and `$VAR`, `${VAR}` and `%VAR%` through
+/// `var`. Unknown variables are left as written.
+fn expand_vars(s: &str, home: Option<&Path>, var: impl Fn(&str) -> Option<String>) -> String {
+    let s = match (s.strip_prefix('~'), home) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with(['/', '\\']) => {
+            format!("{}{}", home.to_string_lossy(), rest)
+        }
+        _ => s.to_string(),
+    };
+    let is_name = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s.as_str();
+    while let Some(start) = rest.find(['$', '%']) {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let (name, len) = if rest[start..].starts_with("${") {
+            match after[1..].find('}') {
+                Some(end) => (&after[1..end + 1], end + 3),
+                None => ("", 1),
+            }
+        } else if rest[start..].starts_with('%') {
+            match after.find('%') {
+                Some(end) if after[..end].chars().all(is_name) => (&after[..end], end + 2),
+                _ => ("", 1),
+            }
+        } else {
+            let end = after.find(|c: char| !is_name(c)).unwrap_or(after.len());
+            (&after[..end], end + 1)
+        };
+        match var(name).filter(|_| !name.is_empty()) {
+            Some(value) => out.push_str(&value),
+            None => out.push_str(&rest[start..start + len]),
+        }
+        rest = &rest[start + len..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// What `run` sets up once for all targets.
+struct Context {
+    cwd: PathBuf,
+    settings: Settings,
+    /// Locates targets and their roots, as the library does
+    resolver: Resolver,
+}
+
+/// The output for one target: a record for `-j` and friends, or the text to
+/// print (suffix and colors included) and the text to copy.
+enum Entry {
+    Record(JsonValue),
+    Text { shown: String, copied: String },
+}
+
+/// Batches at least this large are spread over a thread per core.
+const PARALLEL_THRESHOLD: usize = 64;
+
+/// Resolve every target, in input order. Large batches (thousands of paths
+/// from `--stdin`, say) are split into contiguous chunks, one per thread, so
+/// the results still come back in the order the targets were given.
+fn resolve_all(config: &Config, context: &Context, targets: &[(Option<String>, bool)]) -> Vec<Result<Option<(PathBuf, Entry)>, BwdError>> {
+    let resolve = |(target, previous): &(Option<String>, bool)| resolve_target(config, context, target.as_ref(), *previous);
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    if targets.len() < PARALLEL_THRESHOLD || threads == 1 {
+        return targets.iter().map(resolve).collect();
+    }
+    let chunk_size = targets.len().div_ceil(threads);
+    thread::scope(|scope| {
+        let workers: Vec<_> = targets
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || chunk.iter().map(resolve).collect::<Vec<_>>()))
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("resolver thread panicked"))
+            .collect()
+    })
+}
+
+/// Resolve one target and render it, alongside the resolved path it shows.
+/// `previous` marks a `-` target. `None` means there is nothing to print
+/// (`bwd visit`, `--assert-root`).
+fn resolve_target(config: &Config, context: &Context, target: Option<&String>, previous: bool) -> Result<Option<(PathBuf, Entry)>, BwdError> {
+    let Context { cwd, settings, resolver } = context;
+    // `--wsl` also takes Windows paths: `C:\Users` is `/mnt/c/Users`
+    let wsl_target = if config.wsl { target.and_then(|t| wsl::to_wsl(t, &wsl::mount_root())) } else { None };
+    let target = wsl_target.as_ref().or(target);
+
+    // The path as the user named it, before symlinks are resolved.
+    let requested_path = match (config.command, target) {
+        (Some(Command::Get), _) => {
+            let name = command_operand(config, 0)?;
+            let store_path = bookmark_store_path()?;
+            let mut store = BookmarkStore::load(&store_path)?;
+            // Bookmarks from the enclosing project's `.bwd` file take precedence
+            if let Some(root) = resolver.find_root(cwd) {
+                store.overlay(project_bookmarks(&root));
+            }
+            let (resolved, bookmark) = store.resolve_fuzzy(name)?;
+            let path = bookmark.path.clone();
+
+            // Usage statistics are best effort: a read-only config directory
+            // must not break lookups. The overlaid store is never saved.
+            let _ = BookmarkStore::update(&store_path, |stats| Ok(stats.record_use(resolved, &path, SystemTime::now())));
+            path
+        }
+        (Some(Command::Jump), _) => {
+            command_operand(config, 0)?;
+            let history = History::load(&history_path()?)?;
+            // Directories that are gone, or the one we are already in, are no use
+            let best = history
+                .ranked(&config.operands, SystemTime::now())
+                .into_iter()
+                .find(|visit| visit.path != *cwd && visit.path.is_dir());
+            best.map(|visit| visit.path.clone()).ok_or_else(|| BwdError::NoHistoryMatch(config.operands.join(" ")))?
+        }
+        (Some(Command::Pop), _) => stack::pop(&stack_path()?)?,
+        (_, Some(_)) if previous => previous_dir(cwd)?,
+        (_, Some(t)) => cwd.join(t),
+        _ => cwd.clone(),
+    };
+
+    // With --dots-only the resolver is logical: no symlink resolution and
+    // no existence requirement
+    let final_path = if config.dots_only || target.is_some() || matches!(config.command, Some(Command::Get | Command::Jump | Command::Pop)) {
+        // A missing path is reported as the user typed it
+        resolver.locate(&requested_path).map_err(|err| match (err, target) {
+            (BwdError::InvalidPath(_), Some(target)) => BwdError::InvalidPath(target.clone()),
+            (err, _) => err,
+        })?
+    } else {
+        cwd.clone()
+    };
+
+    if config.command == Some(Command::Save) {
+        let name = command_operand(config, 0)?;
+        let mut bookmark = Bookmark::new(final_path.clone()).with_tags(&config.tags);
+        if let Some(ttl) = config.ttl {
+            bookmark = bookmark.with_ttl(ttl, SystemTime::now())?;
+        }
+        BookmarkStore::update(&bookmark_store_path()?, |store| store.add(name, bookmark, config.force))?;
+    }
+
+    if config.command == Some(Command::Visit) {
+        record_visit(&final_path)?;
+        return Ok(None);
+    }
+    // Like pushd: remember where we are, then print where to go
+    if config.command == Some(Command::Push) {
+        stack::push(&stack_path()?, cwd)?;
+    }
+    // With BWD_HISTORY=1 every resolve counts as a visit. Like bookmark usage
+    // statistics this is best effort and never fails the command.
+    if matches!(config.command, None | Some(Command::Get | Command::Jump)) && history::enabled_by_env() {
+        let _ = record_visit(&final_path);
+    }
+
+    let locate_root = |path: &Path| {
+        if config.cache {
+            root_cache_file().map_or_else(|| resolver.find_root(path), |file| cached_find_root(path, &file, resolver.search()))
+        } else {
+            resolver.find_root(path)
+        }
+    };
+
+    // `bwd root` prints the root itself, through the usual output modes
+    let final_path = if config.command == Some(Command::Root) {
+        locate_root(&final_path).ok_or(BwdError::RootNotFound)?
+    } else {
+        final_path
+    };
+
+    // Root assertion is a pure check: succeed silently or fail loudly.
+    if let Some(expected) = &config.assert_root {
+        assert_root_name(locate_root(&final_path), expected)?;
+        return Ok(None);
+    }
+
+    let absolute_str = final_path.to_string_lossy().to_string();
+
+    // Determine home directory for shortening
+    let home_dir = require_home(get_home_dir(), config.require_home)?;
+
+    // JSON Output Priority (NUON and YAML are the same record in other notations)
+    if config.json || config.nu || config.yaml {
+        let root = locate_root(&final_path);
+        let mut map = build_json(&final_path, home_dir.as_deref(), root.as_deref());
+        if config.json_root_abs {
+            insert_root_paths(&mut map, &final_path, root.as_deref());
+        }
+        insert_cargo_roots(&mut map, &final_path, resolver.search());
+        let repo = discover_repo(&final_path);
+        insert_git_paths(&mut map, repo.as_ref());
+        insert_file_info(&mut map, &final_path, &requested_path);
+        if !config.relative_to.is_empty() {
+            let bases: Vec<PathBuf> = config.relative_to.iter().map(|base| cwd.join(base)).collect();
+            let resolve = !(config.no_resolve || config.dots_only);
+            let relative = relative_to_bases(&requested_path, &bases, resolve).to_string_lossy().to_string();
+            map.insert("relative_to".to_string(), JsonValue::String(relative));
+        }
+        let project = root.as_deref().and_then(project_name);
+        map.insert("project".to_string(), project.map_or(JsonValue::Null, JsonValue::String));
+        let name = repo_name(repo.as_ref(), root.as_deref());
+        map.insert("repo".to_string(), name.map_or(JsonValue::Null, JsonValue::String));
+        if config.git {
+            // The enclosing work tree, which configured markers may put below the root
+            let dirty = repo.as_ref().and_then(GitRepo::is_dirty);
+            map.insert("dirty".to_string(), dirty.map_or(JsonValue::Null, JsonValue::Boolean));
+        }
+        return Ok(Some((final_path, Entry::Record(schema::v1(map)))));
+    }
+
+    let color_mode = config.color.or(settings.color_mode).unwrap_or(ColorMode::Never);
+    let theme = color_mode.enabled(io::stdout().is_terminal()).then_some(&settings.color);
+
+    let output_str = if config.command == Some(Command::Prompt) {
+        let root = locate_root(&final_path);
+        let project = root.as_deref().and_then(|root| project_name(root).map(|name| (root, name)));
+        let project = project.as_ref().map(|(root, name)| (*root, name.as_str()));
+        prompt_segment(&final_path, home_dir.as_deref(), project, config.last.unwrap_or(2), theme, config.prompt_shell)
+    } else if let Some(template) = &config.format {
+        let root = locate_root(&final_path);
+        render_template(template, &template_values(template, &final_path, home_dir.as_deref(), root.as_deref())?)?
+    } else if config.win {
+        wsl::to_windows(&absolute_str, &wsl::mount_root(), wsl::distro_name().as_deref())
+            .ok_or_else(|| BwdError::InvalidPath(absolute_str.clone()))?
+    } else if let Some(root) = config.drive_root {
+        // Paths without a drive (UNC shares) just get forward slashes
+        wsl::to_wsl(&absolute_str, root).unwrap_or_else(|| convert_separators(&absolute_str, '/'))
+    } else if let Some(keep) = config.abbrev {
+        abbreviate_path(&final_path, home_dir.as_deref(), keep)
+    } else if let Some(separator) = &config.breadcrumb {
+        breadcrumb(&final_path, separator, config.last)
+    } else if config.split {
+        // One entry per component, terminated like every other entry
+        path_components(&final_path).join(if config.null { "\0" } else { "\n" })
+    } else if config.uri {
+        file_uri(&absolute_str, cfg!(windows))
+    } else if config.web {
+        web_location(&final_path, config.permalink)?
+    } else if config.git_pathspec {
+        git_pathspec(&final_path)?
+    } else if config.pretty_prompt {
+        pretty_prompt(&final_path, home_dir.as_deref(), config.last.unwrap_or(2))
+    } else if !config.relative_to.is_empty() {
+        let bases: Vec<PathBuf> = config.relative_to.iter().map(|base| cwd.join(base)).collect();
+        let resolve = !(config.no_resolve || config.dots_only);
+        relative_to_bases(&requested_path, &bases, resolve).to_string_lossy().to_string()
+    } else if config.bytes {
+        path_byte_len(&final_path).to_string()
+    } else if config.git_root {
+        let toplevel = resolve_git_root(&final_path).ok_or(BwdError::RootNotFound)?;
+        toplevel.to_string_lossy().to_string()
+    } else if let Some(fragment) = &config.from_root {
+        join_root(locate_root(&final_path), fragment)?.to_string_lossy().to_string()
+    } else if let Some(other) = &config.common_with {
+        let other_path = resolve_existing_or_lexical(&cwd.join(other));
+        common_ancestor(&final_path, &other_path).to_string_lossy().to_string()
+    } else if let Some(threshold) = config.rel_threshold {
+        relative_or_absolute(&final_path, locate_root(&final_path).as_deref(), threshold)
+    } else if config.home_depth {
+        // -1 is the documented sentinel for "not under home"
+        match home_depth(&final_path, home_dir.as_deref()) {
+            Some(depth) => depth.to_string(),
+            None => "-1".to_string(),
+        }
+    } else if config.home_relative_only {
+        // The strict home-relative mode wins over plain -s, being the more specific request
+        home_relative_path(&final_path, home_dir.as_deref())?
+    } else if config.short {
+        shorten_path(&final_path, home_dir.as_deref())
+    } else if config.root {
+        let root = locate_root(&final_path).ok_or(BwdError::RootNotFound)?;
+        relative_to_root(&final_path, &root)
+    } else {
+        absolute_str
+    };
+
+    let output_str = match config.max_width {
+        Some(max) if !(config.uri || config.web) => fit_width(&output_str, max),
+        _ => output_str,
+    };
+
+    // Forced separators, whatever the host uses (a URI keeps its own)
+    let output_str = match config.separator {
+        Some(separator) if !(config.uri || config.web) => convert_separators(&output_str, separator),
+        _ => output_str,
+    };
+
+    let output_str = if config.sanitize {
+        sanitize_for_filename(&output_str)
+    } else {
+        output_str
+    };
+
+    // Quoted for a shell, so both printed and copied
+    let output_str = if config.as_cd {
+        config.escape.unwrap_or(Dialect::Posix).cd_command(&output_str)
+    } else if let Some(dialect) = config.escape {
+        dialect.quote(&output_str)
+    } else {
+        output_str
+    };
+
+    // The type suffix and control escaping are purely visual, so they are
+    // printed but never copied.
+    let suffix = if config.type_suffix && !output_str.ends_with(std::path::MAIN_SEPARATOR) {
+        classify_suffix(&requested_path)
+    } else {
+        ""
+    };
+    let shown = if config.safe { escape_control(&output_str) } else { output_str.clone() };
+    // Colors would end up inside quoted commands and split entries, and the
+    // prompt segment has its own
+    let plain = config.escape.is_some() || config.as_cd || config.split || config.command == Some(Command::Prompt);
+    let shown = match theme {
+        Some(theme) if !plain => {
+            let home_prefixes = home_dir.as_deref().map(|home| color::spellings(home, Some(home))).unwrap_or_default();
+            let roots = locate_root(&final_path).map(|root| color::spellings(&root, home_dir.as_deref())).unwrap_or_default();
+            color::colorize_path(&shown, &home_prefixes, &roots, theme)
+        }
+        _ => shown,
+    };
+    // `--copy-format` renders the copied text on its own
+    let copied = match &config.copy_format {
+        Some(template) if config.copy => {
+            let root = locate_root(&final_path);
+            render_template(template, &template_values(template, &final_path, home_dir.as_deref(), root.as_deref())?)
+                .map_err(|_| BwdError::InvalidValue("--copy-format".to_string(), template.clone()))?
+        }
+        // File objects need the real location, whatever is printed
+        _ if config.copy_backend == Some(clipboard::Backend::File) => final_path.to_string_lossy().to_string(),
+        _ => output_str,
+    };
+    Ok(Some((final_path, Entry::Text { shown: format!("{}{}", shown, suffix), copied })))
+}
+
+/// Print the entries, and copy the text ones (one per line) for `-c`. A
+/// batch of records is printed as a list, a single one on its own.
+fn emit_entries(config: &Config, entries: Vec<Entry>, batch: bool) -> Result<(), BwdError> {
+    let mut records = Vec::new();
+    let mut copied = Vec::new();
+    for entry in entries {
+        match entry {
+            Entry::Record(record) => records.push(record),
+            Entry::Text { shown, copied: text } => {
+                print_entry(&shown, config.null);
+                copied.push(text);
+            }
+        }
+    }
+
+    if !records.is_empty() {
+        let value = if batch { JsonValue::Array(records) } else { records.remove(0) };
+        if config.nu {
+            println!("{}", to_nuon(&value));
+        } else if config.yaml {
+            print!("{}", to_yaml(&value));
+        } else {
+            println!("{}", json_text(&value, config.json_pretty)?);
+        }
+    }
+
+    if config.copy && !copied.is_empty() {
+        // The paths are printed already; a failed copy only earns a warning
+        if let Err(err) = clipboard_backend(config).set_contents(copied.join("\n")) {
+            eprintln!("[bwd warning] {}", err);
+        }
+    }
+    Ok(())
+}
+
+/// The clipboards for `-c`: `--copy=NAME` if given, else the configured
+/// `[clipboard] command`, else the automatic choice, followed by the fallbacks.
+fn clipboard_backend(config: &Config) -> clipboard::Fallback {
+    let command = load_settings().clipboard_command;
+    let primary = match &config.copy_backend {
+        Some(backend) => backend.clone(),
+        None => command.clone().map_or(clipboard::Backend::Auto, clipboard::Backend::Command),
+    };
+    clipboard::Fallback::new(primary, command)
+}
+
+/// Serialize `value` on one line, or indented for `--json-pretty`.
+fn json_text(value: &JsonValue, pretty: bool) -> Result<String, BwdError> {
+    let text = if pretty { value.format() } else { value.stringify() };
+    text.map_err(|e| BwdError::Json(format!("{:?}", e)))
+}
+
+/// Print one output entry, terminated by NUL for `-0` (safe for `xargs -0`
+/// whatever the path contains) or by a newline.
+fn print_entry(entry: &str, null: bool) {
+    if null {
+        print!("{}\0", entry);
+    } else {
+        println!("{}", entry);
+    }
+}
+
+/// Like `flag_value`, but parses the value (e.g. a number) and reports bad input.
+fn parse_flag_value<T: std::str::FromStr>(flag: &str, iter: &mut impl Iterator<Item = String>) -> Result<T, BwdError> {
+    let value = flag_value(flag, iter)?;
+    value.parse().map_err(|_| BwdError::InvalidValue(flag.to_string(), value))
+}
+
+/// Parse a duration like `90s`, `15m`, `2h`, `7d` or `1w`. A bare number
+/// means seconds.
+fn parse_duration(value: &str) -> Option<Duration> {
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+    let amount: u64 = amount.parse().ok()?;
+    let seconds = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
+    Some(Duration::from_secs(amount.checked_mul(seconds)?))
+}
+
+/// Seconds since the Unix epoch, clamped to zero for clocks set before it.
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// A use count weighted by how long ago the last use was, in the spirit of
+/// zoxide: within the hour counts four times, within the day twice, within
+/// the week half, anything older a quarter.
+fn frecency(count: u64, last_used: u64, now: SystemTime) -> f64 {
+    let age = unix_seconds(now).saturating_sub(last_used);
+    let weight = match age {
+        0..3_600 => 4.0,
+        3_600..86_400 => 2.0,
+        86_400..604_800 => 0.5,
+        _ => 0.25,
+    };
+    count as f64 * weight
+}
+
+/// Build the `-j` object: absolute `path`, `$HOME`-tokenized `short`,
+/// token-free `home_relative`, the `root`-relative subpath and its `bytes`.
+fn build_json(path: &Path, home: Option<&Path>, root: Option<&Path>) -> HashMap<String, JsonValue> {
+    let string_or_null = |value: Option<String>| value.map_or(JsonValue::Null, JsonValue::String);
+
+    let mut map = HashMap::new();
+    map.insert("path".to_string(), JsonValue::String(path.to_string_lossy().to_string()));
+    map.insert("short".to_string(), JsonValue::String(shorten_path(path, home)));
+    map.insert("home_relative".to_string(), string_or_null(home_relative(path, home)));
+    map.insert("root".to_string(), string_or_null(root.map(|r| relative_to_root(path, r))));
+    map.insert("bytes".to_string(), JsonValue::Number(path_byte_len(path) as f64));
+    let components = path_components(path).into_iter().map(JsonValue::String).collect();
+    map.insert("components".to_string(), JsonValue::Array(components));
+    map
+}
+
+/// The components of `path`, the root (with any drive prefix) being the
+/// first, as in Python's `PurePath.parts`: `["/", "home", "me"]`.
+fn path_components(path: &Path) -> Vec<String> {
+    let mut parts: Vec<String> = Vec::new();
+    let mut anchor = String::new();
+    for component in path.components() {
+        match component {
+            Component::Prefix(prefix) => anchor.push_str(&prefix.as_os_str().to_string_lossy()),
+            Component::RootDir => anchor.push(std::path::MAIN_SEPARATOR),
+            other => {
+                if !anchor.is_empty() {
+                    parts.push(std::mem::take(&mut anchor));
+                }
+                parts.push(other.as_os_str().to_string_lossy().to_string());
+            }
+        }
+    }
+    if !anchor.is_empty() {
+        parts.push(anchor);
+    }
+    parts
+}
+
+/// Disambiguate the JSON `root` field (which holds the path *below* the root):
+/// `root_path` is the root directory itself and `relative` repeats the subpath
+/// under a clearer name. `root` stays for existing consumers.
+fn insert_root_paths(map: &mut HashMap<String, JsonValue>, path: &Path, root: Option<&Path>) {
+    let (root_path, relative) = match root {
+        Some(r) => (
+            JsonValue::String(r.to_string_lossy().to_string()),
+            JsonValue::String(relative_to_root(path, r)),
+        ),
+        None => (JsonValue::Null, JsonValue::Null),
+    };
+    map.insert("root_path".to_string(), root_path);
+    map.insert("relative".to_string(), relative);
+}
+
+/// The Cargo package containing `path` (nearest `Cargo.toml`) and its
+/// workspace root: the nearest `Cargo.toml` from the package up that has a
+/// `[workspace]` section, or the package itself when there is none, as
+/// `cargo metadata` reports it. Neither is looked for above the boundaries
+/// of `search`.
+fn cargo_roots(path: &Path, search: &RootSearch) -> Option<(PathBuf, PathBuf)> {
+    let package = search.ancestors(path).find(|dir| dir.join("Cargo.toml").is_file())?;
+    let workspace = search
+        .ancestors(package)
+        .find(|dir| fs::read_to_string(dir.join("Cargo.toml")).is_ok_and(|manifest| declares_workspace(&manifest)))
+        .unwrap_or(package);
+    Some((package.to_path_buf(), workspace.to_path_buf()))
+}
+
+/// Whether a manifest has a `[workspace]` (or `[workspace.*]`) table. Only
+/// the headers are looked at, since Cargo.toml uses more TOML than `toml.rs` reads.
+fn declares_workspace(manifest: &str) -> bool {
+    manifest.lines().map(str::trim).any(|line| line == "[workspace]" || line.starts_with("[workspace."))
+}
+
+/// Add `package_root` and `workspace_root` (null outside a Cargo project).
+fn insert_cargo_roots(map: &mut HashMap<String, JsonValue>, path: &Path, search: &RootSearch) {
+    let (package, workspace) = match cargo_roots(path, search) {
+        Some((package, workspace)) => (
+            JsonValue::String(package.to_string_lossy().to_string()),
+            JsonValue::String(workspace.to_string_lossy().to_string()),
+        ),
+        None => (JsonValue::Null, JsonValue::Null),
+    };
+    map.insert("package_root".to_string(), package);
+    map.insert("workspace_root".to_string(), workspace);
+}
+
+/// Add `exists`, `is_dir`, `depth` (named components below the filesystem
+/// root) and `basename` for `path`, and `is_symlink` for the path as the user
+/// `requested` it, since the resolved one never is a link.
+fn insert_file_info(map: &mut HashMap<String, JsonValue>, path: &Path, requested: &Path) {
+    let depth = path.components().filter(|c| matches!(c, Component::Normal(_))).count();
+    let basename = path.file_name().map(|name| name.to_string_lossy().to_string());
+    let is_symlink = fs::symlink_metadata(requested).is_ok_and(|meta| meta.file_type().is_symlink());
+    map.insert("exists".to_string(), JsonValue::Boolean(path.exists()));
+    map.insert("is_dir".to_string(), JsonValue::Boolean(path.is_dir()));
+    map.insert("is_symlink".to_string(), JsonValue::Boolean(is_symlink));
+    map.insert("depth".to_string(), JsonValue::Number(depth as f64));
+    map.insert("basename".to_string(), basename.map_or(JsonValue::Null, JsonValue::String));
+}
+
+/// Add `git_dir` (where the work tree's git data lives), `main_repo` (the
+/// main repository's work tree, which differs from the enclosing work tree
+/// for linked worktrees and submodules) and `branch` (null when detached).
+/// All are null outside git.
+fn insert_git_paths(map: &mut HashMap<String, JsonValue>, repo: Option<&GitRepo>) {
+    let git_dir = repo.map(|repo| repo.git_dir.to_string_lossy().to_string());
+    let branch = repo.and_then(GitRepo::branch);
+    let main_repo = repo.and_then(|repo| repo.main_work_tree()).map(|dir| dir.to_string_lossy().to_string());
+    map.insert("git_dir".to_string(), git_dir.map_or(JsonValue::Null, JsonValue::String));
+    map.insert("main_repo".to_string(), main_repo.map_or(JsonValue::Null, JsonValue::String));
+    map.insert("branch".to_string(), branch.map_or(JsonValue::Null, JsonValue::String));
+}
+
+/// Copy everything read from `reader` to the clipboard verbatim (a trailing
+/// newline included) and hand the text back so it can be echoed unchanged.
+fn copy_stdin(mut reader: impl Read, clipboard: &mut impl Clipboard) -> Result<String, BwdError> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+    clipboard.set_contents(text.clone())?;
+    Ok(text)
+}
+
+fn parse_config(args: &[String]) -> Result<Config, BwdError> {
+    let mut config = Config::default();
+    let mut parsing_flags = true;
+    let mut iter = args.iter();
+
+    while let Some(arg) = iter.next() {
+        if parsing_flags && arg == "--" {
+            parsing_flags = false;
+            continue;
+        }
+
+        // A lone `-` is not a flag: it names the previous directory, or stdin for `import`
+        if parsing_flags && arg.starts_with('-') && arg != "-" {
+            // `--flag=value` is the same as `--flag value`
+            let (arg, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+                _ => (arg.as_str(), None),
+            };
+            // Taken by whichever arm reads a value, inline or not
+            let inline_value = Cell::new(inline_value);
+            let mut iter = std::iter::from_fn(|| inline_value.take()).chain(iter.by_ref().cloned());
+            match arg {
+                "-c" | "--copy" => {
+                    config.copy = true;
+                    // `--copy=osc52` also picks the clipboard
+                    if let Some(name) = inline_value.take() {
+                        config.copy_backend = Some(
+                            clipboard::Backend::from_name(&name)
+                                .ok_or_else(|| BwdError::InvalidValue(arg.to_string(), name.clone()))?,
+                        );
+                    }
+                }
+                "-0" | "--null" => config.null = true,
+                "-s" | "--short" => config.short = true,
+                "-j" | "--json" => config.json = true,
+                "--json-pretty" => {
+                    config.json = true;
+                    config.json_pretty = true;
+                }
+                "-r" | "--root" => {
+                    config.root = true;
+                    // `--root=inner|outer` also picks which marked ancestor is the root
+                    match inline_value.take().as_deref() {
+                        None | Some("inner") => {}
+                        Some("outer") => config.root_outer = true,
+                        Some(other) => return Err(BwdError::InvalidValue(arg.to_string(), other.to_string())),
+                    }
+                }
+                "--home-relative-only" => config.home_relative_only = true,
+                "--longest-common-with" => config.common_with = Some(flag_value(arg, &mut iter)?),
+                "--on-conflict" => config.on_conflict = Some(flag_value(arg, &mut iter)?),
+                "--ttl" => {
+                    let value = flag_value(arg, &mut iter)?;
+                    let ttl = parse_duration(&value).ok_or_else(|| BwdError::InvalidValue(arg.to_string(), value))?;
+                    config.ttl = Some(ttl);
+                }
+                "--nu" => config.nu = true,
+                "--yaml" => config.yaml = true,
+                "--uri" => config.uri = true,
+                "--web" => config.web = true,
+                "--git-pathspec" => config.git_pathspec = true,
+                "--permalink" => {
+                    config.web = true;
+                    config.permalink = true;
+                }
+                "--wsl" => config.wsl = true,
+                "--win" => config.win = true,
+                "--split" => config.split = true,
+                "--color" => {
+                    let mode = inline_value.take().unwrap_or_else(|| "always".to_string());
+                    config.color = Some(ColorMode::from_name(&mode).ok_or_else(|| BwdError::InvalidValue(arg.to_string(), mode))?);
+                }
+                "--max-width" => config.max_width = Some(parse_flag_value(arg, &mut iter)?),
+                // Like `--breadcrumb`, the optional count only comes inline
+                "--abbrev" => {
+                    let keep = inline_value.take().unwrap_or_else(|| "1".to_string());
+                    config.abbrev = Some(keep.parse().map_err(|_| BwdError::InvalidValue(arg.to_string(), keep))?);
+                }
+                // The separator is optional, so it can only be given inline
+                "--breadcrumb" => config.breadcrumb = Some(inline_value.take().unwrap_or_else(|| " › ".to_string())),
+                "--msys" => config.drive_root = Some(wsl::MSYS_ROOT),
+                "--cygwin" => config.drive_root = Some(wsl::CYGWIN_ROOT),
+                "--unix" => config.separator = Some('/'),
+                "--windows" => config.separator = Some('\\'),
+                "--format" => config.format = Some(flag_value(arg, &mut iter)?),
+                "--copy-format" => {
+                    config.copy = true;
+                    config.copy_format = Some(flag_value(arg, &mut iter)?);
+                }
+                "--root-marker" => config.root_markers.push(flag_value(arg, &mut iter)?),
+                "--as-cd" => config.as_cd = true,
+                "--shell" => {
+                    let value = flag_value(arg, &mut iter)?;
+                    let shell = Shell::from_name(&value).ok_or_else(|| BwdError::InvalidValue(arg.to_string(), value))?;
+                    config.prompt_shell = Some(shell);
+                }
+                "--escape" => {
+                    let value = flag_value(arg, &mut iter)?;
+                    let dialect = Dialect::from_name(&value).ok_or_else(|| BwdError::InvalidValue(arg.to_string(), value))?;
+                    config.escape = Some(dialect);
+                }
+                "-n" | "--count" => config.count = Some(parse_flag_value(arg, &mut iter)?),
+                "--from" => config.from = Some(flag_value(arg, &mut iter)?),
+                "--sort" => config.sort = Some(flag_value(arg, &mut iter)?),
+                "--unique" => config.unique = true,
+                "--tag" => config.tags.push(flag_value(arg, &mut iter)?),
+                "-f" | "--force" => config.force = true,
+                "--stdin" => config.stdin = true,
+                "--paste" => config.paste = true,
+                "--first" => config.first = true,
+                "--keep-going" => config.keep_going = true,
+                "--copy-stdin" => config.copy_stdin = true,
+                "--json-root-abs" => config.json_root_abs = true,
+                "--pretty-prompt" => config.pretty_prompt = true,
+                "--last" => config.last = Some(parse_flag_value(arg, &mut iter)?),
+                "--print-sep" => config.print_sep = true,
+                "--no-resolve" => config.no_resolve = true,
+                "--require-home" => config.require_home = true,
+                "--relative-to" => config.relative_to.push(flag_value(arg, &mut iter)?),
+                "--bytes" => config.bytes = true,
+                "--git-root" => config.git_root = true,
+                "--safe" => config.safe = true,
+                // The flag is a record field, so it implies a record format,
+                // which like a plain -j wins over -s, -r and the other text outputs
+                "--git" | "--status" => {
+                    config.json = true;
+                    config.git = true;
+                }
+                "--sanitize" => config.sanitize = true,
+                "--from-root" => config.from_root = Some(flag_value(arg, &mut iter)?),
+                "--rel-threshold" => config.rel_threshold = Some(parse_flag_value(arg, &mut iter)?),
+                // Also checked by main, which sees errors from parsing too
+                "--error-json" => config.error_json = true,
+                "--cache" => config.cache = true,
+                "--dots-only" => config.dots_only = true,
+                "--home-depth" => config.home_depth = true,
+                "--type-suffix" => config.type_suffix = true,
+                "--prefer-marker" => config.prefer_marker = Some(flag_value(arg, &mut iter)?),
+                "--assert-root" => config.assert_root = Some(flag_value(arg, &mut iter)?),
+                _ => continue, // Ignore unknown flags
+            }
+            // A flag that takes no value must not drop one: `--json=no` is
+            // not `--json`
+            if let Some(value) = inline_value.take() {
+                return Err(BwdError::InvalidValue(arg.to_string(), value));
+            }
+            continue;
+        }
+
+        // The first positional may name a subcommand; everything positional
+        // after it belongs to that subcommand. Use `--` for a target that
+        // happens to share a subcommand's name.
+        if config.command.is_some() {
+            config.operands.push(arg.clone());
+            continue;
+        }
+        if parsing_flags
+            && config.target.is_none()
+            && let Some(command) = Command::from_name(arg)
+        {
+            config.command = Some(command);
+            continue;
+        }
+
+        // If it's not a flag (or we stopped parsing flags), it's the target
+        validate_target(arg)?;
+        if config.target.is_none() {
+            config.previous = parsing_flags && arg == "-";
+            config.target = Some(arg.clone());
+        } else {
+            config.more_targets.push((arg.clone(), parsing_flags && arg == "-"));
+        }
+    }
+    Ok(config)
+}
+
+/// Reject targets the OS could never open, before any filesystem call turns
+/// them into an opaque IO error.
+fn validate_target(target: &str) -> Result<(), BwdError> {
+    if target.contains('\0') {
+        return Err(BwdError::InvalidPath(format!(
+            "{} (contains an embedded NUL byte)",
+            target.escape_debug()
+        )));
+    }
+    Ok(())
+}
+
+/// The `index`-th operand of the current subcommand.
+fn command_operand(config: &Config, index: usize) -> Result<&str, BwdError> {
+    match (config.command, config.operands.get(index)) {
+        (_, Some(operand)) => Ok(operand),
+        (Some(command), None) => Err(BwdError::MissingValue(command.name().to_string())),
+        (None, None) => Err(BwdError::MissingValue("command".to_string())),
+    }
+}
+
+/// Take the value that follows a flag such as `--assert-root NAME`.
+fn flag_value(flag: &str, iter: &mut impl Iterator<Item = String>) -> Result<String, BwdError> {
+    iter.next().ok_or_else(|| BwdError::MissingValue(flag.to_string()))
+}
+
+/// Directory for bwd's own data files, such as bookmarks.
+///
+/// `BWD_CONFIG_DIR` wins if set; otherwise `%APPDATA%\bwd` on Windows,
+/// `~/Library/Application Support/bwd` on macOS and `$XDG_CONFIG_HOME/bwd`
+/// (falling back to `~/.config/bwd`) elsewhere.
+fn config_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("BWD_CONFIG_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    let base = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        get_home_dir().map(|home| home.join("Library").join("Application Support"))
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| get_home_dir().map(|home| home.join(".config")))
+    };
+    base.map(|dir| dir.join("bwd"))
+}
+
+fn bookmark_store_path() -> Result<PathBuf, BwdError> {
+    bookmarks::default_store_path().ok_or(BwdError::ConfigDirNotFound)
+}
+
+fn stack_path() -> Result<PathBuf, BwdError> {
+    stack::default_stack_path().ok_or(BwdError::ConfigDirNotFound)
+}
+
+/// Run `f` while holding an exclusive advisory lock on `<file>.lock`, so
+/// that concurrent bwd processes take turns on a read-modify-write of `file`.
+fn with_lock<T>(file: &Path, f: impl FnOnce() -> Result<T, BwdError>) -> Result<T, BwdError> {
+    if let Some(parent) = file.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let lock = fs::File::create(file.with_extension("lock"))?;
+    lock.lock()?;
+    f()
+    // The lock is released when `lock` is dropped
+}
+
+/// Replace `file` with `contents` by renaming a finished temporary file over
+/// it, so readers never see a half-written file.
+fn write_atomic(file: &Path, contents: &str) -> Result<(), BwdError> {
+    if let Some(parent) = file.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let temp = file.with_extension(format!("tmp.{}", process::id()));
+    fs::write(&temp, contents)?;
+    fs::rename(&temp, file)?;
+    Ok(())
+}
+
+fn history_path() -> Result<PathBuf, BwdError> {
+    history::default_history_path().ok_or(BwdError::ConfigDirNotFound)
+}
+
+/// The directory `-` stands for, like `cd -`: `$OLDPWD`, or failing that the
+/// most recently visited directory in the history other than `cwd`.
+fn previous_dir(cwd: &Path) -> Result<PathBuf, BwdError> {
+    if let Some(oldpwd) = env::var_os("OLDPWD").filter(|value| !value.is_empty()) {
+        return Ok(PathBuf::from(oldpwd));
+    }
+    let history = History::load(&history_path()?)?;
+    let previous = history.recent().into_iter().find(|visit| visit.path != cwd);
+    previous.map(|visit| visit.path.clone()).ok_or(BwdError::NoPreviousDirectory)
+}
+
+/// The root search configured in `config.toml`, for when there are no flags
+/// to honor.
+fn settings_root_search() -> RootSearch {
+    let settings = load_settings();
+    RootSearch {
+        stop_at: stop_at_dirs(&settings),
+        stop_at_mounts: settings.stop_at_mounts.unwrap_or(true),
+        markers: settings.root_markers,
+        ..RootSearch::from_env()
+    }
+}
+
+/// The configured search boundaries, resolved like the paths searched from
+/// so that a symlinked `$HOME` still stops the search.
+fn stop_at_dirs(settings: &Settings) -> Vec<PathBuf> {
+    settings.stop_at_dirs(get_home_dir().as_deref()).iter().map(|dir| resolve_existing_or_lexical(dir)).collect()
+}
+
+/// The nearest enclosing git work tree of `path`, the one `GIT_DIR` names
+/// included.
+fn discover_repo(path: &Path) -> Option<GitRepo> {
+    GitRepo::discover_with(path, env_git_repo().as_ref())
+}
+
+/// Settings from `config.toml`, or defaults when there is no config directory.
+fn load_settings() -> Settings {
+    load_settings_from(settings::default_settings_path())
+}
+
+/// Settings from `file`. A broken config is reported once and then ignored,
+/// so it never keeps bwd from printing a path.
+fn load_settings_from(file: Option<PathBuf>) -> Settings {
+    let Some(file) = file else {
+        return Settings::default();
+    };
+    Settings::load(&file).unwrap_or_else(|err| {
+        static WARNED: Once = Once::new();
+        WARNED.call_once(|| eprintln!("[bwd warning] {}", err));
+        Settings::default()
+    })
+}
+
+/// Add `path` to the visit history. Only directories are recorded, and
+/// never ones matching a `history.exclude` pattern.
+fn record_visit(path: &Path) -> Result<(), BwdError> {
+    if !path.is_dir() || load_settings().history_excluded(path, get_home_dir().as_deref()) {
+        return Ok(());
+    }
+    History::update(&history_path()?, |history| {
+        history.record(path, SystemTime::now());
+        history.prune(history::MAX_ENTRIES);
+    })
+}
+
+/// Without a home directory, shortening silently leaves paths absolute (and
+/// JSON `short` equals `path`). `--require-home` turns that into an error.
+fn require_home(home: Option<PathBuf>, required: bool) -> Result<Option<PathBuf>, BwdError> {
+    if required && home.is_none() {
+        return Err(BwdError::HomeNotFound);
+    }
+    Ok(home)
+}
+
+/// Root-relative path, unless it has more than `threshold` components (or
+/// there is no root), in which case the absolute path reads better.
+fn relative_or_absolute(path: &Path, root: Option<&Path>, threshold: usize) -> String {
+    if let Some(root) = root
+        && let Ok(relative) = path.strip_prefix(root)
+        && relative.components().count() <= threshold
+    {
+        return relative_to_root(path, root);
+    }
+    path.to_string_lossy().to_string()
+}
+
+/// The last `n` components of `path`.
+fn keep_last(path: &Path, n: usize) -> PathBuf {
+    let components: Vec<Component> = path.components().collect();
+    components[components.len().saturating_sub(n)..].iter().collect()
+}
+
+/// Replace everything between the head of `path` (its root, or first
+/// component such as `$HOME`) and the last `n` components with `…`.
+fn elide_middle(path: &Path, n: usize) -> PathBuf {
+    let head_len = path
+        .components()
+        .take_while(|c| matches!(c, Component::Prefix(_) | Component::RootDir))
+        .count()
+        .max(1);
+    if path.components().count() <= head_len + n {
+        return path.to_path_buf();
+    }
+    let mut elided: PathBuf = path.components().take(head_len).collect();
+    elided.push("…");
+    elided.push(keep_last(path, n));
+    elided
+}
+
+/// Fish-style abbreviation: `~` for home, then every component but the last
+/// `keep` cut to its first character (`~/p/r/bwd`). Hidden directories keep
+/// their dot (`~/.c/fish`).
+fn abbreviate_path(path: &Path, home: Option<&Path>, keep: usize) -> String {
+    let (head, parts) = match home.and_then(|h| path.strip_prefix(h).ok()) {
+        Some(below_home) => (Some("~".to_string()), path_components(below_home)),
+        None => {
+            let mut parts = path_components(path);
+            let anchor = if path.has_root() && !parts.is_empty() { Some(parts.remove(0)) } else { None };
+            (anchor, parts)
+        }
+    };
+    let short_len = parts.len().saturating_sub(keep);
+    let parts: Vec<String> = parts
+        .into_iter()
+        .enumerate()
+        .map(|(i, part)| {
+            if i >= short_len {
+                return part;
+            }
+            let take = if part.starts_with('.') { 2 } else { 1 };
+            part.chars().take(take).collect()
+        })
+        .collect();
+    join_components(head.as_deref(), &parts)
+}
+
+/// The path's components joined by `separator`, without the root directory
+/// (`home › me › api`). With `max`, only the last `max` components are kept
+/// behind a leading `…`.
+fn breadcrumb(path: &Path, separator: &str, max: Option<usize>) -> String {
+    let sep = std::path::MAIN_SEPARATOR;
+    let mut parts: Vec<String> = path_components(path)
+        .into_iter()
+        .map(|part| part.trim_end_matches(['/', '\\', sep]).to_string())
+        .filter(|part| !part.is_empty())
+        .collect();
+    if let Some(max) = max
+        && parts.len() > max
+    {
+        parts.drain(..parts.len() - max);
+        parts.insert(0, "…".to_string());
+    }
+    parts.join(separator)
+}
+
+/// `bwd prompt`: the project name and the path below its root, or a
+/// `~`-shortened path outside a project, with the middle collapsed so only
+/// the last `keep` components remain (`myapp/…/src/api`). With a `theme`
+/// the project name gets the root color and the collapsed part is dim.
+/// For a `shell` (`--shell`) the text is escaped and the colors marked for
+/// its prompt.
+fn prompt_segment(
+    path: &Path,
+    home: Option<&Path>,
+    project: Option<(&Path, &str)>,
+    keep: usize,
+    theme: Option<&Theme>,
+    shell: Option<Shell>,
+) -> String {
+    let paint = |code: &str, text: &str| match (theme, shell) {
+        (None, _) => text.to_string(),
+        (Some(_), Some(shell)) => color::paint_with(code, text, |escape| shell.prompt_escape(escape)),
+        (Some(_), None) => color::paint(code, text),
+    };
+    let text = |text: &str| shell.map_or_else(|| text.to_string(), |shell| shell.prompt_text(text));
+    let root_code = theme.map_or("", |theme| theme.root.as_str());
+    let (head, parts) = match (project, home.and_then(|h| path.strip_prefix(h).ok())) {
+        (Some((root, name)), _) => (paint(root_code, &text(name)), path_components(path.strip_prefix(root).unwrap_or(Path::new("")))),
+        (None, Some(below_home)) => ("~".to_string(), path_components(below_home)),
+        (None, None) => {
+            let mut parts = path_components(path);
+            let anchor = if parts.is_empty() { String::new() } else { parts.remove(0) };
+            (anchor, parts)
+        }
+    };
+    let mut parts: Vec<String> = parts.iter().map(|part| text(part)).collect();
+    if parts.len() > keep {
+        parts.drain(..parts.len() - keep);
+        parts.insert(0, paint("2", "…"));
+    }
+    join_components(Some(&head), &parts)
+}
+
+/// `parts` joined by the path separator behind `head`, which may be an
+/// anchor such as `/` that already ends in one.
+fn join_components(head: Option<&str>, parts: &[String]) -> String {
+    let separator = std::path::MAIN_SEPARATOR.to_string();
+    match head {
+        None => parts.join(&separator),
+        Some(head) if parts.is_empty() => head.to_string(),
+        Some(head) if head.ends_with(&separator) => format!("{}{}", head, parts.join(&separator)),
+        Some(head) => format!("{}{}{}", head, separator, parts.join(&separator)),
+    }
+}
+
+/// Fit a path into `max` characters by replacing middle components with
+/// `…`, dropping the ones nearest the head first. The head (the first
+/// name, with the root if any: `/home`, `$HOME`) and the basename stay;
+/// when even `head/…/basename` is too long, only the last characters are
+/// kept behind a `…`.
+fn fit_width(path: &str, max: usize) -> String {
+    let width = |s: &str| s.chars().count();
+    if width(path) <= max {
+        return path.to_string();
+    }
+    let mut parts = path_components(Path::new(path));
+    // An anchor such as `/` is kept together with the first name after it
+    if Path::new(path).has_root() && parts.len() > 1 {
+        let first = parts.remove(1);
+        parts[0] = join_components(Some(&parts[0]), &[first]);
+    }
+    if parts.len() > 2 {
+        let head = parts.remove(0);
+        for drop in 1..parts.len() {
+            let mut kept = vec!["…".to_string()];
+            kept.extend_from_slice(&parts[drop..]);
+            let candidate = join_components(Some(&head), &kept);
+            if width(&candidate) <= max {
+                return candidate;
+            }
+        }
+    }
+    let tail: Vec<char> = path.chars().rev().take(max.saturating_sub(1)).collect();
+    std::iter::once('…').chain(tail.into_iter().rev()).collect()
+}
+
+/// Prompt-friendly form: shorten the home prefix, then elide the middle so
+/// only the last `n` components remain, e.g. `$HOME/…/deep/leaf`.
+fn pretty_prompt(path: &Path, home: Option<&Path>, n: usize) -> String {
+    let short = shorten_path(path, home);
+    elide_middle(Path::new(&short), n).to_string_lossy().to_string()
+}
+
+/// Like `shorten_path`, but errors instead of falling back to the absolute path
+/// when `path` is not under `home` (or no home directory is known).
+fn home_relative_path(path: &Path, home: Option<&Path>) -> Result<String, BwdError> {
+    match home {
+        Some(h) if path.starts_with(h) => Ok(shorten_path(path, Some(h))),
+        _ => Err(BwdError::InvalidPath(path.to_string_lossy().to_string())),
+    }
+}
+
+/// Number of components between `home` and `path`, or `None` when `path`
+/// is outside home (or no home directory is known).
+fn home_depth(path: &Path, home: Option<&Path>) -> Option<usize> {
+    let stripped = path.strip_prefix(home?).ok()?;
+    Some(stripped.components().count())
+}
+
+/// Longest directory prefix shared by `a` and `b`, compared component-wise
+/// so `/src/app` and `/src/apple` only share `/src`.
+fn common_ancestor(a: &Path, b: &Path) -> PathBuf {
+    a.components()
+        .zip(b.components())
+        .take_while(|(x, y)| x == y)
+        .map(|(x, _)| x)
+        .collect()
+}
+
+/// Turn a path into something usable as a single filename, e.g.
+/// `/home/user/proj` becomes `home_user_proj`.
+///
+/// Separators, characters reserved on Windows and control characters become
+/// `_`; runs of them collapse into one and leading/trailing ones are dropped.
+fn sanitize_for_filename(s: &str) -> String {
+    let mut sanitized = String::with_capacity(s.len());
+    for c in s.chars() {
+        let unsafe_char = matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') || c.is_control();
+        if !unsafe_char {
+            sanitized.push(c);
+        } else if !sanitized.is_empty() && !sanitized.ends_with('_') {
+            sanitized.push('_');
+        }
+    }
+    sanitized.trim_end_matches('_').to_string()
+}
+
+/// A `file://` URI for the absolute `path`, percent-encoding everything but
+/// unreserved characters and separators. With `windows`, backslashes are
+/// separators, `C:\x` becomes `file:///C:/x` and `\\server\share`
+/// becomes `file://server/share`.
+fn file_uri(path: &str, windows: bool) -> String {
+    let path = if windows { path.replace('\\', "/") } else { path.to_string() };
+    let bytes = path.as_bytes();
+    if windows && bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' {
+        format!("file:///{}{}", &path[..2], percent_encode(&path[2..]))
+    } else if let Some(unc) = path.strip_prefix("//").filter(|_| windows) {
+        format!("file://{}", percent_encode(unc))
+    } else {
+        format!("file://{}", percent_encode(&path))
+    }
+}
+
+/// Percent-encode everything in `s` but unreserved URL characters and `/`.
+fn percent_encode(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for byte in s.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// The `--web` URL for `path`: its page on the forge hosting the enclosing
+/// repository's remote, on the checked-out branch (the commit when HEAD is
+/// detached), or at the current commit for a permalink.
+fn web_location(path: &Path, permalink: bool) -> Result<String, BwdError> {
+    let repo = discover_repo(path).ok_or(BwdError::RootNotFound)?;
+    let remote = repo.remote_url().ok_or_else(|| BwdError::WebUrl("the repository has no remote".to_string()))?;
+    let reference = if permalink { repo.head_commit() } else { repo.branch().or_else(|| repo.head_commit()) };
+    let reference = reference.ok_or_else(|| BwdError::WebUrl("HEAD has no commit yet".to_string()))?;
+    web_url(&remote, &reference, &work_tree_relative(&repo, path), path.is_dir())
+        .ok_or_else(|| BwdError::WebUrl(format!("'{}' is not on GitHub, GitLab or Bitbucket", remote)))
+}
+
+/// `path` below the repository's work tree, `/`-separated whatever the
+/// platform, empty for the work tree itself.
+fn work_tree_relative(repo: &GitRepo, path: &Path) -> String {
+    let relative: Vec<String> = path
+        .strip_prefix(&repo.work_tree)
+        .unwrap_or(Path::new(""))
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy().to_string())
+        .collect();
+    relative.join("/")
+}
+
+/// The `--git-pathspec` form of `path`: anchored at the top of the work
+/// tree with git's `:/` magic, so it means the same from any subdirectory.
+/// git reads pathspecs as globs, so a path with glob characters gets the
+/// long form with `literal` magic instead (`:(top,literal)src/[id]`).
+fn git_pathspec(path: &Path) -> Result<String, BwdError> {
+    let repo = discover_repo(path).ok_or(BwdError::RootNotFound)?;
+    let relative = work_tree_relative(&repo, path);
+    if relative.contains(['*', '?', '[', '\\']) {
+        Ok(format!(":(top,literal){}", relative))
+    } else {
+        Ok(format!(":/{}", relative))
+    }
+}
+
+/// The page for `path` (relative to the work tree, `/`-separated, empty for
+/// the top) at `reference` on the forge hosting `remote`. `None` for hosts
+/// other than GitHub, GitLab and Bitbucket, self-hosted ones included when
+/// their name says which they are.
+fn web_url(remote: &str, reference: &str, path: &str, is_dir: bool) -> Option<String> {
+    let (host, repo) = remote_host_and_repo(remote)?;
+    let scheme = if remote.starts_with("http://") { "http" } else { "https" };
+    let base = format!("{}://{}/{}", scheme, host, repo);
+    let target = [percent_encode(reference), percent_encode(path)].join("/");
+    let target = target.trim_end_matches('/');
+    let kind = if is_dir { "tree" } else { "blob" };
+    if host.contains("github") {
+        Some(format!("{}/{}/{}", base, kind, target))
+    } else if host.contains("gitlab") {
+        Some(format!("{}/-/{}/{}", base, kind, target))
+    } else if host.contains("bitbucket") {
+        Some(format!("{}/src/{}", base, target))
+    } else {
+        None
+    }
+}
+
+/// The host and repository path of a remote URL in any of git's forms:
+/// `https://host/owner/repo.git`, `ssh://git@host:22/owner/repo` or
+/// `git@host:owner/repo.git`. An `http(s)` port is kept, as the web pages
+/// are served there too; an ssh one is not.
+fn remote_host_and_repo(remote: &str) -> Option<(String, String)> {
+    let (scheme, authority, path) = match remote.split_once("://") {
+        Some((scheme, rest)) => {
+            let (authority, path) = rest.split_once('/')?;
+            (scheme, authority, path)
+        }
+        None => {
+            let (authority, path) = remote.split_once(':')?;
+            ("ssh", authority, path)
+        }
+    };
+    let host = authority.rsplit('@').next()?;
+    let host = if matches!(scheme, "http" | "https") { host } else { host.split(':').next()? };
+    let repo = path.trim_matches('/');
+    let repo = repo.strip_suffix(".git").unwrap_or(repo);
+    (!host.is_empty() && !repo.is_empty()).then(|| (host.to_lowercase(), repo.to_string()))
+}
+
+/// Use `separator` for every `/` and `\\` in `s`.
+fn convert_separators(s: &str, separator: char) -> String {
+    s.chars().map(|c| if c == '/' || c == '\\' { separator } else { c }).collect()
+}
+
+/// Make control characters visible: tab, newline and carriage return become
+/// `\t`, `\n` and `\r`, any other control character becomes `?`.
+fn escape_control(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c if c.is_control() => escaped.push('?'),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Length of the path in bytes, for checking against limits like `PATH_MAX`.
+///
+/// On Unix this is the raw byte length the kernel sees. On Windows it is the
+/// UTF-8 (strictly WTF-8) length, not the number of UTF-16 code units.
+fn path_byte_len(path: &Path) -> usize {
+    path.as_os_str().as_encoded_bytes().len()
+}
+
+/// Express `path` relative to `base`, climbing out with `..` where needed.
+///
+/// Both paths are expected to be absolute and already resolved. If they share
+/// no prefix at all (e.g. different Windows drives), `path` is returned as-is.
+fn relativize(path: &Path, base: &Path) -> PathBuf {
+    let common = common_ancestor(path, base);
+    if common.as_os_str().is_empty() {
+        return path.to_path_buf();
+    }
+    let ups = base.components().count() - common.components().count();
+    let mut relative: PathBuf = std::iter::repeat_n(Component::ParentDir, ups).collect();
+    relative.extend(path.components().skip(common.components().count()));
+    if relative.as_os_str().is_empty() {
+        relative.push(".");
+    }
+    relative
+}
+
+/// Relativize against every base and keep the result with the fewest `..`
+/// components, then the fewest components overall. Ties go to the earlier base.
+fn shortest_relative(path: &Path, bases: &[PathBuf]) -> PathBuf {
+    bases
+        .iter()
+        .map(|base| relativize(path, base))
+        .min_by_key(|relative| {
+            let ups = relative.components().filter(|c| *c == Component::ParentDir).count();
+            (ups, relative.components().count())
+        })
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+/// `shortest_relative` for `--relative-to`, optionally without following symlinks.
+///
+/// Resolving both sides can produce long `..` chains across symlink boundaries
+/// (a symlinked base is replaced by its target). With `resolve` off, both paths
+/// are only cleaned lexically, so the result follows the paths as written.
+fn relative_to_bases(path: &Path, bases: &[PathBuf], resolve: bool) -> PathBuf {
+    let clean = |p: &Path| {
+        if resolve {
+            resolve_existing_or_lexical(p)
+        } else {
+            lexical_normalize(p)
+        }
+    };
+    let bases: Vec<PathBuf> = bases.iter().map(|base| clean(base)).collect();
+    shortest_relative(&clean(path), &bases)
+}
+
+/// Classify a path like `ls -F`: `@` for symlinks, `/` for directories and
+/// `*` for executable files. Anything else (or a missing path) gets no suffix.
+fn classify_suffix(path: &Path) -> &'static str {
+    let Ok(meta) = fs::symlink_metadata(path) else {
+        return "";
+    };
+    if meta.file_type().is_symlink() {
+        "@"
+    } else if meta.is_dir() {
+        "/"
+    } else if is_executable(&meta) {
+        "*"
+    } else {
+        ""
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(meta: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    meta.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_meta: &fs::Metadata) -> bool {
+    false
+}
+
+/// The platform path separator: `/` on Unix, `\` on Windows.
+fn path_separator() -> char {
+    std::path::MAIN_SEPARATOR
+}
+
+/// Location of the root lookup cache used by `--cache`.
+fn root_cache_file() -> Option<PathBuf> {
+    cache_dir().map(|dir| dir.join("root-cache.json"))
+}
+
+/// Directory for bwd's disposable files: `%LOCALAPPDATA%\bwd` on Windows,
+/// `~/Library/Caches/bwd` on macOS and `$XDG_CACHE_HOME/bwd` (falling back
+/// to `~/.cache/bwd`) elsewhere.
+fn cache_dir() -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        get_home_dir().map(|home| home.join("Library").join("Caches"))
+    } else {
+        env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| get_home_dir().map(|home| home.join(".cache")))
+    };
+    base.map(|dir| dir.join("bwd"))
+}
+
+/// Create `dir` readable by its owner only, where the platform has modes.
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder.create(dir)
+}
+
+/// `RootSearch::find` backed by a JSON cache file mapping directories to roots.
+///
+/// Each entry records the modification times of the directories whose
+/// contents decide the answer: those from the path up to its root, or every
+/// ancestor for `--outer`. Adding or removing a marker in any of them changes
+/// its time and invalidates the entry. The cache is best-effort: an
+/// unreadable or unwritable file simply means a regular lookup.
+fn cached_find_root(path: &Path, cache_file: &Path, search: &RootSearch) -> Option<PathBuf> {
+    if let Some(root) = search.pinned_root(path) {
+        return Some(root);
+    }
+    let key = format!("{}|{}", search.cache_key(), path.to_string_lossy());
+    let read_entries = || -> HashMap<String, JsonValue> {
+        fs::read_to_string(cache_file)
+            .ok()
+            .and_then(|content| content.parse::<JsonValue>().ok())
+            .and_then(|json| match json {
+                JsonValue::Object(map) => Some(map),
+                _ => None,
+            })
+            .unwrap_or_default()
+    };
+
+    // Writes replace the file atomically, so reading needs no lock
+    if let Some(JsonValue::Object(entry)) = read_entries().remove(&key)
+        && let (Some(JsonValue::String(root)), Some(JsonValue::String(mtimes))) = (entry.get("root"), entry.get("mtimes"))
+    {
+        let root = PathBuf::from(root);
+        if path.starts_with(&root) && root_fingerprint(path, &root, search).as_ref() == Some(mtimes) {
+            return Some(root);
+        }
+    }
+
+    let root = search.find(path);
+    let fingerprint = root.as_deref().and_then(|root| root_fingerprint(path, root, search));
+    if let Some(dir) = cache_file.parent() {
+        let _ = create_private_dir(dir);
+    }
+    // Re-read under the lock so entries written by other processes meanwhile survive
+    let _ = with_lock(cache_file, || {
+        let mut entries = read_entries();
+        match (&root, fingerprint) {
+            (Some(root), Some(mtimes)) => {
+                let mut entry = HashMap::new();
+                entry.insert("root".to_string(), JsonValue::String(root.to_string_lossy().to_string()));
+                entry.insert("mtimes".to_string(), JsonValue::String(mtimes));
+                entries.insert(key, JsonValue::Object(entry));
+            }
+            _ => {
+                entries.remove(&key);
+            }
+        }
+        let content = JsonValue::Object(entries).stringify().map_err(|e| BwdError::Json(format!("{:?}", e)))?;
+        write_atomic(cache_file, &content)
+    });
+    root
+}
+
+/// The modification times a cached `root` for `path` is valid for; `None`
+/// if one of the directories can't be read.
+fn root_fingerprint(path: &Path, root: &Path, search: &RootSearch) -> Option<String> {
+    let mut dirs: Vec<&Path> = path.ancestors().collect();
+    if !search.outer {
+        let inside = dirs.iter().position(|dir| *dir == root)?;
+        dirs.truncate(inside + 1);
+    }
+    let mtimes: Option<Vec<String>> = dirs
+        .into_iter()
+        .map(|dir| {
+            let mtime = fs::metadata(dir).ok()?.modified().ok()?.duration_since(SystemTime::UNIX_EPOCH).ok()?;
+            Some(format!("{}.{:09}", mtime.as_secs(), mtime.subsec_nanos()))
+        })
+        .collect();
+    mtimes.map(|mtimes| mtimes.join(","))
+}
+
+/// Inverse of `-r`: turn a root-relative `fragment` back into an absolute path.
+/// The result is normalized lexically and does not need to exist.
+fn join_root(root: Option<PathBuf>, fragment: &str) -> Result<PathBuf, BwdError> {
+    let root = root.ok_or(BwdError::RootNotFound)?;
+    Ok(lexical_normalize(&root.join(fragment)))
+}
+
+/// Toplevel of the enclosing git work tree, matching `git rev-parse --show-toplevel`.
+///
+/// Like git, the path is resolved physically *before* walking up, so a symlink
+/// into a repository reports the repository's real location rather than the
+/// symlink's parent chain. The result is canonicalized and stripped of the
+/// Windows UNC prefix, which is also how git prints it.
+fn resolve_git_root(path: &Path) -> Option<PathBuf> {
+    let physical = fs::canonicalize(path).map(clean_windows_path).unwrap_or_else(|_| path.to_path_buf());
+    let toplevel = discover_repo(&physical)?.work_tree;
+    fs::canonicalize(toplevel).ok().map(clean_windows_path)
+}
+
+/// The repository's name: from the URL of its `origin` remote, else the
+/// project root's directory name.
+fn repo_name(repo: Option<&GitRepo>, root: Option<&Path>) -> Option<String> {
+    repo.and_then(GitRepo::remote_url)
+        .and_then(|url| git::repo_name(&url))
+        .or_else(|| root.and_then(Path::file_name).map(|name| name.to_string_lossy().to_string()))
+}
+
+/// The `--format` placeholders for `path`. Root-based ones are empty
+/// outside a project, `branch` outside git or on a detached HEAD,
+/// `basename` and `parent` at the filesystem root. The ones that run git or
+/// read more files are only worked out when `template` uses them.
+fn template_values(template: &str, path: &Path, home: Option<&Path>, root: Option<&Path>) -> Result<Vec<(&'static str, Option<String>)>, BwdError> {
+    let lossy = |p: &Path| p.to_string_lossy().to_string();
+    let wanted = |name: &str| template.contains(&format!("{{{}}}", name));
+    let repo = if wanted("branch") || wanted("repo") { discover_repo(path) } else { None };
+    Ok(vec![
+        ("path", Some(lossy(path))),
+        ("short", Some(shorten_path(path, home))),
+        ("rel", root.map(|r| relative_to_root(path, r))),
+        ("root", root.map(lossy)),
+        ("root_name", root.and_then(Path::file_name).map(|name| lossy(Path::new(name)))),
+        ("basename", path.file_name().map(|name| lossy(Path::new(name)))),
+        ("parent", path.parent().map(lossy)),
+        ("project", wanted("project").then(|| root.and_then(project_name)).flatten()),
+        ("branch", repo.as_ref().and_then(GitRepo::branch)),
+        ("repo", wanted("repo").then(|| repo_name(repo.as_ref(), root)).flatten()),
+        (
+            "windows",
+            wanted("windows")
+                .then(|| wsl::to_windows(&lossy(path), &wsl::mount_root(), wsl::distro_name().as_deref()))
+                .flatten(),
+        ),
+    ])
+}
+
+/// Fill `{name}` placeholders in `template` from `values`; `{{` and `}}` are
+/// literal braces. A known placeholder without a value renders empty, an
+/// unknown one is an error.
+fn render_template(template: &str, values: &[(&str, Option<String>)]) -> Result<String, BwdError> {
+    let invalid = || BwdError::InvalidValue("--format".to_string(), template.to_string());
+    let mut out = String::with_capacity(template.len());
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.as_str().starts_with('{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.as_str().starts_with('}') => {
+                chars.next();
+                out.push('}');
+            }
+            '{' => {
+                let rest = chars.as_str();
+                let end = rest.find('}').ok_or_else(invalid)?;
+                let (_, value) = values.iter().find(|(name, _)| *name == &rest[..end]).ok_or_else(invalid)?;
+                out.push_str(value.as_deref().unwrap_or(""));
+                chars = rest[end + 1..].chars();
+            }
+            '}' => return Err(invalid()),
+            c => out.push(c),
+        }
+    }
+    Ok(out)
+}
+
+/// The bookmarks in `root`'s `.bwd`. One broken project file must not take
+/// the user's own bookmarks down with it: warn and skip it.
+fn project_bookmarks(root: &Path) -> BTreeMap<String, Bookmark> {
+    bookmarks::project_bookmarks(root).unwrap_or_else(|err| {
+        eprintln!("[bwd warning] {}", err);
+        BTreeMap::new()
+    })
+}
+
+/// The metadata in `root`'s `.bwd-root`. A file that is not valid TOML is
+/// still a marker, as it was before the file could hold metadata: warn and
+/// carry on without metadata.
+fn project_meta(root: &Path) -> ProjectMeta {
+    ProjectMeta::load(root).unwrap_or_else(|err| {
+        eprintln!("[bwd warning] {}", err);
+        ProjectMeta::default()
+    })
+}
+
+/// The project's display name, see `project::project_name`. A malformed
+/// `.bwd-root` gets the directory name, with a warning.
+fn project_name(root: &Path) -> Option<String> {
+    project_meta(root).display_name(root)
+}
+
+/// Verify that the detected project root's directory name equals `expected`.
+/// A name or alias from the root's `.bwd-root` is accepted as well.
+fn assert_root_name(root: Option<PathBuf>, expected: &str) -> Result<PathBuf, BwdError> {
+    let root = root.ok_or(BwdError::RootNotFound)?;
+    let name = root
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    if name == expected || project_meta(&root).answers_to(expected) {
+        Ok(root)
+    } else {
+        Err(BwdError::RootMismatch(expected.to_string(), name))
+    }
+}
+
+/// Every flag `parse_config` understands, for shell completion. Keep in sync.
+const FLAGS: &[&str] = &[
+    "--help",
+    "--version",
+    "--copy",
+    "--short",
+    "--json",
+    "--root",
+    "--assert-root",
+    "--home-relative-only",
+    "--prefer-marker",
+    "--type-suffix",
+    "--home-depth",
+    "--dots-only",
+    "--cache",
+    "--longest-common-with",
+    "--error-json",
+    "--rel-threshold",
+    "--from-root",
+    "--sanitize",
+    "--git",
+    "--safe",
+    "--git-root",
+    "--bytes",
+    "--relative-to",
+    "--require-home",
+    "--no-resolve",
+    "--print-sep",
+    "--pretty-prompt",
+    "--last",
+    "--json-root-abs",
+    "--copy-stdin",
+    "--force",
+    "--tag",
+    "--on-conflict",
+    "--ttl",
+    "--sort",
+    "--from",
+    "--count",
+    "--escape",
+    "--as-cd",
+    "--nu",
+    "--root-marker",
+    "--format",
+    "--null",
+    "--json-pretty",
+    "--yaml",
+    "--uri",
+    "--unix",
+    "--windows",
+    "--wsl",
+    "--win",
+    "--msys",
+    "--cygwin",
+    "--split",
+    "--breadcrumb",
+    "--color",
+    "--shell",
+    "--abbrev",
+    "--max-width",
+    "--stdin",
+    "--first",
+    "--keep-going",
+    "--unique",
+    "--copy-format",
+    "--paste",
+    "--web",
+    "--permalink",
+    "--git-pathspec",
+    "--status",
+];
+
+fn print_help() {
+    println!("bwd - Better Working Directory");
+    println!("\nUsage:");
+    println!("  bwd [target...] [-c] [-s] [-j] [-r] [--home-relative-only] [--assert-root NAME]");
+    println!("  bwd save NAME [PATH] [--tag TAG] [--ttl 2h]  Bookmark PATH (default: current directory) as NAME");
+    println!("  bwd get NAME           Print the bookmarked path (works with -s, -j, -c)");
+    println!("  bwd list [--tag TAG] [--sort name|frecency]  List bookmarks (-j for a JSON array, -s to shorten)");
+    println!("  bwd rm NAME            Delete a bookmark");
+    println!("  bwd mv OLD NEW         Rename a bookmark");
+    println!("  bwd export [json|toml] Print all bookmarks");
+    println!("  bwd import FILE [json|toml] [--on-conflict keep|overwrite|error]");
+    println!("  bwd root [PATH]        Print the project root directory (works with -s, -j, -c)");
+    println!("  bwd prompt [PATH]      Print a compact prompt segment, e.g. myapp/…/src/api (--last N, --color, --shell)");
+    println!("  bwd push [PATH]        Push the current directory on the stack and print PATH");
+    println!("  bwd pop                Pop and print the top of the directory stack");
+    println!("  bwd stack              List the directory stack, top first");
+    println!("  bwd init SHELL         Print shell integration (bash, zsh, fish, powershell) to eval");
+    println!("  bwd completions SHELL  Print a completion script for the same shells");
+    println!("  bwd visit [PATH]       Record a directory visit in the history (for shell hooks)");
+    println!("  bwd recent [-n N]      List the N (default 10) most recently visited directories");
+    println!("  bwd jump QUERY...      Print the best matching visited directory (works with -s, -j, -c)");
+    println!("  bwd import-history --from zoxide|autojump|z [FILE]  Merge another jump tool's database into the history");
+    println!("\nFlags:");
+    println!("  -c, --copy     Copy to clipboard (--copy=system|osc52|tmux|primary|file; default auto)");
+    println!("  -s, --short    Shorten path (replace home with $HOME)");
+    println!("  -j, --json     Output JSON (path, short, home_relative, root)");
+    println!("  -r, --root     Print path relative to project root (.git, .hg, .svn, .jj or .bwd-root)");
+    println!("  --home-relative-only  Like -s, but fail if the path is outside home");
+    println!("  --longest-common-with PATH  Print the deepest directory shared with PATH");
+    println!("  --cache        Cache root lookups in the user cache directory");
+    println!("  --dots-only    Collapse . and .. without resolving symlinks or requiring the path to exist");
+    println!("  --home-depth   Print how many directories deep the path is below home (-1 if outside)");
+    println!("  --type-suffix  Append / for directories, * for executables, @ for symlinks");
+    println!("  --prefer-marker NAME  Prefer the nearest root containing NAME over closer markers");
+    println!("  --assert-root NAME  Exit non-zero unless the project root is named NAME");
+    println!("  --error-json   Report errors as JSON on stderr");
+    println!("  --rel-threshold N  Print root-relative, or absolute if deeper than N below the root");
+    println!("  --from-root FRAGMENT  Print the absolute path of a root-relative FRAGMENT");
+    println!("  --sanitize     Replace separators and unsafe characters with _ (for filenames)");
+    println!("  --git, --status  Print -j output with a \"dirty\" flag (from the index, no git process)");
+    println!("  --safe         Print control characters as visible escapes (\\t, \\n, ?)");
+    println!("  --git-root     Print the git toplevel (same as git rev-parse --show-toplevel)");
+    println!("  --bytes        Print the path length in bytes (also \"bytes\" in -j)");
+    println!("  --relative-to BASE  Print the path relative to BASE (repeatable, shortest wins)");
+    println!("  --require-home  Fail if no home directory can be determined");
+    println!("  --no-resolve   Don't follow symlinks when computing --relative-to");
+    println!("  --print-sep    Print the platform path separator and exit");
+    println!("  --pretty-prompt  Shorten home and elide the middle, e.g. $HOME/…/deep/leaf");
+    println!("  --last N       Components kept by --pretty-prompt, --breadcrumb and bwd prompt (default 2; all for --breadcrumb)");
+    println!("  --json-root-abs  Add root_path (absolute root) and relative to -j output");
+    println!("  --copy-stdin   Copy stdin to the clipboard verbatim and echo it");
+    println!("  -f, --force    Let save/mv overwrite an existing bookmark");
+    println!("  --tag TAG      Tag a bookmark on save (repeatable) or filter bwd list (all must match)");
+    println!("  --ttl DURATION  Let a saved bookmark expire, e.g. 30m, 2h, 7d");
+    println!("  --sort ORDER   Order bwd list by name (default) or frecency, batch output by path");
+    println!("  -n, --count N  Number of directories bwd recent prints (default 10)");
+    println!("  --escape SHELL Quote the output for posix (sh/bash/zsh), fish, powershell or cmd");
+    println!("  --as-cd        Print a paste-ready cd command (quoted per --escape, default posix)");
+    println!("  -              As a target: the previous directory ($OLDPWD, like cd -)");
+    println!("  --nu           Print the -j record as NUON for Nushell");
+    println!("  --root-marker NAME  Detect the project root by NAME (repeatable, in priority order)");
+    println!("  --root=inner|outer  Like -r, with the nearest (default) or outermost marked ancestor as root");
+    println!("  --format TEMPLATE  Print TEMPLATE, e.g. \"{{root_name}}:{{rel}}\"; see the README for placeholders");
+    println!("  -0, --null     End each printed path with NUL instead of a newline");
+    println!("  --json-pretty  Like -j, indented for reading");
+    println!("  --yaml         Print the -j record as YAML");
+    println!("  --uri          Print the path as a file:// URI");
+    println!("  --web          Print the path's GitHub/GitLab/Bitbucket URL on the current branch");
+    println!("  --permalink    Like --web, pinned to the current commit");
+    println!("  --git-pathspec Print the path as a top-level pathspec (:/src/lib.rs) for git commands");
+    println!("  --unix, --windows  Print / or \\ separators regardless of the OS");
+    println!("  --wsl, --win   Translate Windows <-> WSL paths (C:\\x <-> /mnt/c/x)");
+    println!("  --msys, --cygwin  Print C:\\foo as /c/foo or /cygdrive/c/foo");
+    println!("  --split        Print one path component per line");
+    println!("  --breadcrumb[=SEP]  Print the path as home › me › api (--last N to trim)");
+    println!("  --abbrev[=N]   Abbreviate all but the last N components: ~/p/r/bwd");
+    println!("  --max-width N  Elide middle components so the path fits in N characters");
+    println!("  --color[=WHEN] Colorize output: always (bare --color), auto or never");
+    println!("  --shell SHELL  Mark bwd prompt's colors for a bash or zsh prompt");
+    println!("  --stdin        Also resolve each path read from stdin (one per line, NUL-separated with -0)");
+    println!("  --first        Use only the first match of a glob target such as 'src/**/fixtures'");
+    println!("  --keep-going   With several targets, report failures and carry on (exit 1 at the end)");
+    println!("  --unique       Drop batch entries resolving to a path already printed");
+    println!("  --copy-format TEMPLATE  Copy TEMPLATE (placeholders as --format) instead of the printed text; implies -c");
+    println!("  --paste        Resolve the path on the clipboard (~ and $VAR expanded)");
+    println!("  -h, --help     Show this help");
+    println!("  -v, --version  Show version");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn parse(args: &[&str]) -> Result<Config, BwdError> {
+        parse_config(&args.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn test_parse_config_defaults() {
+        let args: Vec<String> = vec![];
+        let config = parse_config(&args).unwrap();
+        assert_eq!(config.target, None);
+        assert!(!config.copy);
+        assert!(!config.short);
+        assert!(!config.json);
+        assert!(!config.root);
+    }
+
+    #[test]
+    fn test_parse_config_short_flag() {
+        let args: Vec<String> = vec!["-s".to_string()];
+        let config = parse_config(&args).unwrap();
+        assert!(config.short);
+        assert!(!config.json);
+    }
+
+    #[test]
+    fn test_parse_config_json_flag() {
+        let args: Vec<String> = vec!["--json".to_string()];
+        let config = parse_config(&args).unwrap();
+        assert!(config.json);
+        assert!(!config.short);
+    }
+
+    #[test]
+    fn test_parse_config_all_flags() {
+        let args: Vec<String> = vec!["-c".to_string(), "-s".to_string(), "-j".to_string()];
+        let config = parse_config(&args).unwrap();
+        assert!(config.copy);
+        assert!(config.short);
+        assert!(config.json);
+    }
+
+    #[test]
+    fn test_parse_config_target_only() {
+        let args: Vec<String> = vec!["some/path".to_string()];
+        let config = parse_config(&args).unwrap();
+        assert_eq!(config.target, Some("some/path".to_string()));
+    }
+
+    #[test]
+    fn test_parse_config_ignore_unknown_flags_as_target() {
+        // Unknown flags are ignored, so target remains None unless it's positional
+        // In the loop: if not parsing flags, or not starting with -, it's target.
+        // If it starts with - and is unknown, it's ignored.
+        let args: Vec<String> = vec!["-x".to_string()];
+        let config = parse_config(&args).unwrap();
+        assert_eq!(config.target, None);
+        // But if we have -x followed by path?
+        let args2: Vec<String> = vec!["-x".to_string(), "path".to_string()];
+        let config2 = parse_config(&args2).unwrap();
+        assert_eq!(config2.target, Some("path".to_string()));
+    }
+
+    #[test]
+    fn test_parse_config_dash_separator() {
+        let args: Vec<String> = vec!["--".to_string(), "-file".to_string()];
+        let config = parse_config(&args).unwrap();
+        assert_eq!(config.target, Some("-file".to_string()));
+        assert!(!config.copy);
+    }
+
+    #[test]
+    fn test_parse_config_dash_separator_with_flags() {
+        let args: Vec<String> = vec!["-c".to_string(), "--".to_string(), "-file".to_string()];
+        let config = parse_config(&args).unwrap();
+        assert_eq!(config.target, Some("-file".to_string()));
+        assert!(config.copy);
+    }
+
+    #[test]
+    fn test_parse_config_flags_after_separator_are_target() {
+        let args: Vec<String> = vec!["--".to_string(), "-c".to_string()];
+        let config = parse_config(&args).unwrap();
+        assert_eq!(config.target, Some("-c".to_string()));
+        assert!(!config.copy);
+    }
+
+    #[test]
+    fn test_parse_config_root_flag() {
+        let args: Vec<String> = vec!["-r".to_string()];
+        let config = parse_config(&args).unwrap();
+        assert!(config.root);
+    }
+
+    #[test]
+    fn test_parse_config_multiple_targets() {
+        let config = parse(&["src", "-s", "tests", "-", "--", "-"]).unwrap();
+        assert_eq!(config.target.as_deref(), Some("src"));
+        assert_eq!(
+            config.more_targets,
+            vec![("tests".to_string(), false), ("-".to_string(), true), ("-".to_string(), false)]
+        );
+        assert!(config.short);
+        // Subcommands still own every positional after them
+        assert!(parse(&["root", "a", "b"]).unwrap().more_targets.is_empty());
+    }
+
+    #[test]
+    fn test_parse_config_null_flag() {
+        assert!(parse(&["-0"]).unwrap().null);
+        assert!(parse(&["--null", "recent"]).unwrap().null);
+        assert!(!parse(&[]).unwrap().null);
+    }
+
+    #[test]
+    fn test_parse_config_assert_root() {
+        let args: Vec<String> = vec!["--assert-root".to_string(), "bpwd".to_string()];
+        let config = parse_config(&args).unwrap();
+        assert_eq!(config.assert_root, Some("bpwd".to_string()));
+        assert_eq!(config.target, None);
+
+        let missing: Vec<String> = vec!["--assert-root".to_string()];
+        assert!(matches!(parse_config(&missing), Err(BwdError::MissingValue(_))));
+    }
+
+    #[test]
+    fn test_assert_root_name() {
+        let temp_dir = std::env::temp_dir();
+        let test_root = temp_dir.join(format!("bpwd_test_assert_{}", process::id()));
+        if test_root.exists() {
+            let _ = fs::remove_dir_all(&test_root);
+        }
+        let child = test_root.join("subdir");
+        fs::create_dir_all(&child).unwrap();
+        fs::create_dir(test_root.join(".bwd-root")).unwrap();
+
+        let name = test_root.file_name().unwrap().to_string_lossy().to_string();
+        assert_eq!(assert_root_name(RootSearch::default().find(&child), &name).unwrap(), test_root);
+        assert!(matches!(
+            assert_root_name(RootSearch::default().find(&child), "other"),
+            Err(BwdError::RootMismatch(expected, found)) if expected == "other" && found == name
+        ));
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_root);
+    }
+
+    #[test]
+    fn test_assert_root_name_no_root() {
+        let temp_dir = std::env::temp_dir();
+        let test_dir = temp_dir.join(format!("bpwd_test_assert_none_{}", process::id()));
+        fs::create_dir_all(&test_dir).unwrap();
+
+        assert!(matches!(assert_root_name(RootSearch::default().find(&test_dir), "anything"), Err(BwdError::RootNotFound)));
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_dir);
+    }
+
+    #[test]
+    fn test_home_relative_path_under_home() {
+        let home = PathBuf::from("/home/user");
+        let path = home.join("docs");
+        let expected = PathBuf::from("$HOME").join("docs").to_string_lossy().to_string();
+        assert_eq!(home_relative_path(&path, Some(&home)).unwrap(), expected);
+        assert_eq!(home_relative_path(&home, Some(&home)).unwrap(), "$HOME");
+    }
+
+    #[test]
+    fn test_home_relative_path_outside_home() {
+        let home = PathBuf::from("/home/user");
+        let path = PathBuf::from("/var/log");
+        assert!(matches!(home_relative_path(&path, Some(&home)), Err(BwdError::InvalidPath(_))));
+        assert!(matches!(home_relative_path(&path, None), Err(BwdError::InvalidPath(_))));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_classify_suffix() {
+        use std::os::unix::fs::{PermissionsExt, symlink};
+
+        let temp_dir = std::env::temp_dir();
+        let test_dir = temp_dir.join(format!("bpwd_test_classify_{}", process::id()));
+        if test_dir.exists() {
+            let _ = fs::remove_dir_all(&test_dir);
+        }
+        fs::create_dir_all(&test_dir).unwrap();
+
+        let exe = test_dir.join("run.sh");
+        fs::write(&exe, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&exe, fs::Permissions::from_mode(0o755)).unwrap();
+        let plain = test_dir.join("notes.txt");
+        fs::write(&plain, "").unwrap();
+        let link = test_dir.join("link");
+        symlink(&test_dir, &link).unwrap();
+
+        assert_eq!(classify_suffix(&test_dir), "/");
+        assert_eq!(classify_suffix(&exe), "*");
+        assert_eq!(classify_suffix(&plain), "");
+        assert_eq!(classify_suffix(&link), "@");
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_dir);
+    }
+
+    #[test]
+    fn test_home_depth() {
+        let home = PathBuf::from("/home/user");
+        assert_eq!(home_depth(&home, Some(&home)), Some(0));
+        assert_eq!(home_depth(&home.join("a/b"), Some(&home)), Some(2));
+        // Outside home maps to the -1 sentinel in text output
+        assert_eq!(home_depth(Path::new("/var/log"), Some(&home)), None);
+        assert_eq!(home_depth(&home, None), None);
+    }
+
+    #[test]
+    fn test_cached_find_root() {
+        let temp_dir = std::env::temp_dir();
+        let test_root = temp_dir.join(format!("bpwd_test_cache_{}", process::id()));
+        if test_root.exists() {
+            let _ = fs::remove_dir_all(&test_root);
+        }
+        let inner = test_root.join("inner");
+        let child = inner.join("src");
+        fs::create_dir_all(&child).unwrap();
+        fs::create_dir(test_root.join(".bwd-root")).unwrap();
+        let cache_file = test_root.join("root-cache.json");
+
+        assert_eq!(cached_find_root(&child, &cache_file, &RootSearch::default()), Some(test_root.clone()));
+        assert!(cache_file.exists());
+
+        let content = fs::read_to_string(&cache_file).unwrap();
+        assert!(content.contains(&child.to_string_lossy().to_string()));
+        assert_eq!(cached_find_root(&child, &cache_file, &RootSearch::default()), Some(test_root.clone()));
+
+        // A nearer marker changes its directory's time and invalidates the entry
+        fs::create_dir(inner.join(".bwd-root")).unwrap();
+        assert_eq!(cached_find_root(&child, &cache_file, &RootSearch::default()), Some(inner.clone()));
+        fs::remove_dir(inner.join(".bwd-root")).unwrap();
+        assert_eq!(cached_find_root(&child, &cache_file, &RootSearch::default()), Some(test_root.clone()));
+
+        // So does removing the cached root's own marker
+        fs::remove_dir(test_root.join(".bwd-root")).unwrap();
+        assert_ne!(cached_find_root(&child, &cache_file, &RootSearch::default()), Some(test_root.clone()));
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_root);
+    }
+
+    #[test]
+    fn test_build_json_home_relative() {
+        let home = PathBuf::from("/home/user");
+        let map = build_json(&home.join("projects/bpwd"), Some(&home), None);
+        let expected = PathBuf::from("projects/bpwd").to_string_lossy().to_string();
+        assert_eq!(map.get("home_relative"), Some(&JsonValue::String(expected)));
+        assert_eq!(map.get("root"), Some(&JsonValue::Null));
+
+        let map = build_json(Path::new("/var/log"), Some(&home), None);
+        assert_eq!(map.get("home_relative"), Some(&JsonValue::Null));
+        assert_eq!(home_relative(&home, Some(&home)), Some(".".to_string()));
+    }
+
+    #[test]
+    fn test_common_ancestor_deep_prefix() {
+        let a = PathBuf::from("/home/user/projects/bpwd/src");
+        let b = PathBuf::from("/home/user/projects/other/docs");
+        assert_eq!(common_ancestor(&a, &b), PathBuf::from("/home/user/projects"));
+        // Component-wise, not string-wise
+        assert_eq!(common_ancestor(Path::new("/src/app"), Path::new("/src/apple")), PathBuf::from("/src"));
+    }
+
+    #[test]
+    fn test_common_ancestor_only_root() {
+        let a = PathBuf::from("/home/user");
+        let b = PathBuf::from("/var/log");
+        assert_eq!(common_ancestor(&a, &b), PathBuf::from("/"));
+    }
+
+    #[test]
+    fn test_error_json_root_not_found() {
+        let json: JsonValue = error_json(&BwdError::RootNotFound).parse().unwrap();
+        let JsonValue::Object(map) = json else {
+            panic!("error JSON should be an object");
+        };
+        assert_eq!(map.get("error"), Some(&JsonValue::String("RootNotFound".to_string())));
+        assert_eq!(map.get("message"), Some(&JsonValue::String("Root not found".to_string())));
+    }
+
+    #[test]
+    fn test_relative_or_absolute() {
+        let root = PathBuf::from("/work/proj");
+        let short = root.join("src");
+        let long = root.join("a/b/c/d");
+        let expected_short = PathBuf::from("src").to_string_lossy().to_string();
+
+        assert_eq!(relative_or_absolute(&short, Some(&root), 2), expected_short);
+        assert_eq!(relative_or_absolute(&long, Some(&root), 2), long.to_string_lossy());
+        assert_eq!(relative_or_absolute(&short, None, 2), short.to_string_lossy());
+    }
+
+    #[test]
+    fn test_parse_config_rel_threshold() {
+        let args: Vec<String> = vec!["--rel-threshold".to_string(), "3".to_string()];
+        assert_eq!(parse_config(&args).unwrap().rel_threshold, Some(3));
+
+        let bad: Vec<String> = vec!["--rel-threshold".to_string(), "many".to_string()];
+        assert!(matches!(parse_config(&bad), Err(BwdError::InvalidValue(_, _))));
+    }
+
+    #[test]
+    fn test_join_root() {
+        let temp_dir = std::env::temp_dir();
+        let test_root = temp_dir.join(format!("bpwd_test_from_root_{}", process::id()));
+        if test_root.exists() {
+            let _ = fs::remove_dir_all(&test_root);
+        }
+        let child = test_root.join("docs");
+        fs::create_dir_all(&child).unwrap();
+        fs::create_dir(test_root.join(".bwd-root")).unwrap();
+
+        let resolved = join_root(RootSearch::default().find(&child), "src/lib.rs").unwrap();
+        assert_eq!(resolved, test_root.join("src").join("lib.rs"));
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_root);
+    }
+
+    #[test]
+    fn test_join_root_outside_repo() {
+        assert!(matches!(join_root(None, "src/lib.rs"), Err(BwdError::RootNotFound)));
+    }
+
+    #[test]
+    fn test_sanitize_for_filename_path() {
+        assert_eq!(sanitize_for_filename("/home/user/proj"), "home_user_proj");
+        assert_eq!(sanitize_for_filename(r"C:\Users\me"), "C_Users_me");
+        assert_eq!(sanitize_for_filename("a/b?c|d/"), "a_b_c_d");
+    }
+
+    #[test]
+    fn test_sanitize_for_filename_already_safe() {
+        assert_eq!(sanitize_for_filename("my-project_v2.log"), "my-project_v2.log");
+        assert_eq!(sanitize_for_filename("projects/my-project"), "projects_my-project");
+    }
+
+    #[test]
+    fn test_escape_control() {
+        assert_eq!(escape_control("/tmp/a\tb"), "/tmp/a\\tb");
+        assert_eq!(escape_control("/tmp/line\nbreak"), "/tmp/line\\nbreak");
+        assert_eq!(escape_control("/tmp/\x1b[31m"), "/tmp/?[31m");
+        assert_eq!(escape_control("/tmp/plain"), "/tmp/plain");
+    }
+
+    #[test]
+    fn test_resolve_git_root_canonical() {
+        let temp_dir = std::env::temp_dir();
+        let test_root = temp_dir.join(format!("bpwd_test_git_root_{}", process::id()));
+        if test_root.exists() {
+            let _ = fs::remove_dir_all(&test_root);
+        }
+        let child = test_root.join("src").join("deep");
+        fs::create_dir_all(&child).unwrap();
+        fs::create_dir(test_root.join(".git")).unwrap();
+
+        // The temp dir itself may sit behind a symlink (e.g. /tmp -> /private/tmp on macOS)
+        let canonical_root = clean_windows_path(fs::canonicalize(&test_root).unwrap());
+        assert_eq!(resolve_git_root(&child), Some(canonical_root.clone()));
+
+        #[cfg(unix)]
+        {
+            // A symlink into the repo resolves to the real toplevel, as git does
+            let link_dir = temp_dir.join(format!("bpwd_test_git_root_link_{}", process::id()));
+            let _ = fs::remove_file(&link_dir);
+            std::os::unix::fs::symlink(&child, &link_dir).unwrap();
+            assert_eq!(resolve_git_root(&link_dir), Some(canonical_root));
+            let _ = fs::remove_file(&link_dir);
+        }
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_root);
+    }
+
+    #[test]
+    fn test_path_byte_len() {
+        assert_eq!(path_byte_len(Path::new("/tmp/abc")), 8);
+        // Each of these characters takes more than one byte
+        let unicode = Path::new("/tmp/dự-án");
+        assert_eq!(path_byte_len(unicode), 13);
+        assert!(path_byte_len(unicode) > unicode.to_string_lossy().chars().count());
+    }
+
+    #[test]
+    fn test_relativize() {
+        let path = PathBuf::from("/work/proj/src");
+        assert_eq!(relativize(&path, Path::new("/work/proj")), PathBuf::from("src"));
+        assert_eq!(relativize(&path, Path::new("/work/other/docs")), PathBuf::from("../../proj/src"));
+        assert_eq!(relativize(&path, &path), PathBuf::from("."));
+    }
+
+    #[test]
+    fn test_shortest_relative_picks_nearest_base() {
+        let path = PathBuf::from("/work/proj/src/lib");
+        let bases = vec![PathBuf::from("/work/other/deep"), PathBuf::from("/work/proj/tests")];
+        assert_eq!(shortest_relative(&path, &bases), PathBuf::from("../src/lib"));
+    }
+
+    #[test]
+    fn test_require_home_unset() {
+        // Default: silent fallback, short equals the absolute path
+        let home = require_home(None, false).unwrap();
+        assert_eq!(home, None);
+        let path = PathBuf::from("/home/user/docs");
+        let map = build_json(&path, home.as_deref(), None);
+        assert_eq!(map.get("short"), map.get("path"));
+
+        // --require-home: a clear error instead
+        assert!(matches!(require_home(None, true), Err(BwdError::HomeNotFound)));
+        let set = Some(PathBuf::from("/home/user"));
+        assert_eq!(require_home(set.clone(), true).unwrap(), set);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_relative_to_bases_symlinked_base() {
+        let temp_dir = std::env::temp_dir();
+        let test_root = temp_dir.join(format!("bpwd_test_rel_link_{}", process::id()));
+        if test_root.exists() {
+            let _ = fs::remove_dir_all(&test_root);
+        }
+        let real_base = test_root.join("real").join("deep");
+        let target = test_root.join("other");
+        fs::create_dir_all(&real_base).unwrap();
+        fs::create_dir_all(&target).unwrap();
+        let link = test_root.join("link");
+        std::os::unix::fs::symlink(&real_base, &link).unwrap();
+
+        let bases = vec![link.clone()];
+        // Resolved: the base is really real/deep, two levels below the root
+        assert_eq!(relative_to_bases(&target, &bases, true), PathBuf::from("../../other"));
+        // Lexical: the base is the link, a sibling of the target
+        assert_eq!(relative_to_bases(&target, &bases, false), PathBuf::from("../other"));
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_root);
+    }
+
+    #[test]
+    fn test_path_separator() {
+        let expected = if cfg!(windows) { '\\' } else { '/' };
+        assert_eq!(path_separator(), expected);
+    }
+
+    #[test]
+    fn test_pretty_prompt_deep_home_path() {
+        let home = PathBuf::from("/home/user");
+        let path = home.join("projects/work/deep/leaf");
+        let expected = PathBuf::from("$HOME").join("…").join("deep").join("leaf");
+        assert_eq!(pretty_prompt(&path, Some(&home), 2), expected.to_string_lossy());
+
+        let outside = PathBuf::from("/var/lib/app/data/cache");
+        assert_eq!(pretty_prompt(&outside, Some(&home), 1), PathBuf::from("/…/cache").to_string_lossy());
+    }
+
+    #[test]
+    fn test_pretty_prompt_short_path() {
+        let home = PathBuf::from("/home/user");
+        let path = home.join("projects/bpwd");
+        let expected = PathBuf::from("$HOME").join("projects").join("bpwd");
+        assert_eq!(pretty_prompt(&path, Some(&home), 2), expected.to_string_lossy());
+        assert_eq!(keep_last(&path, 2), PathBuf::from("projects/bpwd"));
+    }
+
+    #[test]
+    fn test_parse_config_rejects_nul_in_target() {
+        let args: Vec<String> = vec!["bad\0path".to_string()];
+        match parse_config(&args) {
+            Err(BwdError::InvalidPath(message)) => {
+                assert!(message.contains("NUL"));
+                assert!(message.contains("bad\\0path"));
+            }
+            _ => panic!("expected InvalidPath for a target with an embedded NUL"),
+        }
+    }
+
+    #[test]
+    fn test_insert_root_paths() {
+        let root = PathBuf::from("/work/proj");
+        let path = root.join("src/api");
+        let mut map = build_json(&path, None, Some(&root));
+        insert_root_paths(&mut map, &path, Some(&root));
+
+        let relative = PathBuf::from("src/api").to_string_lossy().to_string();
+        assert_eq!(map.get("root_path"), Some(&JsonValue::String(root.to_string_lossy().to_string())));
+        assert_eq!(map.get("relative"), Some(&JsonValue::String(relative.clone())));
+        assert_eq!(map.get("root"), Some(&JsonValue::String(relative)));
+    }
+
+    /// Records copies instead of touching the OS clipboard
+    #[derive(Default)]
+    struct FakeClipboard {
+        contents: Option<String>,
+    }
+
+    impl Clipboard for FakeClipboard {
+        fn set_contents(&mut self, contents: String) -> Result<(), BwdError> {
+            self.contents = Some(contents);
+            Ok(())
+        }
+    }
 
-struct Config {
-    target: Option<String>,
-    copy: bool,
-    short: bool,
-    json: bool,
-    root: bool,
-}
+    #[test]
+    fn test_copy_stdin_verbatim() {
+        let mut clipboard = FakeClipboard::default();
+        let echoed = copy_stdin("hello world\n".as_bytes(), &mut clipboard).unwrap();
+        assert_eq!(echoed, "hello world\n");
+        assert_eq!(clipboard.contents, Some("hello world\n".to_string()));
 
-fn main() {
-    if let Err(err) = run() {
-        eprintln!("[bwd error] {}", err);
-        process::exit(1);
+        let mut clipboard = FakeClipboard::default();
+        copy_stdin("no newline".as_bytes(), &mut clipboard).unwrap();
+        assert_eq!(clipboard.contents, Some("no newline".to_string()));
     }
-}
 
-fn run() -> Result<(), BwdError> {
-    let args: Vec<String> = env::args().skip(1).collect();
+    #[test]
+    fn test_parse_config_subcommand() {
+        let args: Vec<String> = vec!["save".to_string(), "work".to_string(), "-s".to_string()];
+        let config = parse_config(&args).unwrap();
+        assert_eq!(config.command, Some(Command::Save));
+        assert_eq!(config.operands, vec!["work".to_string()]);
+        assert_eq!(config.target, None);
+        assert!(config.short);
 
-    // Check for help/version flags, but respect the -- separator.
-    let flags_end = args.iter().position(|arg| arg == "--").unwrap_or(args.len());
-    let flags_slice = &args[..flags_end];
+        // After `--`, a subcommand name is just a target
+        let args: Vec<String> = vec!["--".to_string(), "get".to_string()];
+        let config = parse_config(&args).unwrap();
+        assert_eq!(config.command, None);
+        assert_eq!(config.target, Some("get".to_string()));
 
-    if flags_slice.iter().any(|arg| arg == "-h" || arg == "--help") {
-        print_help();
-        return Ok(());
+        let args: Vec<String> = vec!["get".to_string()];
+        let config = parse_config(&args).unwrap();
+        assert!(matches!(command_operand(&config, 0), Err(BwdError::MissingValue(_))));
     }
 
-    if flags_slice.iter().any(|arg| arg == "-v" || arg == "--version") {
-        println!("{} v{}", "bwd", env!("CARGO_PKG_VERSION"));
-        return Ok(());
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("2h"), Some(Duration::from_secs(7200)));
+        assert_eq!(parse_duration("15m"), Some(Duration::from_secs(900)));
+        assert_eq!(parse_duration("90"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("1w"), Some(Duration::from_secs(604_800)));
+        assert_eq!(parse_duration("2 hours"), None);
+        assert_eq!(parse_duration("h"), None);
     }
 
-    let config = parse_config(&args);
+    #[test]
+    fn test_inline_flag_values() {
+        let args: Vec<String> = ["list", "--sort=frecency", "--tag", "a=b", "--", "--tag=x"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let config = parse_config(&args).unwrap();
+        assert_eq!(config.sort.as_deref(), Some("frecency"));
+        // Only the flag itself is split, never its separate value or anything after `--`
+        assert_eq!(config.tags, vec!["a=b".to_string()]);
+        assert_eq!(config.operands, vec!["--tag=x".to_string()]);
 
-    let cwd = env::current_dir().map_err(BwdError::Io)?;
+        // Flags without a value reject an inline one instead of dropping it
+        assert!(matches!(parse(&["--json=no"]), Err(BwdError::InvalidValue(flag, value)) if flag == "--json" && value == "no"));
+        assert!(matches!(parse(&["--stdin=", "-s"]), Err(BwdError::InvalidValue(..))));
+        assert!(parse(&["--color=never", "--abbrev=2", "--max-width=30"]).is_ok());
+        assert!(parse(&["--no-such-flag=1"]).is_ok());
+    }
 
-    let final_path = if let Some(t) = &config.target {
-        let path = cwd.join(t);
-        if !path.exists() {
-            return Err(BwdError::InvalidPath(t.to_string()));
-        }
-        clean_windows_path(fs::canonicalize(path).map_err(BwdError::Io)?)
-    } else {
-        cwd
-    };
+    #[test]
+    fn test_dash_target_means_previous() {
+        let config = parse(&["-", "-s"]).unwrap();
+        assert!(config.previous);
+        assert!(config.short);
 
-    let absolute_str = final_path.to_string_lossy().to_string();
+        // After `--` it is a directory literally named `-`
+        assert!(!parse(&["--", "-"]).unwrap().previous);
+        // ...and for subcommands it stays an operand
+        let import = parse(&["import", "-"]).unwrap();
+        assert!(!import.previous);
+        assert_eq!(import.operands, vec!["-".to_string()]);
+    }
 
-    // Determine home directory for shortening
-    let home_dir = get_home_dir();
-
-    // JSON Output Priority
-    if config.json {
-        let short_str = shorten_path(&final_path, home_dir.as_deref());
-        
-        let root_val = if let Some(root) = find_root(&final_path) {
-             let relative = final_path.strip_prefix(&root).unwrap_or(Path::new(""));
-             let s = if relative.as_os_str().is_empty() {
-                 ".".to_string()
-             } else {
-                 relative.to_string_lossy().to_string()
-             };
-             JsonValue::String(s)
-        } else {
-            JsonValue::Null
-        };
-        
+    #[test]
+    fn test_to_nuon() {
         let mut map = HashMap::new();
-        map.insert("path".to_string(), JsonValue::String(absolute_str));
-        map.insert("short".to_string(), JsonValue::String(short_str));
-        map.insert("root".to_string(), root_val);
-        
-        let json_obj = JsonValue::Object(map);
-        let json_str = json_obj.stringify().map_err(|e| BwdError::Json(format!("{:?}", e)))?;
-        println!("{}", json_str);
-        return Ok(());
+        map.insert("path".to_string(), JsonValue::String("/srv/\"odd\"\\dir".to_string()));
+        map.insert("root".to_string(), JsonValue::Null);
+        map.insert("bytes".to_string(), JsonValue::Number(15.0));
+        map.insert("two words".to_string(), JsonValue::Array(vec![JsonValue::Boolean(true)]));
+        assert_eq!(
+            to_nuon(&JsonValue::Object(map)),
+            r#"{bytes: 15, path: "/srv/\"odd\"\\dir", root: null, "two words": [true]}"#
+        );
+        assert_eq!(to_nuon(&JsonValue::String("a\u{1}".to_string())), r#""a\u{1}""#);
     }
 
-    // Short Output Priority
-    if config.short {
-        let short_str = shorten_path(&final_path, home_dir.as_deref());
-        println!("{}", short_str);
-        if config.copy {
-            cli_clipboard::set_contents(short_str).map_err(|e| BwdError::Clipboard(e.to_string()))?;
-        }
-        return Ok(());
+    #[test]
+    fn test_to_yaml() {
+        let mut inner = HashMap::new();
+        inner.insert("depth".to_string(), JsonValue::Number(2.0));
+        let mut map = HashMap::new();
+        map.insert("path".to_string(), JsonValue::String("/srv/no: \"x\"".to_string()));
+        map.insert("root".to_string(), JsonValue::Null);
+        map.insert("dirty".to_string(), JsonValue::Boolean(false));
+        map.insert("two words".to_string(), JsonValue::Array(vec![JsonValue::String("a".to_string()), JsonValue::Array(vec![])]));
+        map.insert("stats".to_string(), JsonValue::Object(inner));
+        assert_eq!(
+            to_yaml(&JsonValue::Object(map)),
+            "dirty: false\npath: \"/srv/no: \\\"x\\\"\"\nroot: null\nstats:\n  depth: 2\n\"two words\":\n  - \"a\"\n  - []\n"
+        );
     }
 
-    // Default Output Priority
-    // Note: Previously logic handled -r here. If user passed -r but NOT -j or -s, 
-    // should we still output relative path?
-    // The prompt says "Default: In đường dẫn tuyệt đối".
-    // But if explicit -r is passed, it's not "Default". 
-    // I will preserve -r behavior if explicitly requested, otherwise default to absolute.
-    let output_str = if config.root {
-         if let Some(root) = find_root(&final_path) {
-             let relative = final_path.strip_prefix(&root).unwrap_or(Path::new(""));
-             if relative.as_os_str().is_empty() {
-                 ".".to_string()
-             } else {
-                 relative.to_string_lossy().to_string()
-             }
-        } else {
-            return Err(BwdError::RootNotFound);
-        }
-    } else {
-        absolute_str
-    };
+    #[test]
+    fn test_root_search_inner_and_outer() {
+        let temp_dir = std::env::temp_dir();
+        let test_root = temp_dir.join(format!("bpwd_test_outer_{}", process::id()));
+        let _ = fs::remove_dir_all(&test_root);
+        let nested = test_root.join("vendor").join("lib");
+        let child = nested.join("src");
+        fs::create_dir_all(&child).unwrap();
+        fs::create_dir(test_root.join(".git")).unwrap();
+        fs::create_dir(nested.join(".git")).unwrap();
 
-    println!("{}", output_str);
+        let outer = RootSearch { outer: true, ..RootSearch::default() };
+        assert_eq!(RootSearch::default().find(&child), Some(nested.clone()));
+        assert_eq!(outer.find(&child), Some(test_root.clone()));
 
-    if config.copy {
-        cli_clipboard::set_contents(output_str).map_err(|e| BwdError::Clipboard(e.to_string()))?;
-    }
+        let config = parse(&["--root=outer"]).unwrap();
+        assert!(config.root && config.root_outer);
+        assert!(!parse(&["-r"]).unwrap().root_outer);
+        assert!(matches!(parse(&["--root=middle"]), Err(BwdError::InvalidValue(_, _))));
 
-    Ok(())
-}
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_root);
+    }
 
-fn parse_config(args: &[String]) -> Config {
-    let mut target = None;
-    let mut copy = false;
-    let mut short = false;
-    let mut json = false;
-    let mut root = false;
-    let mut parsing_flags = true;
+    #[test]
+    fn test_cargo_roots() {
+        let temp_dir = std::env::temp_dir();
+        let test_root = temp_dir.join(format!("bpwd_test_cargo_{}", process::id()));
+        let _ = fs::remove_dir_all(&test_root);
+        let member = test_root.join("crates").join("core");
+        let child = member.join("src");
+        fs::create_dir_all(&child).unwrap();
+        fs::write(test_root.join("Cargo.toml"), "[workspace]\nmembers = [\"crates/*\"]\n").unwrap();
+        fs::write(member.join("Cargo.toml"), "[package]\nname = \"core\"\n").unwrap();
 
-    for arg in args {
-        if parsing_flags && arg == "--" {
-            parsing_flags = false;
-            continue;
-        }
+        let search = RootSearch::default();
+        assert_eq!(cargo_roots(&child, &search), Some((member.clone(), test_root.clone())));
+        // The workspace is not looked for above a boundary
+        let bounded = RootSearch { stop_at: vec![member.clone()], ..RootSearch::default() };
+        assert_eq!(cargo_roots(&child, &bounded), Some((member.clone(), member.clone())));
+        // A package with no workspace above is its own workspace root
+        fs::write(test_root.join("Cargo.toml"), "[package]\nname = \"solo\"\n").unwrap();
+        assert_eq!(cargo_roots(&child, &search), Some((member.clone(), member.clone())));
+        assert!(declares_workspace("  [workspace.dependencies]\nserde = \"1\""));
 
-        if parsing_flags && arg.starts_with('-') {
-            match arg.as_str() {
-                "-c" | "--copy" => copy = true,
-                "-s" | "--short" => short = true,
-                "-j" | "--json" => json = true,
-                "-r" | "--root" => root = true,
-                _ => {} // Ignore unknown flags
-            }
-            continue;
-        }
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_root);
+    }
 
-        // If it's not a flag (or we stopped parsing flags), it's the target
-        if target.is_none() {
-            target = Some(arg.clone());
-        }
+    #[test]
+    fn test_render_template() {
+        let values = [("project", Some("myapp".to_string())), ("rel", Some("src/api".to_string())), ("path", None)];
+        assert_eq!(render_template("{project} › {rel}", &values).unwrap(), "myapp › src/api");
+        assert_eq!(render_template("{{{path}}}", &values).unwrap(), "{}");
+        assert!(matches!(render_template("{nope}", &values), Err(BwdError::InvalidValue(_, _))));
+        assert!(render_template("{rel", &values).is_err());
+        assert!(render_template("rel}", &values).is_err());
     }
-    Config { target, copy, short, json, root }
-}
 
-fn get_home_dir() -> Option<PathBuf> {
-    env::var("HOME").ok().map(PathBuf::from)
-        .or_else(|| env::var("USERPROFILE").ok().map(PathBuf::from))
-}
+    #[test]
+    fn test_bwd_root_metadata() {
+        let temp_dir = std::env::temp_dir();
+        let test_root = temp_dir.join(format!("bpwd_test_bwd_root_meta_{}", process::id()));
+        let _ = fs::remove_dir_all(&test_root);
+        let package = test_root.join("packages").join("api");
+        let child = package.join("src");
+        fs::create_dir_all(&child).unwrap();
+        fs::write(test_root.join(".bwd-root"), "name = \"myapp\"\naliases = [\"app\"]\nmarkers = [\"package.json\"]\n").unwrap();
+        fs::write(package.join("package.json"), "{}").unwrap();
 
-fn shorten_path(path: &Path, home: Option<&Path>) -> String {
-    if let Some(h) = home {
-        if let Ok(stripped) = path.strip_prefix(h) {
-             let replacement = if stripped.as_os_str().is_empty() {
-                 PathBuf::from("$HOME")
-             } else {
-                 PathBuf::from("$HOME").join(stripped)
-             };
-             return replacement.to_string_lossy().to_string();
-        }
+        // Aliases satisfy --assert-root
+        assert_eq!(assert_root_name(Some(test_root.clone()), "app").unwrap(), test_root);
+        assert!(assert_root_name(Some(test_root.clone()), "other").is_err());
+
+        // The project's markers replace the default ones only when allowed
+        let search = RootSearch { project_markers: true, ..RootSearch::default() };
+        assert_eq!(search.find(&child), Some(package.clone()));
+        assert_eq!(RootSearch::default().find(&child), Some(test_root.clone()));
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_root);
     }
-    path.to_string_lossy().to_string()
-}
 
-fn find_root(path: &Path) -> Option<PathBuf> {
-    let mut current = path;
-    loop {
-        if current.join(".git").exists() || current.join(".bwd-root").exists() {
-            return Some(current.to_path_buf());
-        }
-        match current.parent() {
-            Some(p) => current = p,
-            None => return None,
-        }
+    #[test]
+    fn test_root_search_pinned_root() {
+        let temp_dir = std::env::temp_dir();
+        let test_root = temp_dir.join(format!("bpwd_test_pinned_root_{}", process::id()));
+        let _ = fs::remove_dir_all(&test_root);
+        let pinned = test_root.join("workspace");
+        let child = pinned.join("src");
+        fs::create_dir_all(&child).unwrap();
+        fs::create_dir(child.join(".git")).unwrap();
+
+        let search = RootSearch { pinned: Some(pinned.clone()), ..RootSearch::default() };
+        // Markers below the pinned root are not looked at
+        assert_eq!(search.find(&child), Some(pinned.clone()));
+        let cache_file = test_root.join("cache.json");
+        assert_eq!(cached_find_root(&child, &cache_file, &search), Some(pinned.clone()));
+        assert!(!cache_file.exists());
+        // Outside of it the usual search applies
+        assert_eq!(search.find(&test_root), None);
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_root);
     }
-}
 
-/// Strip the UNC prefix (\\?\$ which is common on Windows when using canonicalize()
-fn clean_windows_path(path: PathBuf) -> PathBuf {
-    let path_str = path.to_string_lossy();
-    if path_str.starts_with(r"\\?\") {
-        PathBuf::from(&path_str[4..])
-    } else {
-        path
+    #[test]
+    fn test_template_values() {
+        let home = PathBuf::from("/home/me");
+        let root = home.join("bpwd");
+        let path = root.join("src").join("api");
+        let render = |template: &str| {
+            render_template(template, &template_values(template, &path, Some(&home), Some(&root)).unwrap()).unwrap()
+        };
+        assert_eq!(render("{root_name}:{rel}"), format!("bpwd:{}", Path::new("src").join("api").display()));
+        assert_eq!(render("{basename}"), "api");
+        assert_eq!(render("{parent}"), root.join("src").to_string_lossy());
+        assert_eq!(render("{short}"), shorten_path(&path, Some(&home)));
+        assert_eq!(render("{project}"), "bpwd");
+
+        let values = template_values("[{root}{rel}]", &path, None, None).unwrap();
+        assert_eq!(render_template("[{root}{rel}]", &values).unwrap(), "[]");
     }
-}
 
-fn print_help() {
-    println!("bwd - Better Working Directory");
-    println!("\nUsage:");
-    println!("  bwd [target] [-c] [-s] [-j] [-r]");
-    println!("\nFlags:");
-    println!("  -c, --copy     Copy to clipboard");
-    println!("  -s, --short    Shorten path (replace home with $HOME)");
-    println!("  -j, --json     Output JSON (path, short, root)");
-    println!("  -r, --root     Print path relative to project root (.git or .bwd-root)");
-    println!("  -h, --help     Show this help");
-    println!("  -v, --version  Show version");
-}
+    #[test]
+    fn test_json_text_pretty() {
+        let mut map = HashMap::new();
+        map.insert("path".to_string(), JsonValue::String("/a".to_string()));
+        let value = JsonValue::Object(map);
+        assert_eq!(json_text(&value, false).unwrap(), r#"{"path":"/a"}"#);
+        let pretty = json_text(&value, true).unwrap();
+        assert!(pretty.contains('\n'));
+        assert_eq!(pretty.parse::<JsonValue>().unwrap(), value);
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::path::PathBuf;
+        let config = parse_config(&["--json-pretty".to_string()]).unwrap();
+        assert!(config.json && config.json_pretty);
+    }
 
     #[test]
-    fn test_parse_config_defaults() {
-        let args: Vec<String> = vec![];
-        let config = parse_config(&args);
-        assert_eq!(config.target, None);
-        assert_eq!(config.copy, false);
-        assert_eq!(config.short, false);
-        assert_eq!(config.json, false);
-        assert_eq!(config.root, false);
+    fn test_file_uri() {
+        assert_eq!(file_uri("/home/me/my project/#1", false), "file:///home/me/my%20project/%231");
+        assert_eq!(file_uri("/srv/caf\u{e9}", false), "file:///srv/caf%C3%A9");
+        // A backslash is an ordinary character outside Windows
+        assert_eq!(file_uri("/tmp/a\\b", false), "file:///tmp/a%5Cb");
+        assert_eq!(file_uri(r"C:\Users\me\My Docs", true), "file:///C:/Users/me/My%20Docs");
+        assert_eq!(file_uri(r"\\server\share\x", true), "file://server/share/x");
     }
 
     #[test]
-    fn test_parse_config_short_flag() {
-        let args: Vec<String> = vec!["-s".to_string()];
-        let config = parse_config(&args);
-        assert!(config.short);
-        assert!(!config.json);
+    fn test_git_pathspec() {
+        let temp_dir = std::env::temp_dir();
+        let test_root = temp_dir.join(format!("bpwd_test_git_pathspec_{}", process::id()));
+        let _ = fs::remove_dir_all(&test_root);
+        fs::create_dir_all(test_root.join(".git")).unwrap();
+        fs::create_dir_all(test_root.join("src").join("bin")).unwrap();
+
+        assert_eq!(git_pathspec(&test_root.join("src").join("bin")).unwrap(), ":/src/bin");
+        assert_eq!(git_pathspec(&test_root).unwrap(), ":/");
+        // Glob characters are taken literally, not matched against other files
+        fs::create_dir_all(test_root.join("app").join("[id]")).unwrap();
+        assert_eq!(git_pathspec(&test_root.join("app").join("[id]")).unwrap(), ":(top,literal)app/[id]");
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_root);
     }
 
     #[test]
-    fn test_parse_config_json_flag() {
-        let args: Vec<String> = vec!["--json".to_string()];
-        let config = parse_config(&args);
-        assert!(config.json);
-        assert!(!config.short);
+    fn test_web_url() {
+        let github = "git@github.com:CodeTease/bpwd.git";
+        assert_eq!(web_url(github, "main", "src/main.rs", false).unwrap(), "https://github.com/CodeTease/bpwd/blob/main/src/main.rs");
+        assert_eq!(web_url(github, "main", "", true).unwrap(), "https://github.com/CodeTease/bpwd/tree/main");
+        assert_eq!(
+            web_url("https://gitlab.com/group/sub/tool.git", "feature/x", "docs/my notes", true).unwrap(),
+            "https://gitlab.com/group/sub/tool/-/tree/feature/x/docs/my%20notes"
+        );
+        assert_eq!(
+            web_url("ssh://git@bitbucket.org:22/team/app", "c0ffee", "lib.rs", false).unwrap(),
+            "https://bitbucket.org/team/app/src/c0ffee/lib.rs"
+        );
+        assert_eq!(
+            web_url("https://user@gitlab.example.com/ops/infra", "main", "", true).unwrap(),
+            "https://gitlab.example.com/ops/infra/-/tree/main"
+        );
+        // A custom HTTPS port serves the web pages too; an ssh one does not
+        assert_eq!(
+            web_url("https://gitlab.example.com:8443/ops/infra.git", "main", "", true).unwrap(),
+            "https://gitlab.example.com:8443/ops/infra/-/tree/main"
+        );
+        assert_eq!(
+            web_url("http://bitbucket.lan:7990/team/app", "main", "lib.rs", false).unwrap(),
+            "http://bitbucket.lan:7990/team/app/src/main/lib.rs"
+        );
+        assert_eq!(web_url("/srv/git/bpwd.git", "main", "", true), None);
+        assert_eq!(web_url("https://git.example.com/bpwd", "main", "", true), None);
     }
 
     #[test]
-    fn test_parse_config_all_flags() {
-        let args: Vec<String> = vec!["-c".to_string(), "-s".to_string(), "-j".to_string()];
-        let config = parse_config(&args);
-        assert!(config.copy);
-        assert!(config.short);
-        assert!(config.json);
+    fn test_convert_separators() {
+        assert_eq!(convert_separators(r"C:\Users\me/src", '/'), "C:/Users/me/src");
+        assert_eq!(convert_separators("/home/me/src", '\\'), r"\home\me\src");
+        assert_eq!(parse(&["--unix"]).unwrap().separator, Some('/'));
+        assert_eq!(parse(&["--unix", "--windows"]).unwrap().separator, Some('\\'));
     }
 
     #[test]
-    fn test_shorten_path_match() {
-        // Construct paths in a platform-agnostic way for testing logic
-        let home = PathBuf::from("/home/user");
-        let path = home.join("docs/project");
-        let short = shorten_path(&path, Some(&home));
-        
-        // Expected: $HOME/docs/project
-        // Note: join uses OS separator. On unix it's /, on windows \
-        // The shorten_path implementation uses PathBuf::from("$HOME").join(...)
-        // So it should match OS separator.
-        let expected = PathBuf::from("$HOME").join("docs/project").to_string_lossy().to_string();
-        assert_eq!(short, expected);
+    fn test_path_components() {
+        let root = std::path::MAIN_SEPARATOR.to_string();
+        let path = PathBuf::from(&root).join("home").join("me");
+        assert_eq!(path_components(&path), vec![root.clone(), "home".to_string(), "me".to_string()]);
+        assert_eq!(path_components(Path::new(&root)), vec![root.clone()]);
+        assert_eq!(path_components(Path::new("a/b")), vec!["a".to_string(), "b".to_string()]);
+
+        let map = build_json(&path, None, None);
+        let expected: Vec<JsonValue> = path_components(&path).into_iter().map(JsonValue::String).collect();
+        assert_eq!(map.get("components"), Some(&JsonValue::Array(expected)));
     }
 
     #[test]
-    fn test_shorten_path_exact_match() {
-        let home = PathBuf::from("/home/user");
-        let short = shorten_path(&home, Some(&home));
-        assert_eq!(short, "$HOME");
+    fn test_breadcrumb() {
+        let path = PathBuf::from(std::path::MAIN_SEPARATOR.to_string()).join("home").join("me").join("api");
+        assert_eq!(breadcrumb(&path, " › ", None), "home › me › api");
+        assert_eq!(breadcrumb(&path, "/", Some(2)), "…/me/api");
+        assert_eq!(breadcrumb(&path, " › ", Some(5)), "home › me › api");
+
+        assert_eq!(parse(&["--breadcrumb"]).unwrap().breadcrumb.as_deref(), Some(" › "));
+        let config = parse(&["--breadcrumb=:", "src"]).unwrap();
+        assert_eq!(config.breadcrumb.as_deref(), Some(":"));
+        assert_eq!(config.target.as_deref(), Some("src"));
     }
 
     #[test]
-    fn test_shorten_path_no_match() {
-        let home = PathBuf::from("/home/user");
-        let path = PathBuf::from("/var/log");
-        let short = shorten_path(&path, Some(&home));
-        assert_eq!(short, path.to_string_lossy().to_string());
+    fn test_prompt_segment() {
+        let sep = std::path::MAIN_SEPARATOR.to_string();
+        let home = PathBuf::from(&sep).join("home").join("me");
+        let root = home.join("code").join("bpwd");
+        let path = root.join("src").join("api").join("v2");
+        let join = |parts: &[&str]| parts.join(&sep);
+
+        assert_eq!(prompt_segment(&path, Some(&home), Some((&root, "myapp")), 2, None, None), join(&["myapp", "…", "api", "v2"]));
+        assert_eq!(prompt_segment(&root, Some(&home), Some((&root, "myapp")), 2, None, None), "myapp");
+        assert_eq!(prompt_segment(&path, Some(&home), None, 3, None, None), join(&["~", "…", "src", "api", "v2"]));
+        let outside = PathBuf::from(&sep).join("srv").join("data");
+        assert_eq!(prompt_segment(&outside, Some(&home), None, 2, None, None), format!("{}{}", sep, join(&["srv", "data"])));
+
+        let colored = prompt_segment(&path, None, Some((&root, "myapp")), 1, Some(&Theme::default()), None);
+        assert!(colored.starts_with("\x1b[1;34mmyapp\x1b[0m"));
+        assert!(colored.contains("\x1b[2m…\x1b[0m"));
+        // Marked for the shell, so line editing knows the escapes take no room
+        let bash = prompt_segment(&path, None, Some((&root, "myapp")), 1, Some(&Theme::default()), Some(Shell::Bash));
+        assert!(bash.starts_with("\x01\x1b[1;34m\x02myapp\x01\x1b[0m\x02"));
+        let zsh = prompt_segment(&root.join("100%"), None, Some((&root, "myapp")), 1, None, Some(Shell::Zsh));
+        assert_eq!(zsh, join(&["myapp", "100%%"]));
     }
 
     #[test]
-    fn test_shorten_path_no_home() {
-        let path = PathBuf::from("/home/user/docs");
-        let short = shorten_path(&path, None);
-        assert_eq!(short, path.to_string_lossy().to_string());
+    fn test_abbreviate_path() {
+        let sep = std::path::MAIN_SEPARATOR.to_string();
+        let home = PathBuf::from(&sep).join("home").join("me");
+        let path = home.join("projects").join("rust").join("bwd");
+        let join = |parts: &[&str]| parts.join(&sep);
+        assert_eq!(abbreviate_path(&path, Some(&home), 1), join(&["~", "p", "r", "bwd"]));
+        assert_eq!(abbreviate_path(&path, Some(&home), 2), join(&["~", "p", "rust", "bwd"]));
+        assert_eq!(abbreviate_path(&home.join(".config").join("fish"), Some(&home), 1), join(&["~", ".c", "fish"]));
+        assert_eq!(abbreviate_path(&home, Some(&home), 1), "~");
+        assert_eq!(abbreviate_path(&path, None, 1), format!("{}{}", sep, join(&["h", "m", "p", "r", "bwd"])));
+
+        assert_eq!(parse(&["--abbrev"]).unwrap().abbrev, Some(1));
+        assert_eq!(parse(&["--abbrev=3"]).unwrap().abbrev, Some(3));
+        assert!(matches!(parse(&["--abbrev=x"]), Err(BwdError::InvalidValue(_, _))));
     }
 
     #[test]
-    fn test_clean_windows_path_no_prefix() {
-        let p = PathBuf::from("/usr/bin");
-        assert_eq!(clean_windows_path(p.clone()), p);
+    fn test_fit_width() {
+        let sep = std::path::MAIN_SEPARATOR.to_string();
+        let join = |parts: &[&str]| parts.join(&sep);
+        let path = format!("{}{}", sep, join(&["home", "me", "projects", "api", "v2"]));
+        assert_eq!(fit_width(&path, 100), path);
+        let fitted = fit_width(&path, 16);
+        assert_eq!(fitted, format!("{}{}", sep, join(&["home", "…", "api", "v2"])));
+        assert!(fitted.chars().count() <= 16);
+        assert_eq!(fit_width(&path, 12), format!("{}{}", sep, join(&["home", "…", "v2"])));
+        // Too narrow for any component layout: keep the end
+        assert_eq!(fit_width(&path, 4), format!("…{}v2", sep));
+        assert_eq!(fit_width("relative", 4), "…ive");
     }
 
     #[test]
-    fn test_clean_windows_path_with_prefix() {
-        let p = PathBuf::from(r"\\?\C:\Windows");
-        let expected = PathBuf::from(r"C:\Windows");
-        assert_eq!(clean_windows_path(p), expected);
+    fn test_insert_file_info() {
+        let temp_dir = std::env::temp_dir();
+        let test_root = temp_dir.join(format!("bpwd_test_file_info_{}", process::id()));
+        let _ = fs::remove_dir_all(&test_root);
+        fs::create_dir_all(&test_root).unwrap();
+
+        let mut map = HashMap::new();
+        insert_file_info(&mut map, &test_root, &test_root);
+        assert_eq!(map["exists"], JsonValue::Boolean(true));
+        assert_eq!(map["is_dir"], JsonValue::Boolean(true));
+        assert_eq!(map["is_symlink"], JsonValue::Boolean(false));
+        assert_eq!(map["depth"], JsonValue::Number(test_root.components().filter(|c| matches!(c, Component::Normal(_))).count() as f64));
+        assert_eq!(map["basename"], JsonValue::String(format!("bpwd_test_file_info_{}", process::id())));
+
+        let missing = test_root.join("missing.txt");
+        insert_file_info(&mut map, &missing, &missing);
+        assert_eq!(map["exists"], JsonValue::Boolean(false));
+        assert_eq!(map["is_dir"], JsonValue::Boolean(false));
+
+        #[cfg(unix)]
+        {
+            let link = test_root.join("link");
+            std::os::unix::fs::symlink(&test_root, &link).unwrap();
+            insert_file_info(&mut map, &test_root, &link);
+            assert_eq!(map["is_symlink"], JsonValue::Boolean(true));
+        }
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_root);
     }
 
     #[test]
-    fn test_parse_config_target_only() {
-        let args: Vec<String> = vec!["some/path".to_string()];
-        let config = parse_config(&args);
-        assert_eq!(config.target, Some("some/path".to_string()));
+    fn test_stdin_targets() {
+        assert_eq!(stdin_targets(b"src\r\ntests\n\ndocs", false).unwrap(), vec!["src", "tests", "docs"]);
+        assert_eq!(stdin_targets(b"with\nnewline\0other\0", true).unwrap(), vec!["with\nnewline", "other"]);
+        assert_eq!(stdin_targets(b"a\0b\n", false).unwrap(), vec!["a\0b"]);
+        assert!(stdin_targets(b"", false).unwrap().is_empty());
+        // A path that is not UTF-8 is rejected, not mangled
+        let err = stdin_targets(b"caf\xe9\nok", false).unwrap_err();
+        assert!(matches!(err, BwdError::InvalidPath(ref label) if label.contains("not valid UTF-8")));
     }
 
     #[test]
-    fn test_parse_config_ignore_unknown_flags_as_target() {
-        // Unknown flags are ignored, so target remains None unless it's positional
-        // In the loop: if not parsing flags, or not starting with -, it's target.
-        // If it starts with - and is unknown, it's ignored.
-        let args: Vec<String> = vec!["-x".to_string()];
-        let config = parse_config(&args);
-        assert_eq!(config.target, None);
-        // But if we have -x followed by path?
-        let args2: Vec<String> = vec!["-x".to_string(), "path".to_string()];
-        let config2 = parse_config(&args2);
-        assert_eq!(config2.target, Some("path".to_string()));
+    fn test_batch_failed_error() {
+        let err = BwdError::BatchFailed(1, 3);
+        assert_eq!(err.kind(), "BatchFailed");
+        assert_eq!(err.to_string(), "1 of 3 targets failed");
+        let record = error_record(&BwdError::InvalidPath("nope".to_string()));
+        assert_eq!(record["error"], JsonValue::String("InvalidPath".to_string()));
     }
 
     #[test]
-    fn test_parse_config_dash_separator() {
-        let args: Vec<String> = vec!["--".to_string(), "-file".to_string()];
-        let config = parse_config(&args);
-        assert_eq!(config.target, Some("-file".to_string()));
-        assert!(!config.copy);
+    fn test_resolve_all_keeps_order() {
+        let temp_dir = std::env::temp_dir();
+        let test_root = temp_dir.join(format!("bpwd_test_resolve_all_{}", process::id()));
+        let _ = fs::remove_dir_all(&test_root);
+        let mut targets = Vec::new();
+        for i in 0..PARALLEL_THRESHOLD * 2 {
+            let dir = test_root.join(format!("d{}", i));
+            fs::create_dir_all(&dir).unwrap();
+            targets.push((Some(dir.to_string_lossy().to_string()), false));
+        }
+        targets.insert(7, (Some(test_root.join("missing").to_string_lossy().to_string()), false));
+
+        let config = parse_config(&[]).unwrap();
+        let context = Context { cwd: test_root.clone(), settings: Settings::default(), resolver: Resolver::new().cwd(&test_root) };
+        let results = resolve_all(&config, &context, &targets);
+        assert_eq!(results.len(), targets.len());
+        assert!(matches!(results[7], Err(BwdError::InvalidPath(_))));
+        for (result, (target, _)) in results.iter().zip(&targets).filter(|(result, _)| result.is_ok()) {
+            let Ok(Some((path, Entry::Text { copied, .. }))) = result else {
+                panic!("expected a text entry");
+            };
+            assert!(copied.ends_with(target.as_ref().unwrap().rsplit('/').next().unwrap()));
+            assert_eq!(path.to_string_lossy(), *copied);
+        }
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_root);
     }
 
     #[test]
-    fn test_parse_config_dash_separator_with_flags() {
-        let args: Vec<String> = vec!["-c".to_string(), "--".to_string(), "-file".to_string()];
-        let config = parse_config(&args);
-        assert_eq!(config.target, Some("-file".to_string()));
+    fn test_sort_flag_forms() {
+        let config = parse(&["--stdin", "--sort=path", "--unique"]).unwrap();
+        assert!(config.unique && config.sort.as_deref() == Some("path"));
+        // `--sort` always takes a key, whatever follows
+        let config = parse(&["src", "--sort", "name"]).unwrap();
+        assert_eq!(config.sort.as_deref(), Some("name"));
+        assert_eq!(config.target.as_deref(), Some("src"));
+        assert!(matches!(parse_config(&["a".to_string(), "--sort".to_string()]), Err(BwdError::MissingValue(_))));
+    }
+
+    #[test]
+    fn test_copy_backend_flag() {
+        let config = parse(&["-c"]).unwrap();
+        assert_eq!(config.copy_backend, None);
+        let config = parse(&["--copy=osc52"]).unwrap();
         assert!(config.copy);
+        assert_eq!(config.copy_backend, Some(clipboard::Backend::Osc52));
+        assert!(matches!(parse(&["--copy=carrier-pigeon"]), Err(BwdError::InvalidValue(_, _))));
     }
 
     #[test]
-    fn test_parse_config_flags_after_separator_are_target() {
-        let args: Vec<String> = vec!["--".to_string(), "-c".to_string()];
-        let config = parse_config(&args);
-        assert_eq!(config.target, Some("-c".to_string()));
-        assert!(!config.copy);
+    fn test_copy_format_implies_copy() {
+        let config = parse(&["-s", "--copy-format", "{path}"]).unwrap();
+        assert!(config.copy && config.short);
+        assert_eq!(config.copy_format.as_deref(), Some("{path}"));
     }
 
     #[test]
-    fn test_parse_config_root_flag() {
-        let args: Vec<String> = vec!["-r".to_string()];
-        let config = parse_config(&args);
-        assert!(config.root);
+    fn test_pasted_target() {
+        let home = PathBuf::from("/home/me");
+        let var = |name: &str| match name {
+            "PROJECTS" => Some("/srv/projects".to_string()),
+            "USERPROFILE" => Some(r"C:\Users\me".to_string()),
+            _ => None,
+        };
+        assert_eq!(pasted_target("  ~/src/api \n", Some(&home), var).unwrap(), "/home/me/src/api");
+        assert_eq!(pasted_target("\n\"$PROJECTS/bpwd\"\nsecond line", Some(&home), var).unwrap(), "/srv/projects/bpwd");
+        assert_eq!(pasted_target("'${PROJECTS}/x'", Some(&home), var).unwrap(), "/srv/projects/x");
+        assert_eq!(pasted_target(r"%USERPROFILE%\src", Some(&home), var).unwrap(), r"C:\Users\me\src");
+        // Unknown variables, stray signs and `~user` stay as written
+        assert_eq!(pasted_target("$NOPE/100%/a$", Some(&home), var).unwrap(), "$NOPE/100%/a$");
+        assert_eq!(pasted_target("~bob/x", Some(&home), var).unwrap(), "~bob/x");
+        assert!(matches!(pasted_target(" \n ", Some(&home), var), Err(BwdError::Clipboard(_))));
     }
 
     #[test]
-    fn test_find_root_git() {
+    fn test_json_record_schema() {
         let temp_dir = std::env::temp_dir();
-        let test_root = temp_dir.join(format!("bpwd_test_git_{}", process::id()));
-        if test_root.exists() {
-            let _ = fs::remove_dir_all(&test_root);
-        }
-        fs::create_dir_all(&test_root).unwrap();
-        fs::create_dir(test_root.join(".git")).unwrap();
-        
-        let child = test_root.join("subdir");
-        fs::create_dir(&child).unwrap();
+        let test_root = temp_dir.join(format!("bpwd_test_json_schema_{}", process::id()));
+        let _ = fs::remove_dir_all(&test_root);
+        fs::create_dir_all(test_root.join(".git")).unwrap();
+        fs::create_dir_all(test_root.join("src")).unwrap();
 
-        assert_eq!(find_root(&child), Some(test_root.clone()));
-        assert_eq!(find_root(&test_root), Some(test_root.clone()));
+        let root = test_root.to_string_lossy().to_string();
+        let config = parse(&["-j", "--json-root-abs", "--git", "--relative-to", &root]).unwrap();
+        let context = Context { cwd: test_root.join("src"), settings: Settings::default(), resolver: Resolver::new().cwd(test_root.join("src")) };
+        let Ok(Some((_, Entry::Record(JsonValue::Object(map))))) = resolve_target(&config, &context, None, false) else {
+            panic!("expected a JSON record");
+        };
+        assert_eq!(map.get("schema_version"), Some(&JsonValue::Number(schema::VERSION as f64)));
+        // With every optional field switched on, the record is exactly the documented schema
+        let mut keys: Vec<&str> = map.keys().map(String::as_str).collect();
+        let mut fields = schema::V1_FIELDS.to_vec();
+        keys.sort_unstable();
+        fields.sort_unstable();
+        assert_eq!(keys, fields);
 
         // Cleanup
         let _ = fs::remove_dir_all(&test_root);
     }
 
     #[test]
-    fn test_find_root_bwd() {
+    fn test_status_uses_enclosing_work_tree() {
         let temp_dir = std::env::temp_dir();
-        let test_root = temp_dir.join(format!("bpwd_test_bwd_{}", process::id()));
-        if test_root.exists() {
-            let _ = fs::remove_dir_all(&test_root);
-        }
+        let test_root = temp_dir.join(format!("bpwd_test_status_{}", process::id()));
+        let _ = fs::remove_dir_all(&test_root);
+        let app = test_root.join("app");
+        fs::create_dir_all(app.join(".git")).unwrap();
+        fs::write(test_root.join("monorepo.toml"), "").unwrap();
+        // An index tracking `gone.rs`, which has been deleted
+        let mut index = b"DIRC\0\0\0\x02\0\0\0\x01".to_vec();
+        index.extend([0; 60]);
+        index.extend(7u16.to_be_bytes());
+        index.extend(b"gone.rs\0\0\0");
+        index.extend([0; 20]);
+        fs::write(app.join(".git").join("index"), index).unwrap();
+
+        // `--status` alone implies `-j`, the only output with room for the flag
+        let config = parse(&["--status"]).unwrap();
+        assert!(config.json && config.git);
+        // The configured marker puts the root above the git work tree
+        let root_search = RootSearch { markers: vec!["monorepo.toml".to_string()], ..RootSearch::default() };
+        let context = Context { cwd: app.clone(), settings: Settings::default(), resolver: Resolver::new().cwd(&app).root_search(root_search) };
+        let Ok(Some((_, Entry::Record(JsonValue::Object(map))))) = resolve_target(&config, &context, None, false) else {
+            panic!("expected a JSON record");
+        };
+        assert_eq!(map.get("root"), Some(&JsonValue::String("app".to_string())));
+        assert_eq!(map.get("dirty"), Some(&JsonValue::Boolean(true)));
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_root);
+    }
+
+    #[test]
+    fn test_malformed_bwd_root_is_still_a_marker() {
+        let temp_dir = std::env::temp_dir();
+        let test_root = temp_dir.join(format!("bpwd_test_bwd_root_plain_{}", process::id()));
+        let _ = fs::remove_dir_all(&test_root);
+        fs::create_dir_all(test_root.join("src")).unwrap();
+        fs::write(test_root.join(".bwd-root"), "this is not toml\n").unwrap();
+        let name = test_root.file_name().unwrap().to_string_lossy().to_string();
+
+        assert_eq!(project_name(&test_root), Some(name.clone()));
+        assert_eq!(assert_root_name(Some(test_root.clone()), &name).unwrap(), test_root);
+        let values = template_values("{project}", &test_root.join("src"), None, Some(&test_root)).unwrap();
+        assert_eq!(render_template("{project}", &values).unwrap(), name);
+
+        let config = parse(&["-j"]).unwrap();
+        let context = Context { cwd: test_root.join("src"), settings: Settings::default(), resolver: Resolver::new().cwd(test_root.join("src")) };
+        let Ok(Some((_, Entry::Record(JsonValue::Object(map))))) = resolve_target(&config, &context, None, false) else {
+            panic!("expected a JSON record");
+        };
+        assert_eq!(map.get("project"), Some(&JsonValue::String(name)));
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_root);
+    }
+
+    #[test]
+    fn test_malformed_settings_fall_back_to_defaults() {
+        let file = std::env::temp_dir().join(format!("bpwd_test_bad_settings_{}.toml", process::id()));
+        fs::write(&file, "[root\nmarkers = nope\n").unwrap();
+
+        assert_eq!(load_settings_from(Some(file.clone())), Settings::default());
+        assert_eq!(load_settings_from(None), Settings::default());
+        fs::write(&file, "[root]\nmarkers = [\"package.json\"]\n").unwrap();
+        assert_eq!(load_settings_from(Some(file.clone())).root_markers, vec!["package.json".to_string()]);
+
+        // Cleanup
+        let _ = fs::remove_file(&file);
+    }
+
+    #[test]
+    fn test_malformed_project_bookmarks_are_skipped() {
+        let temp_dir = std::env::temp_dir();
+        let test_root = temp_dir.join(format!("bpwd_test_bad_project_file_{}", process::id()));
+        let _ = fs::remove_dir_all(&test_root);
         fs::create_dir_all(&test_root).unwrap();
-        fs::create_dir(test_root.join(".bwd-root")).unwrap();
-        
-        let child = test_root.join("subdir/deep");
-        fs::create_dir_all(&child).unwrap();
 
-        assert_eq!(find_root(&child), Some(test_root.clone()));
+        fs::write(test_root.join(bookmarks::PROJECT_FILE), "[bookmarks]\ndocs = \"docs\"\n").unwrap();
+        assert_eq!(project_bookmarks(&test_root).keys().collect::<Vec<_>>(), vec!["docs"]);
+        fs::write(test_root.join(bookmarks::PROJECT_FILE), "[bookmarks\ndocs = docs\n").unwrap();
+        assert!(project_bookmarks(&test_root).is_empty());
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_root);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_stop_at_dirs_are_canonical() {
+        let temp_dir = std::env::temp_dir();
+        let test_root = temp_dir.join(format!("bpwd_test_stop_at_link_{}", process::id()));
+        let _ = fs::remove_dir_all(&test_root);
+        let real_home = test_root.join("data").join("me");
+        fs::create_dir_all(real_home.join("code")).unwrap();
+        fs::create_dir(test_root.join(".git")).unwrap();
+        std::os::unix::fs::symlink(&real_home, test_root.join("home")).unwrap();
+        let real_home = fs::canonicalize(&real_home).unwrap();
+
+        let settings = Settings::parse(&format!("[root]\nstop_at = [\"{}\"]\n", test_root.join("home").display())).unwrap();
+        assert_eq!(stop_at_dirs(&settings), vec![real_home.clone()]);
+        // The boundary holds for the canonical paths the search walks
+        let search = RootSearch { stop_at: stop_at_dirs(&settings), ..RootSearch::default() };
+        assert_eq!(search.find(&real_home.join("code")), None);
 
         // Cleanup
         let _ = fs::remove_dir_all(&test_root);
     }
-}
\ No newline at end of file
+}
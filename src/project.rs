@@ -0,0 +1,115 @@
+//! Optional metadata in a `.bwd-root` file:
+//!
+//! ```toml
+//! name = "myapp"
+//! aliases = ["app", "ma"]
+//! markers = ["package.json"]
+//! ```
+//!
+//! An empty `.bwd-root` file (or a `.bwd-root` directory) is still just a
+//! marker. `markers` replaces the configured root markers for every search
+//! started inside the project.
+
+use std::fs;
+use std::path::Path;
+
+use crate::{BwdError, toml};
+
+pub const FILE_NAME: &str = ".bwd-root";
+
+#[derive(Debug, Default, PartialEq)]
+pub struct ProjectMeta {
+    pub name: Option<String>,
+    pub aliases: Vec<String>,
+    pub markers: Vec<String>,
+}
+
+impl ProjectMeta {
+    /// The metadata in `root/.bwd-root`, or defaults when it is absent or a
+    /// directory.
+    pub fn load(root: &Path) -> Result<Self, BwdError> {
+        let file = root.join(FILE_NAME);
+        if !file.is_file() {
+            return Ok(ProjectMeta::default());
+        }
+        Self::parse(&fs::read_to_string(&file)?)
+            .map_err(|err| BwdError::Toml(format!("{}: {}", file.to_string_lossy(), err)))
+    }
+
+    pub fn parse(content: &str) -> Result<Self, BwdError> {
+        let doc = toml::parse(content)?;
+        let string_array = |key: &str| match doc.root.get(key) {
+            Some(value) => value.as_string_array().ok_or_else(|| BwdError::Toml(format!("{} must be an array of strings", key))),
+            None => Ok(Vec::new()),
+        };
+        let name = match doc.root.get("name") {
+            Some(value) => Some(value.as_str().ok_or_else(|| BwdError::Toml("name must be a string".to_string()))?.to_string()),
+            None => None,
+        };
+        Ok(ProjectMeta { name, aliases: string_array("aliases")?, markers: string_array("markers")? })
+    }
+
+    /// The display name of the project at `root`: `name`, else the root's
+    /// directory name.
+    pub fn display_name(&self, root: &Path) -> Option<String> {
+        self.name.clone().or_else(|| root.file_name().map(|name| name.to_string_lossy().to_string()))
+    }
+
+    /// Whether `name` is the project's name or one of its aliases.
+    pub fn answers_to(&self, name: &str) -> bool {
+        self.name.as_deref() == Some(name) || self.aliases.iter().any(|alias| alias == name)
+    }
+}
+
+/// The project's display name: `name` from `.bwd-root`, else the root's
+/// directory name. A malformed `.bwd-root` is an error; bwd itself still
+/// treats it as a marker and falls back to the directory name.
+pub fn project_name(root: &Path) -> Result<Option<String>, BwdError> {
+    Ok(ProjectMeta::load(root)?.display_name(root))
+}
+
+/// Markers declared by the nearest `.bwd-root` among `dirs` that declares
+/// any. Unreadable or malformed files are skipped: the search falls back to
+/// the usual markers rather than failing.
+pub fn marker_override<'a>(mut dirs: impl Iterator<Item = &'a Path>) -> Option<Vec<String>> {
+    dirs.find_map(|dir| {
+        let meta = ProjectMeta::load(dir).ok()?;
+        (!meta.markers.is_empty()).then_some(meta.markers)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+
+    #[test]
+    fn test_project_meta() {
+        let meta = ProjectMeta::parse("name = \"myapp\"\naliases = [\"app\"]\nmarkers = [\"package.json\"]\n").unwrap();
+        assert_eq!(meta.name.as_deref(), Some("myapp"));
+        assert!(meta.answers_to("myapp") && meta.answers_to("app") && !meta.answers_to("other"));
+        assert_eq!(meta.markers, vec!["package.json".to_string()]);
+        assert_eq!(ProjectMeta::parse("").unwrap(), ProjectMeta::default());
+        assert!(ProjectMeta::parse("name = 1").is_err());
+
+        let temp_dir = std::env::temp_dir();
+        let test_root = temp_dir.join(format!("bpwd_test_project_meta_{}", process::id()));
+        let _ = fs::remove_dir_all(&test_root);
+        let named = test_root.join("named");
+        let plain = test_root.join("plain");
+        fs::create_dir_all(named.join("src")).unwrap();
+        fs::create_dir_all(plain.join(FILE_NAME)).unwrap();
+        fs::write(named.join(FILE_NAME), "name = \"myapp\"\nmarkers = [\"Cargo.toml\"]\n").unwrap();
+
+        assert_eq!(project_name(&named).unwrap().as_deref(), Some("myapp"));
+        assert_eq!(project_name(&plain).unwrap().as_deref(), Some("plain"));
+        fs::write(test_root.join(FILE_NAME), "this is not toml\n").unwrap();
+        assert!(matches!(project_name(&test_root), Err(BwdError::Toml(_))));
+        let src = named.join("src");
+        assert_eq!(marker_override(src.ancestors()), Some(vec!["Cargo.toml".to_string()]));
+        assert_eq!(marker_override(plain.ancestors()), None);
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_root);
+    }
+}
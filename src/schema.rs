@@ -0,0 +1,54 @@
+//! The schema of the `-j` record, which `--nu` and `--yaml` share.
+//!
+//! Every record carries `schema_version`. Within a version, fields are only
+//! ever added: none is renamed or removed, and none changes its type (a
+//! field that may be `null` says so from the start). Anything else means a
+//! new version, with the old serializer kept alongside for a release so
+//! consumers can move over. Consumers should ignore fields they don't know.
+
+use std::collections::HashMap;
+
+use tinyjson::JsonValue;
+
+/// The version this bwd writes.
+pub const VERSION: u32 = 1;
+
+/// Every field a version 1 record may hold. Some only appear with a flag
+/// (`root_path` and `relative` with `--json-root-abs`, `relative_to` with
+/// `--relative-to`, `dirty` with `--git`).
+pub const V1_FIELDS: &[&str] = &[
+    "schema_version",
+    "path",
+    "short",
+    "home_relative",
+    "root",
+    "bytes",
+    "components",
+    "root_path",
+    "relative",
+    "package_root",
+    "workspace_root",
+    "git_dir",
+    "main_repo",
+    "branch",
+    "exists",
+    "is_dir",
+    "is_symlink",
+    "depth",
+    "basename",
+    "relative_to",
+    "project",
+    "repo",
+    "dirty",
+];
+
+/// Seal the fields of a record as a version 1 object.
+pub fn v1(mut fields: HashMap<String, JsonValue>) -> JsonValue {
+    debug_assert!(
+        fields.keys().all(|key| V1_FIELDS.contains(&key.as_str())),
+        "a field missing from schema::V1_FIELDS: {:?}",
+        fields.keys().filter(|key| !V1_FIELDS.contains(&key.as_str())).collect::<Vec<_>>()
+    );
+    fields.insert("schema_version".to_string(), JsonValue::Number(VERSION as f64));
+    JsonValue::Object(fields)
+}
@@ -0,0 +1,186 @@
+//! User settings from `config.toml` in the bwd config directory:
+//!
+//! ```toml
+//! [root]
+//! markers = ["Cargo.toml", "package.json", ".git"]
+//! stop_at = ["~", "/mnt/nfs"]
+//! stop_at_mounts = false
+//!
+//! [color]
+//! mode = "auto"
+//! root = "1;35"
+//!
+//! [history]
+//! exclude = ["**/node_modules/**", "/tmp/**"]
+//!
+//! [clipboard]
+//! command = "wl-copy"
+//! ```
+//!
+//! A missing file means defaults; unknown keys are ignored.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::color::{ColorMode, Theme};
+use crate::{BwdError, glob, toml};
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Settings {
+    /// Root markers in priority order; empty means the built-in ones
+    pub root_markers: Vec<String>,
+    /// Directories the root search never climbs above; `None` means `~`
+    pub stop_at: Option<Vec<String>>,
+    /// Whether the root search stops at mount points; `None` means yes
+    pub stop_at_mounts: Option<bool>,
+    /// Glob patterns for directories the history never records
+    pub history_exclude: Vec<String>,
+    /// `--color` when not given on the command line
+    pub color_mode: Option<ColorMode>,
+    pub color: Theme,
+    /// Program `-c` pipes to instead of using the system clipboard
+    pub clipboard_command: Option<String>,
+}
+
+impl Settings {
+    pub fn load(file: &Path) -> Result<Self, BwdError> {
+        if !file.is_file() {
+            return Ok(Settings::default());
+        }
+        Self::parse(&fs::read_to_string(file)?)
+    }
+
+    pub fn parse(content: &str) -> Result<Self, BwdError> {
+        let doc = toml::parse(content)?;
+        Ok(Settings {
+            root_markers: string_array(&doc, "root", "markers")?,
+            stop_at: match doc.tables.get("root").is_some_and(|t| t.contains_key("stop_at")) {
+                true => Some(string_array(&doc, "root", "stop_at")?),
+                false => None,
+            },
+            stop_at_mounts: match doc.tables.get("root").and_then(|t| t.get("stop_at_mounts")) {
+                Some(value) => Some(value.as_bool().ok_or_else(|| BwdError::Toml("root.stop_at_mounts must be a boolean".to_string()))?),
+                None => None,
+            },
+            history_exclude: string_array(&doc, "history", "exclude")?,
+            color_mode: match string(&doc, "color", "mode")? {
+                Some(mode) => Some(ColorMode::from_name(&mode).ok_or_else(|| BwdError::Toml(format!("unknown color.mode '{}'", mode)))?),
+                None => None,
+            },
+            color: {
+                let theme = Theme::default();
+                Theme {
+                    home: string(&doc, "color", "home")?.unwrap_or(theme.home),
+                    root: string(&doc, "color", "root")?.unwrap_or(theme.root),
+                    basename: string(&doc, "color", "basename")?.unwrap_or(theme.basename),
+                }
+            },
+            clipboard_command: string(&doc, "clipboard", "command")?,
+        })
+    }
+
+    /// The root search boundaries, `~` standing for `home`. Without a
+    /// `stop_at` setting that is just `home` itself.
+    pub fn stop_at_dirs(&self, home: Option<&Path>) -> Vec<PathBuf> {
+        let Some(stop_at) = &self.stop_at else {
+            return home.map(Path::to_path_buf).into_iter().collect();
+        };
+        stop_at
+            .iter()
+            .filter_map(|dir| match (dir.strip_prefix('~'), home) {
+                (Some(rest), Some(home)) => Some(home.join(rest.trim_start_matches(['/', '\\']))),
+                (Some(_), None) => None,
+                (None, _) => Some(PathBuf::from(dir)),
+            })
+            .collect()
+    }
+
+    /// Whether `path` matches one of the history exclusion patterns. A
+    /// leading `~` in a pattern stands for `home`.
+    pub fn history_excluded(&self, path: &Path, home: Option<&Path>) -> bool {
+        let path = path.to_string_lossy();
+        self.history_exclude.iter().any(|pattern| {
+            let pattern = match (pattern.strip_prefix('~'), home) {
+                (Some(rest), Some(home)) => format!("{}{}", home.to_string_lossy(), rest),
+                _ => pattern.clone(),
+            };
+            glob::matches(&pattern, &path)
+        })
+    }
+}
+
+/// `[table] key = "..."`, or `None` when absent.
+fn string(doc: &toml::TomlDocument, table: &str, key: &str) -> Result<Option<String>, BwdError> {
+    let Some(value) = doc.tables.get(table).and_then(|t| t.get(key)) else {
+        return Ok(None);
+    };
+    value
+        .as_str()
+        .map(|s| Some(s.to_string()))
+        .ok_or_else(|| BwdError::Toml(format!("{}.{} must be a string", table, key)))
+}
+
+/// `[table] key = [...]`, or empty when absent.
+fn string_array(doc: &toml::TomlDocument, table: &str, key: &str) -> Result<Vec<String>, BwdError> {
+    let Some(value) = doc.tables.get(table).and_then(|t| t.get(key)) else {
+        return Ok(Vec::new());
+    };
+    value
+        .as_string_array()
+        .ok_or_else(|| BwdError::Toml(format!("{}.{} must be an array of strings", table, key)))
+}
+
+/// Location of the settings file inside the bwd config directory.
+pub fn default_settings_path() -> Option<PathBuf> {
+    crate::config_dir().map(|dir| dir.join("config.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_exclude() {
+        let settings = Settings::parse("[history]\nexclude = [\"**/node_modules/**\", \"~/.cache/**\"]\n").unwrap();
+        let home = PathBuf::from("/home/me");
+        assert!(settings.history_excluded(Path::new("/srv/app/node_modules/x"), Some(&home)));
+        assert!(settings.history_excluded(Path::new("/home/me/.cache"), Some(&home)));
+        assert!(!settings.history_excluded(Path::new("/home/me/src"), Some(&home)));
+
+        assert_eq!(Settings::parse("").unwrap(), Settings::default());
+        let markers = Settings::parse("[root]\nmarkers = [\"Cargo.toml\", \".git\"]\n").unwrap().root_markers;
+        assert_eq!(markers, vec!["Cargo.toml".to_string(), ".git".to_string()]);
+        assert!(Settings::parse("[history]\nexclude = \"/tmp/**\"\n").is_err());
+    }
+
+    #[test]
+    fn test_stop_at_dirs() {
+        let home = PathBuf::from("/home/me");
+        assert_eq!(Settings::default().stop_at_dirs(Some(&home)), vec![home.clone()]);
+        assert!(Settings::default().stop_at_dirs(None).is_empty());
+
+        let settings = Settings::parse("[root]\nstop_at = [\"~/work\", \"/mnt/nfs\"]\nstop_at_mounts = false\n").unwrap();
+        assert_eq!(settings.stop_at_dirs(Some(&home)), vec![home.join("work"), PathBuf::from("/mnt/nfs")]);
+        assert_eq!(settings.stop_at_mounts, Some(false));
+        // An empty list lifts the default `~` boundary
+        assert!(Settings::parse("[root]\nstop_at = []\n").unwrap().stop_at_dirs(Some(&home)).is_empty());
+        assert!(Settings::parse("[root]\nstop_at_mounts = \"no\"\n").is_err());
+    }
+
+    #[test]
+    fn test_color_settings() {
+        let settings = Settings::parse("[color]\nmode = \"auto\"\nroot = \"1;35\"\n").unwrap();
+        assert_eq!(settings.color_mode, Some(ColorMode::Auto));
+        assert_eq!(settings.color.root, "1;35");
+        assert_eq!(settings.color.home, Theme::default().home);
+        assert!(Settings::parse("[color]\nmode = \"sometimes\"\n").is_err());
+    }
+
+    #[test]
+    fn test_clipboard_command() {
+        let settings = Settings::parse("[clipboard]\ncommand = \"xclip -sel clip\"\n").unwrap();
+        assert_eq!(settings.clipboard_command.as_deref(), Some("xclip -sel clip"));
+        assert_eq!(Settings::default().clipboard_command, None);
+        assert!(Settings::parse("[clipboard]\ncommand = [\"wl-copy\"]\n").is_err());
+    }
+}
@@ -0,0 +1,321 @@
+//! Shell integration: `bwd init <shell>` prints code to `eval` that defines
+//! `bcd` (cd to whatever bwd prints) and records visits into the history.
+//! `bwd completions <shell>` prints a completion script, which asks the
+//! hidden `bwd __complete commands|flags|bookmarks` for its candidates so
+//! that bookmark names stay current.
+
+/// A shell bwd can integrate with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+impl Shell {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "bash" => Some(Shell::Bash),
+            "zsh" => Some(Shell::Zsh),
+            "fish" => Some(Shell::Fish),
+            "powershell" | "pwsh" => Some(Shell::Powershell),
+            _ => None,
+        }
+    }
+
+    /// The code printed by `bwd init`.
+    pub fn init_script(&self) -> &'static str {
+        match self {
+            Shell::Bash => BASH_INIT,
+            Shell::Zsh => ZSH_INIT,
+            Shell::Fish => FISH_INIT,
+            Shell::Powershell => POWERSHELL_INIT,
+        }
+    }
+
+    /// The code printed by `bwd completions`.
+    pub fn completion_script(&self) -> &'static str {
+        match self {
+            Shell::Bash => BASH_COMPLETION,
+            Shell::Zsh => ZSH_COMPLETION,
+            Shell::Fish => FISH_COMPLETION,
+            Shell::Powershell => POWERSHELL_COMPLETION,
+        }
+    }
+
+    /// `escape`, a terminal control sequence in a prompt, marked as taking
+    /// no room so the shell can measure the prompt: between `\001` and `\002`
+    /// for bash (what `\[` and `\]` in `PS1` turn into, and honored in a
+    /// command substitution's output as well), inside `%{ %}` for zsh. Fish
+    /// and PowerShell measure escapes themselves.
+    pub fn prompt_escape(&self, escape: &str) -> String {
+        match self {
+            Shell::Bash => format!("\x01{}\x02", escape),
+            Shell::Zsh => format!("%{{{}%}}", escape),
+            Shell::Fish | Shell::Powershell => escape.to_string(),
+        }
+    }
+
+    /// `text` shown literally in a prompt: zsh expands `%` sequences in it.
+    pub fn prompt_text(&self, text: &str) -> String {
+        match self {
+            Shell::Zsh => text.replace('%', "%%"),
+            _ => text.to_string(),
+        }
+    }
+}
+
+/// How `--escape` quotes a path so it survives interpolation into a command line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dialect {
+    /// `'...'` with `'` written as `'\''` (sh, bash, zsh)
+    Posix,
+    /// `'...'` with `\` and `'` backslash-escaped
+    Fish,
+    /// `'...'` with `'` doubled
+    Powershell,
+    /// `"..."` with `%` pulled out of the quotes and caret-escaped
+    Cmd,
+}
+
+impl Dialect {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "posix" | "sh" | "bash" | "zsh" => Some(Dialect::Posix),
+            "fish" => Some(Dialect::Fish),
+            "powershell" | "pwsh" => Some(Dialect::Powershell),
+            "cmd" => Some(Dialect::Cmd),
+            _ => None,
+        }
+    }
+
+    pub fn quote(&self, s: &str) -> String {
+        match self {
+            Dialect::Posix => format!("'{}'", s.replace('\'', r"'\''")),
+            Dialect::Fish => format!("'{}'", s.replace('\\', r"\\").replace('\'', r"\'")),
+            Dialect::Powershell => format!("'{}'", s.replace('\'', "''")),
+            Dialect::Cmd => format!("\"{}\"", s.replace('"', "\"\"").replace('%', "\"^%\"")),
+        }
+    }
+
+    /// A complete command that changes to `path`. cmd needs `/d` to switch
+    /// drives as well.
+    pub fn cd_command(&self, path: &str) -> String {
+        match self {
+            Dialect::Cmd => format!("cd /d {}", self.quote(path)),
+            _ => format!("cd {}", self.quote(path)),
+        }
+    }
+}
+
+const BASH_INIT: &str = r#"# bwd shell integration for bash. Add to ~/.bashrc:
+#   eval "$(bwd init bash)"
+
+bcd() {
+    local dir
+    dir="$(command bwd "$@")" && builtin cd -- "$dir"
+}
+
+__bwd_last_pwd=""
+__bwd_hook() {
+    if [[ "$PWD" != "$__bwd_last_pwd" ]]; then
+        __bwd_last_pwd="$PWD"
+        command bwd visit >/dev/null 2>&1
+    fi
+}
+if [[ ";${PROMPT_COMMAND[*]:-};" != *";__bwd_hook;"* ]]; then
+    PROMPT_COMMAND="__bwd_hook;${PROMPT_COMMAND:-}"
+fi
+"#;
+
+const ZSH_INIT: &str = r#"# bwd shell integration for zsh. Add to ~/.zshrc:
+#   eval "$(bwd init zsh)"
+
+bcd() {
+    local dir
+    dir="$(command bwd "$@")" && builtin cd -- "$dir"
+}
+
+__bwd_hook() {
+    command bwd visit >/dev/null 2>&1
+}
+autoload -Uz add-zsh-hook
+add-zsh-hook chpwd __bwd_hook
+"#;
+
+const FISH_INIT: &str = r#"# bwd shell integration for fish. Add to ~/.config/fish/config.fish:
+#   bwd init fish | source
+
+function bcd --description 'cd to the directory bwd prints'
+    set -l dir (command bwd $argv); or return
+    builtin cd -- $dir
+end
+
+function __bwd_hook --on-variable PWD
+    command bwd visit >/dev/null 2>&1
+end
+"#;
+
+const POWERSHELL_INIT: &str = r#"# bwd shell integration for PowerShell. Add to $PROFILE:
+#   Invoke-Expression (& bwd init powershell | Out-String)
+
+function bcd {
+    $dir = & bwd @args
+    if ($LASTEXITCODE -eq 0 -and $dir) { Set-Location -LiteralPath $dir }
+}
+
+# Wrap the prompt only once, so reloading $PROFILE doesn't wrap our own wrapper
+if (-not $global:__bwd_prompt_installed) {
+    $global:__bwd_prompt_installed = $true
+    $global:__bwd_last_pwd = $null
+    $global:__bwd_prompt = $function:prompt
+    function global:prompt {
+        $pwdPath = $PWD.ProviderPath
+        if ($pwdPath -ne $global:__bwd_last_pwd) {
+            $global:__bwd_last_pwd = $pwdPath
+            & bwd visit *> $null
+        }
+        & $global:__bwd_prompt
+    }
+}
+"#;
+
+const BASH_COMPLETION: &str = r#"# bwd completion for bash. Add to ~/.bashrc:
+#   eval "$(bwd completions bash)"
+
+_bwd() {
+    local cur="${COMP_WORDS[COMP_CWORD]}"
+    local prev="${COMP_WORDS[COMP_CWORD-1]}"
+    local IFS=$'
+'
+    COMPREPLY=()
+    if [[ "$cur" == -* ]]; then
+        COMPREPLY=($(compgen -W "$(command bwd __complete flags)" -- "$cur"))
+        return
+    fi
+    case "$prev" in
+        get|rm|mv)
+            COMPREPLY=($(compgen -W "$(command bwd __complete bookmarks)" -- "$cur"))
+            return
+            ;;
+    esac
+    if [[ $COMP_CWORD -eq 1 ]]; then
+        COMPREPLY=($(compgen -W "$(command bwd __complete commands)" -- "$cur"))
+    fi
+    COMPREPLY+=($(compgen -d -- "$cur"))
+}
+complete -o filenames -F _bwd bwd bcd
+"#;
+
+const ZSH_COMPLETION: &str = r#"#compdef bwd bcd
+# bwd completion for zsh. Add to ~/.zshrc (after compinit):
+#   eval "$(bwd completions zsh)"
+
+_bwd() {
+    local -a items
+    if [[ $PREFIX == -* ]]; then
+        items=(${(f)"$(command bwd __complete flags)"})
+        compadd -a items
+        return
+    fi
+    case ${words[CURRENT-1]} in
+        get|rm|mv)
+            items=(${(f)"$(command bwd __complete bookmarks)"})
+            compadd -a items
+            return
+            ;;
+    esac
+    if (( CURRENT == 2 )); then
+        items=(${(f)"$(command bwd __complete commands)"})
+        compadd -a items
+    fi
+    _directories
+}
+compdef _bwd bwd bcd
+"#;
+
+const FISH_COMPLETION: &str = r#"# bwd completion for fish. Add to ~/.config/fish/config.fish:
+#   bwd completions fish | source
+
+for cmd in bwd bcd
+    complete -c $cmd -f
+    complete -c $cmd -n 'string match -q -- "-*" (commandline -ct)' -a '(command bwd __complete flags)'
+    complete -c $cmd -n '__fish_is_first_token' -a '(command bwd __complete commands)'
+    complete -c $cmd -n '__fish_seen_subcommand_from get rm mv' -a '(command bwd __complete bookmarks)'
+    complete -c $cmd -a '(__fish_complete_directories (commandline -ct))'
+end
+"#;
+
+const POWERSHELL_COMPLETION: &str = r#"# bwd completion for PowerShell. Add to $PROFILE:
+#   Invoke-Expression (& bwd completions powershell | Out-String)
+
+Register-ArgumentCompleter -Native -CommandName bwd, bcd -ScriptBlock {
+    param($wordToComplete, $commandAst, $cursorPosition)
+    $words = @($commandAst.CommandElements | ForEach-Object { $_.ToString() })
+    $position = if ($wordToComplete) { $words.Count - 1 } else { $words.Count }
+    $previous = if ($position -ge 1) { $words[$position - 1] } else { '' }
+    $candidates = if ($wordToComplete -like '-*') {
+        & bwd __complete flags
+    } elseif ($previous -in 'get', 'rm', 'mv') {
+        & bwd __complete bookmarks
+    } else {
+        $dirs = Get-ChildItem -Directory -Path "$wordToComplete*" -ErrorAction SilentlyContinue | ForEach-Object { $_.Name }
+        if ($position -eq 1) { @(& bwd __complete commands) + $dirs } else { $dirs }
+    }
+    $candidates | Where-Object { $_ -like "$wordToComplete*" } | ForEach-Object {
+        [System.Management.Automation.CompletionResult]::new($_, $_, 'ParameterValue', $_)
+    }
+}
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_init_scripts() {
+        for name in ["bash", "zsh", "fish", "powershell", "pwsh"] {
+            let script = Shell::from_name(name).unwrap().init_script();
+            assert!(script.contains("bcd"), "{} defines bcd", name);
+            assert!(script.contains("bwd visit"), "{} records visits", name);
+        }
+        assert_eq!(Shell::from_name("tcsh"), None);
+        // Sourcing the init twice must not wrap the prompt in itself
+        let script = Shell::Powershell.init_script();
+        let guard = script.find("if (-not $global:__bwd_prompt_installed)").unwrap();
+        assert!(guard < script.find("$global:__bwd_prompt = $function:prompt").unwrap());
+    }
+
+    #[test]
+    fn test_dialect_quoting() {
+        let path = r"/it's $HOME/a\b";
+        assert_eq!(Dialect::Posix.quote(path), r"'/it'\''s $HOME/a\b'");
+        assert_eq!(Dialect::Fish.quote(path), r"'/it\'s $HOME/a\\b'");
+        assert_eq!(Dialect::Powershell.quote(path), r"'/it''s $HOME/a\b'");
+        assert_eq!(Dialect::Cmd.quote(r"C:\100% done"), r#""C:\100"^%" done""#);
+        assert_eq!(Dialect::Posix.cd_command("/srv/my app"), "cd '/srv/my app'");
+        assert_eq!(Dialect::Cmd.cd_command(r"D:\work"), r#"cd /d "D:\work""#);
+        assert_eq!(Dialect::from_name("zsh"), Some(Dialect::Posix));
+        assert_eq!(Dialect::from_name("csh"), None);
+    }
+
+    #[test]
+    fn test_prompt_escapes() {
+        assert_eq!(Shell::Bash.prompt_escape("\x1b[1m"), "\x01\x1b[1m\x02");
+        assert_eq!(Shell::Zsh.prompt_escape("\x1b[1m"), "%{\x1b[1m%}");
+        assert_eq!(Shell::Fish.prompt_escape("\x1b[1m"), "\x1b[1m");
+        assert_eq!(Shell::Zsh.prompt_text("100%"), "100%%");
+        assert_eq!(Shell::Bash.prompt_text("100%"), "100%");
+    }
+
+    #[test]
+    fn test_completion_scripts() {
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish, Shell::Powershell] {
+            let script = shell.completion_script();
+            for kind in ["commands", "flags", "bookmarks"] {
+                assert!(script.contains(&format!("bwd __complete {}", kind)), "{:?} completes {}", shell, kind);
+            }
+        }
+    }
+}
@@ -0,0 +1,77 @@
+//! Persistent directory stack (`bwd push` / `bwd pop` / `bwd stack`).
+//!
+//! Unlike the shell's own `pushd`, the stack lives in a file, one directory
+//! per line with the top last, so it is shared by every shell and terminal.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::BwdError;
+
+/// The stack, bottom first. A missing file is an empty stack.
+pub fn load(file: &Path) -> Result<Vec<PathBuf>, BwdError> {
+    if !file.exists() {
+        return Ok(Vec::new());
+    }
+    Ok(fs::read_to_string(file)?.lines().filter(|line| !line.is_empty()).map(PathBuf::from).collect())
+}
+
+fn save(file: &Path, stack: &[PathBuf]) -> Result<(), BwdError> {
+    let lines: String = stack.iter().map(|path| format!("{}\n", path.to_string_lossy())).collect();
+    crate::write_atomic(file, &lines)
+}
+
+/// Put `path` on top of the stack. Paths containing a newline cannot be
+/// stored and are rejected.
+pub fn push(file: &Path, path: &Path) -> Result<(), BwdError> {
+    if path.to_string_lossy().contains('\n') {
+        return Err(BwdError::InvalidPath(path.to_string_lossy().escape_debug().to_string()));
+    }
+    crate::with_lock(file, || {
+        let mut stack = load(file)?;
+        stack.push(path.to_path_buf());
+        save(file, &stack)
+    })
+}
+
+/// Take the top directory off the stack.
+pub fn pop(file: &Path) -> Result<PathBuf, BwdError> {
+    crate::with_lock(file, || {
+        let mut stack = load(file)?;
+        let top = stack.pop().ok_or(BwdError::StackEmpty)?;
+        save(file, &stack)?;
+        Ok(top)
+    })
+}
+
+/// Location of the stack file inside the bwd config directory.
+pub fn default_stack_path() -> Option<PathBuf> {
+    crate::config_dir().map(|dir| dir.join("stack"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+
+    #[test]
+    fn test_push_pop_order() {
+        let temp_dir = std::env::temp_dir();
+        let test_dir = temp_dir.join(format!("bpwd_test_stack_{}", process::id()));
+        let _ = fs::remove_dir_all(&test_dir);
+        let file = test_dir.join("stack");
+
+        assert!(load(&file).unwrap().is_empty());
+        push(&file, Path::new("/first")).unwrap();
+        push(&file, Path::new("/second dir")).unwrap();
+        assert!(push(&file, Path::new("/bad\nname")).is_err());
+        assert_eq!(load(&file).unwrap(), [PathBuf::from("/first"), PathBuf::from("/second dir")]);
+
+        assert_eq!(pop(&file).unwrap(), PathBuf::from("/second dir"));
+        assert_eq!(pop(&file).unwrap(), PathBuf::from("/first"));
+        assert!(matches!(pop(&file), Err(BwdError::StackEmpty)));
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&test_dir);
+    }
+}
@@ -0,0 +1,271 @@
+//! Just enough TOML for bwd's own files: top-level keys and `[table]`
+//! sections holding strings, integers, booleans and single-line arrays.
+//!
+//! Dotted keys, inline tables, dates and multi-line strings are not
+//! supported and are reported as errors rather than misread.
+
+use std::collections::BTreeMap;
+
+use crate::BwdError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TomlValue {
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+    Array(Vec<TomlValue>),
+}
+
+impl TomlValue {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            TomlValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            TomlValue::Boolean(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// The string items of an array, ignoring anything else.
+    pub fn as_string_array(&self) -> Option<Vec<String>> {
+        match self {
+            TomlValue::Array(items) => Some(items.iter().filter_map(|item| item.as_str()).map(String::from).collect()),
+            _ => None,
+        }
+    }
+}
+
+pub type TomlTable = BTreeMap<String, TomlValue>;
+
+/// A parsed document: keys before the first header live in `root`.
+#[derive(Debug, Default, PartialEq)]
+pub struct TomlDocument {
+    pub root: TomlTable,
+    pub tables: BTreeMap<String, TomlTable>,
+}
+
+pub fn parse(content: &str) -> Result<TomlDocument, BwdError> {
+    let mut doc = TomlDocument::default();
+    let mut current: Option<String> = None;
+
+    for (index, raw_line) in content.lines().enumerate() {
+        let error = |message: &str| BwdError::Toml(format!("line {}: {}", index + 1, message));
+        let line = raw_line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix('[') {
+            let (name, rest) = parse_key(header.trim_start()).map_err(|e| error(&e))?;
+            let rest = rest.trim_start();
+            let Some(rest) = rest.strip_prefix(']') else {
+                return Err(error("expected ']' after table name"));
+            };
+            if !is_comment_or_empty(rest) {
+                return Err(error("unexpected text after table header"));
+            }
+            doc.tables.entry(name.clone()).or_default();
+            current = Some(name);
+            continue;
+        }
+
+        let (key, rest) = parse_key(line).map_err(|e| error(&e))?;
+        let Some(rest) = rest.trim_start().strip_prefix('=') else {
+            return Err(error("expected '=' after key"));
+        };
+        let (value, rest) = parse_value(rest.trim_start()).map_err(|e| error(&e))?;
+        if !is_comment_or_empty(rest) {
+            return Err(error("unexpected text after value"));
+        }
+        let table = match &current {
+            Some(name) => doc.tables.entry(name.clone()).or_default(),
+            None => &mut doc.root,
+        };
+        table.insert(key, value);
+    }
+    Ok(doc)
+}
+
+fn is_comment_or_empty(rest: &str) -> bool {
+    let rest = rest.trim();
+    rest.is_empty() || rest.starts_with('#')
+}
+
+/// A bare (`a-z A-Z 0-9 _ -`) or quoted key, plus the unparsed remainder.
+fn parse_key(input: &str) -> Result<(String, &str), String> {
+    if input.starts_with('"') || input.starts_with('\'') {
+        let (value, rest) = parse_string(input)?;
+        return Ok((value, rest));
+    }
+    let end = input
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+        .unwrap_or(input.len());
+    if end == 0 {
+        return Err("expected a key".to_string());
+    }
+    if input[end..].starts_with('.') {
+        return Err("dotted keys are not supported".to_string());
+    }
+    Ok((input[..end].to_string(), &input[end..]))
+}
+
+fn parse_value(input: &str) -> Result<(TomlValue, &str), String> {
+    if input.starts_with('"') || input.starts_with('\'') {
+        let (value, rest) = parse_string(input)?;
+        return Ok((TomlValue::String(value), rest));
+    }
+    if let Some(mut rest) = input.strip_prefix('[') {
+        let mut items = Vec::new();
+        loop {
+            rest = rest.trim_start();
+            if let Some(after) = rest.strip_prefix(']') {
+                return Ok((TomlValue::Array(items), after));
+            }
+            let (item, after) = parse_value(rest)?;
+            items.push(item);
+            rest = after.trim_start();
+            if let Some(after) = rest.strip_prefix(',') {
+                rest = after;
+            } else if !rest.starts_with(']') {
+                return Err("expected ',' or ']' in array".to_string());
+            }
+        }
+    }
+
+    let end = input.find(|c: char| c == ',' || c == ']' || c == '#' || c.is_whitespace()).unwrap_or(input.len());
+    let (token, rest) = input.split_at(end);
+    match token {
+        "true" => Ok((TomlValue::Boolean(true), rest)),
+        "false" => Ok((TomlValue::Boolean(false), rest)),
+        _ => token
+            .replace('_', "")
+            .parse::<i64>()
+            .map(|n| (TomlValue::Integer(n), rest))
+            .map_err(|_| format!("unsupported value '{}'", token)),
+    }
+}
+
+/// A basic (`"..."`, with escapes) or literal (`'...'`) string.
+fn parse_string(input: &str) -> Result<(String, &str), String> {
+    let mut chars = input.char_indices();
+    let quote = match chars.next() {
+        Some((_, c @ ('"' | '\''))) => c,
+        _ => return Err("expected a string".to_string()),
+    };
+    let mut value = String::new();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            c if c == quote => return Ok((value, &input[i + 1..])),
+            '\\' if quote == '"' => {
+                let escaped = match chars.next().map(|(_, e)| e) {
+                    Some('"') => '"',
+                    Some('\\') => '\\',
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('r') => '\r',
+                    Some('b') => '\u{8}',
+                    Some('f') => '\u{c}',
+                    Some(u @ ('u' | 'U')) => {
+                        let len = if u == 'u' { 4 } else { 8 };
+                        let hex: String = chars.by_ref().take(len).map(|(_, h)| h).collect();
+                        u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| format!("invalid unicode escape '\\{}{}'", u, hex))?
+                    }
+                    _ => return Err("invalid escape sequence".to_string()),
+                };
+                value.push(escaped);
+            }
+            c => value.push(c),
+        }
+    }
+    Err("unterminated string".to_string())
+}
+
+/// Render `s` as a TOML basic string.
+pub fn quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04X}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Render `name` as a key, quoting it only when it is not a valid bare key.
+pub fn key(name: &str) -> String {
+    let bare = !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if bare { name.to_string() } else { quote(name) }
+}
+
+/// Render a list of strings as a single-line array.
+pub fn string_array(items: &[String]) -> String {
+    let quoted: Vec<String> = items.iter().map(|item| quote(item)).collect();
+    format!("[{}]", quoted.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tables_and_values() {
+        let doc = parse(
+            r#"
+# leading comment
+version = 1
+enabled = true
+
+[work]
+path = "/home/user/work" # trailing comment
+tags = ["job", 'literal\n', ]
+
+["my project"]
+path = "C:\\Users\\me\\my project"
+"#,
+        )
+        .unwrap();
+        assert_eq!(doc.root.get("version"), Some(&TomlValue::Integer(1)));
+        assert_eq!(doc.root.get("enabled"), Some(&TomlValue::Boolean(true)));
+
+        let work = &doc.tables["work"];
+        assert_eq!(work["path"].as_str(), Some("/home/user/work"));
+        assert_eq!(
+            work["tags"].as_string_array(),
+            Some(vec!["job".to_string(), "literal\\n".to_string()])
+        );
+        assert_eq!(doc.tables["my project"]["path"].as_str(), Some(r"C:\Users\me\my project"));
+    }
+
+    #[test]
+    fn test_parse_errors_name_the_line() {
+        let err = parse("[ok]\npath = /unquoted").unwrap_err();
+        assert!(matches!(&err, BwdError::Toml(message) if message.starts_with("line 2")));
+        assert!(parse("a.b = 1").is_err());
+        assert!(parse("name = \"open").is_err());
+    }
+
+    #[test]
+    fn test_quote_and_key_roundtrip() {
+        let tricky = "say \"hi\"\\\tnow";
+        let doc = parse(&format!("{} = {}", key("odd key"), quote(tricky))).unwrap();
+        assert_eq!(doc.root["odd key"].as_str(), Some(tricky));
+        assert_eq!(key("plain_key-1"), "plain_key-1");
+        assert_eq!(string_array(&["a".to_string(), "b\"".to_string()]), r#"["a", "b\""]"#);
+    }
+}
@@ -0,0 +1,100 @@
+//! Translation between WSL and Windows paths, following `wslpath`: drive
+//! `C:` is mounted at `<root>c`, where the root is `/mnt/` unless
+//! `/etc/wsl.conf` sets `[automount] root`. Linux paths outside the mounts
+//! are reached from Windows through `\\wsl.localhost\<distro>`.
+//!
+//! MSYS2/Git Bash (`/c/...`) and Cygwin (`/cygdrive/c/...`) mount drives the
+//! same way under a different root.
+
+use std::env;
+use std::fs;
+
+const DEFAULT_MOUNT_ROOT: &str = "/mnt/";
+
+/// The drive mount root from `/etc/wsl.conf`, always ending in `/`.
+pub fn mount_root() -> String {
+    parse_mount_root(&fs::read_to_string("/etc/wsl.conf").unwrap_or_default())
+}
+
+/// `root` in the `[automount]` section of a `wsl.conf`. The file is INI, so
+/// values may or may not be quoted.
+fn parse_mount_root(conf: &str) -> String {
+    let mut in_automount = false;
+    for line in conf.lines().map(str::trim) {
+        if let Some(section) = line.strip_prefix('[') {
+            in_automount = section.trim_end_matches(']').trim().eq_ignore_ascii_case("automount");
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        if in_automount && key.trim() == "root" {
+            let value = value.split('#').next().unwrap_or("").trim().trim_matches(|c| c == '"' || c == '\'');
+            if value.starts_with('/') {
+                return format!("{}/", value.trim_end_matches('/'));
+            }
+        }
+    }
+    DEFAULT_MOUNT_ROOT.to_string()
+}
+
+/// `C:\Users\me` (or `C:/Users/me`) as `<root>c/Users/me`; `None` for
+/// anything that is not an absolute drive path.
+pub fn to_wsl(path: &str, root: &str) -> Option<String> {
+    let bytes = path.as_bytes();
+    let is_drive = bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':';
+    let rest = path.get(2..).filter(|rest| is_drive && (rest.is_empty() || rest.starts_with(['\\', '/'])))?;
+    let drive = (bytes[0] as char).to_ascii_lowercase();
+    let rest = rest.replace('\\', "/");
+    Some(format!("{}{}{}", root, drive, rest.trim_end_matches('/')))
+}
+
+/// `<root>c/Users/me` as `C:\Users\me`. Other absolute paths go through
+/// `\\wsl.localhost\<distro>`, which needs `distro` (`WSL_DISTRO_NAME`).
+pub fn to_windows(path: &str, root: &str, distro: Option<&str>) -> Option<String> {
+    if let Some(mounted) = path.strip_prefix(root) {
+        let (drive, rest) = mounted.split_at(mounted.find('/').unwrap_or(mounted.len()));
+        if drive.len() == 1 && drive.chars().all(|c| c.is_ascii_alphabetic()) {
+            let rest = if rest.is_empty() { "\\".to_string() } else { rest.replace('/', "\\") };
+            return Some(format!("{}:{}", drive.to_ascii_uppercase(), rest));
+        }
+    }
+    if !path.starts_with('/') {
+        return None;
+    }
+    Some(format!("\\\\wsl.localhost\\{}{}", distro?, path.replace('/', "\\")))
+}
+
+/// The running distribution's name, as WSL sets it for every process.
+pub fn distro_name() -> Option<String> {
+    env::var("WSL_DISTRO_NAME").ok().filter(|name| !name.is_empty())
+}
+
+/// Drive mount root of MSYS2 and Git Bash.
+pub const MSYS_ROOT: &str = "/";
+/// Drive mount root of Cygwin.
+pub const CYGWIN_ROOT: &str = "/cygdrive/";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wsl_translation() {
+        assert_eq!(parse_mount_root(""), "/mnt/");
+        assert_eq!(parse_mount_root("[automount]\nenabled = true\nroot = /windir # custom\n"), "/windir/");
+        assert_eq!(parse_mount_root("[network]\nroot = /x/\n"), "/mnt/");
+
+        assert_eq!(to_wsl(r"C:\Users\me", "/mnt/").as_deref(), Some("/mnt/c/Users/me"));
+        assert_eq!(to_wsl("D:/", "/windir/").as_deref(), Some("/windir/d"));
+        assert_eq!(to_wsl("relative\\x", "/mnt/"), None);
+        assert_eq!(to_wsl("C:relative", "/mnt/"), None);
+        assert_eq!(to_wsl(r"C:\foo", MSYS_ROOT).as_deref(), Some("/c/foo"));
+        assert_eq!(to_wsl(r"C:\foo", CYGWIN_ROOT).as_deref(), Some("/cygdrive/c/foo"));
+
+        assert_eq!(to_windows("/mnt/c/Users/me", "/mnt/", None).as_deref(), Some(r"C:\Users\me"));
+        assert_eq!(to_windows("/mnt/d", "/mnt/", None).as_deref(), Some(r"D:\"));
+        assert_eq!(to_windows("/home/me", "/mnt/", Some("Ubuntu")).as_deref(), Some(r"\\wsl.localhost\Ubuntu\home\me"));
+        assert_eq!(to_windows("/mnt/wsl/x", "/mnt/", None), None);
+    }
+}